@@ -0,0 +1,761 @@
+//! A textual form of Maru bytecode.
+//!
+//! Each line holds one instruction, written as its lowercase mnemonic
+//! followed by its operands, or a block label:
+//!
+//! ```text
+//! .tag Some 1
+//! block0:
+//!     load32 r1, 10
+//!     adds r3, r1, r2
+//!     call 4 (r1, +r3)
+//!     if r0, @block1, @block2+8
+//!     switch r1 { 0 -> @block1, 7 -> @block2, _ -> @block3 }
+//!     match r0 { Some -> @block1, 0 -> @block2 }
+//! ```
+//!
+//! Registers are written `r<n>`, globals `g<n>` and branches
+//! `@block<n>` with an optional byte offset. A `+` in front of a call
+//! argument sets `increment_ref`. Match tags are numbers or names
+//! declared with `.tag`. Integer immediates and switch cases may be
+//! negative, and are stored in two's complement at their width. Comments
+//! start with `;`.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    CallArgument, DecodeError, DecodedInstruction, Encoding, Id, Instruction, Instructions, JumpBranch, MatchCase,
+    NumericType, Register, SwitchCase,
+};
+
+impl fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DecodedInstruction as D;
+        if let D::StartBlock { id } = self {
+            return write!(f, "block{}:", id);
+        }
+        write!(f, "{}", self.instruction().mnemonic())?;
+        match self {
+            D::Load8 { dst, value } => write!(f, " r{}, {}", dst, value),
+            D::Load16 { dst, value } => write!(f, " r{}, {}", dst, value),
+            D::Load32 { dst, value } => write!(f, " r{}, {}", dst, value),
+            D::Load64 { dst, value } => write!(f, " r{}, {}", dst, value),
+            D::Loadf32 { dst, value } if value.is_nan() => write!(f, " r{}, {:#x}", dst, value.to_bits()),
+            D::Loadf32 { dst, value } => write!(f, " r{}, {:?}", dst, value),
+            D::Loadf64 { dst, value } if value.is_nan() => write!(f, " r{}, {:#x}", dst, value.to_bits()),
+            D::Loadf64 { dst, value } => write!(f, " r{}, {:?}", dst, value),
+            D::Copy { dst, src }
+            | D::Clone { dst, src }
+            | D::Move { dst, src }
+            | D::FetchRef { dst, src }
+            | D::Unary { dst, src, .. } => write!(f, " r{}, r{}", dst, src),
+            D::Clear { register } | D::Destroy { register } | D::Forget { register } | D::MakeShared { register } => {
+                write!(f, " r{}", register)
+            }
+            D::LoadReturn { dst } => write!(f, " r{}", dst),
+            D::SetGlobal { global, src } => write!(f, " g{}, r{}", global, src),
+            D::CopyGlobal { dst, global } | D::CloneGlobal { dst, global } => write!(f, " r{}, g{}", dst, global),
+            D::Binary { dst, lhs, rhs, .. } => write!(f, " r{}, r{}, r{}", dst, lhs, rhs),
+            D::Convert { dst, src, ty, .. } => write!(f, " r{}, r{}, {}", dst, src, ty.name()),
+            D::FmaF { dst, lhs, rhs, addend } => write!(f, " r{}, r{}, r{}, r{}", dst, lhs, rhs, addend),
+            D::Handle { effect, handler, body } => write!(f, " {}, r{}, r{}", effect, handler, body),
+            D::Resume { continuation, value } => write!(f, " r{}, r{}", continuation, value),
+            D::DropContinuation { continuation } => write!(f, " r{}", continuation),
+            D::CreateArray { dst, len: a, fill: b }
+            | D::GetElement { dst, array: a, index: b }
+            | D::SetElement { array: dst, index: a, src: b } => write!(f, " r{}, r{}, r{}", dst, a, b),
+            D::ArrayLength { dst, array } => write!(f, " r{}, r{}", dst, array),
+            D::SliceArray { dst, array, start, end } => write!(f, " r{}, r{}, r{}, r{}", dst, array, start, end),
+            D::CopyElements { dst, dst_start, src, src_start, count } => {
+                write!(f, " r{}, r{}, r{}, r{}, r{}", dst, dst_start, src, src_start, count)
+            }
+            D::LoadString { dst, string } => write!(f, " r{}, {}", dst, string),
+            D::ConcatString { dst, lhs: a, rhs: b }
+            | D::CompareString { dst, lhs: a, rhs: b }
+            | D::StringByte { dst, string: a, index: b }
+            | D::StringChar { dst, string: a, index: b } => write!(f, " r{}, r{}, r{}", dst, a, b),
+            D::StringLength { dst, src } => write!(f, " r{}, r{}", dst, src),
+            D::SliceString { dst, string, start, end } => write!(f, " r{}, r{}, r{}, r{}", dst, string, start, end),
+            D::CreateObject { dst, type_id, variant } => write!(f, " r{}, {}, {}", dst, type_id, variant),
+            D::GetField { dst, object, field }
+            | D::CopyField { dst, object, field }
+            | D::TakeField { dst, object, field } => write!(f, " r{}, r{}, {}", dst, object, field),
+            D::SetField { object, field, src }
+            | D::MoveField { object, field, src }
+            | D::PlaceField { object, field, src } => write!(f, " r{}, {}, r{}", object, field, src),
+            D::Call { function, arguments }
+            | D::CallTail { function, arguments }
+            | D::Perform { effect: function, arguments } => {
+                write!(f, " {} ", function)?;
+                write_arguments(f, arguments)
+            }
+            D::Invoke { closure, arguments } | D::InvokeTail { closure, arguments } => {
+                write!(f, " r{} ", closure)?;
+                write_arguments(f, arguments)
+            }
+            D::Return { src } => write!(f, " r{}", src),
+            D::ReturnTail | D::ReturnUnit | D::ReturnTailUnit => Ok(()),
+            D::CreateClosure { dst, function, captures } => {
+                write!(f, " r{}, {} ", dst, function)?;
+                write_arguments(f, captures)
+            }
+            D::CreateFnObject { dst, function } => write!(f, " r{}, {}", dst, function),
+            D::Jump { branch } => write!(f, " {}", Branch(branch)),
+            D::If { condition, then_branch, else_branch } => {
+                write!(f, " r{}, {}, {}", condition, Branch(then_branch), Branch(else_branch))
+            }
+            D::Switch { src, default, cases } => {
+                write!(f, " r{} {{ ", src)?;
+                for case in cases {
+                    write!(f, "{} -> {}, ", case.value, Branch(&case.branch))?;
+                }
+                write!(f, "_ -> {} }}", Branch(default))
+            }
+            D::Match { src, cases } => write_match(f, *src, cases, &Tags::new()),
+            D::StartBlock { .. } => unreachable!(),
+        }
+    }
+}
+
+/// An instruction printed with the tag names in `tags`.
+struct Tagged<'a>(&'a DecodedInstruction, &'a Tags);
+
+impl fmt::Display for Tagged<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            DecodedInstruction::Match { src, cases } => {
+                write!(f, "{}", Instruction::Match.mnemonic())?;
+                write_match(f, *src, cases, self.1)
+            }
+            instruction => write!(f, "{}", instruction),
+        }
+    }
+}
+
+fn write_match(f: &mut fmt::Formatter<'_>, src: Register, cases: &[MatchCase], tags: &Tags) -> fmt::Result {
+    write!(f, " r{} {{", src)?;
+    for (i, case) in cases.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "," };
+        match tags.name(case.tag) {
+            Some(name) => write!(f, "{} {} -> {}", separator, name, Branch(&case.branch))?,
+            None => write!(f, "{} {} -> {}", separator, case.tag, Branch(&case.branch))?,
+        }
+    }
+    write!(f, " }}")
+}
+
+/// The tag names declared by `.tag` lines, in the order they were declared.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tags {
+    names: Vec<(String, Id)>,
+}
+
+impl Tags {
+    pub fn new() -> Self {
+        Tags { names: Vec::new() }
+    }
+
+    /// Declares `name` as `tag`, replacing an earlier declaration of `name`.
+    pub fn declare(&mut self, name: String, tag: Id) {
+        self.names.retain(|(declared, _)| *declared != name);
+        self.names.push((name, tag));
+    }
+
+    /// The tag declared as `name`.
+    pub fn get(&self, name: &str) -> Option<Id> {
+        self.names.iter().find(|(declared, _)| declared == name).map(|(_, tag)| *tag)
+    }
+
+    /// The name `tag` is printed as, if exactly one name was declared for it.
+    pub fn name(&self, tag: Id) -> Option<&str> {
+        let mut names = self.names.iter().filter(|(_, declared)| *declared == tag);
+        match (names.next(), names.next()) {
+            (Some((name, _)), None) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Id)> {
+        self.names.iter().map(|(name, tag)| (name.as_str(), *tag))
+    }
+}
+
+struct Branch<'a>(&'a JumpBranch);
+
+impl fmt::Display for Branch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.offset {
+            0 => write!(f, "@block{}", self.0.block_id),
+            offset => write!(f, "@block{}{:+}", self.0.block_id, offset),
+        }
+    }
+}
+
+fn write_arguments(f: &mut fmt::Formatter<'_>, arguments: &[CallArgument]) -> fmt::Result {
+    write!(f, "(")?;
+    for (i, argument) in arguments.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        let increment = if argument.increment_ref { "+" } else { "" };
+        write!(f, "{}r{}", increment, argument.register)?;
+    }
+    write!(f, ")")
+}
+
+/// Prints a function body as assembly, one instruction per line.
+pub fn disassemble(bytes: &[u8]) -> Result<String, DecodeError> {
+    disassemble_with(bytes, Encoding::Fixed)
+}
+
+/// Prints a function body laid out in `encoding` as assembly.
+pub fn disassemble_with(bytes: &[u8], encoding: Encoding) -> Result<String, DecodeError> {
+    disassemble_tagged(bytes, encoding, &Tags::new())
+}
+
+/// Prints a function body laid out in `encoding` as assembly, declaring
+/// `tags` first and naming the match cases with them.
+pub fn disassemble_tagged(bytes: &[u8], encoding: Encoding, tags: &Tags) -> Result<String, DecodeError> {
+    let mut output = String::new();
+    for (name, tag) in tags.iter() {
+        output.push_str(&format!(".tag {} {}\n", name, tag));
+    }
+    for instruction in Instructions::with_encoding(bytes, encoding) {
+        let (_, instruction) = instruction?;
+        if !matches!(instruction, DecodedInstruction::StartBlock { .. }) {
+            output.push_str("    ");
+        }
+        output.push_str(&Tagged(&instruction, tags).to_string());
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// An error in assembly source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    /// The line the error is on, starting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+/// Assembles source text into a function body.
+pub fn assemble(source: &str) -> Result<Box<[u8]>, AssemblyError> {
+    assemble_with(source, Encoding::Fixed)
+}
+
+/// Assembles source text into a function body laid out in `encoding`.
+pub fn assemble_with(source: &str, encoding: Encoding) -> Result<Box<[u8]>, AssemblyError> {
+    assemble_tagged(source, encoding).map(|(bytes, _)| bytes)
+}
+
+/// Assembles source text like `assemble_with`, also returning the tag names
+/// it declared so `disassemble_tagged` can print it back.
+pub fn assemble_tagged(source: &str, encoding: Encoding) -> Result<(Box<[u8]>, Tags), AssemblyError> {
+    let mnemonics = Instruction::all().map(|instruction| (instruction.mnemonic(), instruction)).collect();
+    let mut tags = Tags::new();
+    let mut bytes = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: String| AssemblyError { line: line_number, message };
+        let text = line.split(';').next().unwrap_or_default();
+        let tokens = tokenize(text).map_err(error)?;
+        if tokens.is_empty() {
+            continue;
+        }
+        let mut parser = Parser { tokens, position: 0, tags: &tags };
+        match parser.line(&mnemonics).map_err(error)? {
+            Line::Tag(name, tag) => tags.declare(name, tag),
+            Line::Instruction(instruction) => instruction.encode_with(&mut bytes, encoding),
+        }
+    }
+    Ok((bytes.into_boxed_slice(), tags))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(String),
+    Punct(char),
+    Arrow,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let signed_number = (c == '+' || c == '-') && chars.get(i + 1).is_some_and(char::is_ascii_digit);
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && chars.get(i + 1) == Some(&'>') {
+            tokens.push(Token::Arrow);
+            i += 2;
+        } else if c.is_ascii_digit() || signed_number {
+            let start = i;
+            i += 1;
+            while i < chars.len() {
+                let c = chars[i];
+                let exponent_sign = (c == '+' || c == '-') && matches!(chars[i - 1], 'e' | 'E')
+                    && !chars[start..i].contains(&'x');
+                if c.is_ascii_alphanumeric() || c == '.' || c == '_' || exponent_sign {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Number(chars[start..i].iter().collect()));
+        } else if c.is_alphabetic() || c == '_' || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+        } else if ",(){}@:+-".contains(c) {
+            tokens.push(Token::Punct(c));
+            i += 1;
+        } else {
+            return Err(format!("unexpected character `{}`", c));
+        }
+    }
+    Ok(tokens)
+}
+
+enum Line {
+    Tag(String, Id),
+    Instruction(DecodedInstruction),
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    tags: &'a Tags,
+}
+
+impl Parser<'_> {
+    fn line(&mut self, mnemonics: &HashMap<&str, Instruction>) -> Result<Line, String> {
+        let word = self.word()?;
+        if word == ".tag" {
+            let name = self.word()?;
+            let tag = self.number()?;
+            self.end()?;
+            return Ok(Line::Tag(name, tag));
+        }
+        if let Some(id) = word.strip_prefix("block") && self.peek() == Some(&Token::Punct(':')) {
+            let id = parse_integer(id)?;
+            self.position += 1;
+            self.end()?;
+            return Ok(Line::Instruction(DecodedInstruction::StartBlock { id }));
+        }
+        let instruction = *mnemonics.get(word.as_str()).ok_or(format!("unknown mnemonic `{}`", word))?;
+        let decoded = self.operands(instruction)?;
+        self.end()?;
+        Ok(Line::Instruction(decoded))
+    }
+
+    fn operands(&mut self, instruction: Instruction) -> Result<DecodedInstruction, String> {
+        use DecodedInstruction as D;
+        use Instruction::*;
+        let decoded = match instruction {
+            Load8 => D::Load8 { dst: self.register()?, value: self.comma_then(|parser| parser.immediate(8))? as u8 },
+            Load16 => {
+                D::Load16 { dst: self.register()?, value: self.comma_then(|parser| parser.immediate(16))? as u16 }
+            }
+            Load32 => {
+                D::Load32 { dst: self.register()?, value: self.comma_then(|parser| parser.immediate(32))? as u32 }
+            }
+            Load64 => D::Load64 { dst: self.register()?, value: self.comma_then(|parser| parser.immediate(64))? },
+            Loadf32 => {
+                let dst = self.register()?;
+                self.punct(',')?;
+                let value = self.float(32, |bits| f32::from_bits(bits as u32), |text| text.parse::<f32>())?;
+                D::Loadf32 { dst, value }
+            }
+            Loadf64 => {
+                let dst = self.register()?;
+                self.punct(',')?;
+                D::Loadf64 { dst, value: self.float(64, f64::from_bits, |text| text.parse::<f64>())? }
+            }
+            Copy => D::Copy { dst: self.register()?, src: self.comma_then(Self::register)? },
+            Clone => D::Clone { dst: self.register()?, src: self.comma_then(Self::register)? },
+            Move => D::Move { dst: self.register()?, src: self.comma_then(Self::register)? },
+            Clear => D::Clear { register: self.register()? },
+            Destroy => D::Destroy { register: self.register()? },
+            Forget => D::Forget { register: self.register()? },
+            LoadReturn => D::LoadReturn { dst: self.register()? },
+            FetchRef => D::FetchRef { dst: self.register()?, src: self.comma_then(Self::register)? },
+            MakeShared => D::MakeShared { register: self.register()? },
+            SetGlobal => D::SetGlobal { global: self.global()?, src: self.comma_then(Self::register)? },
+            CopyGlobal => D::CopyGlobal { dst: self.register()?, global: self.comma_then(Self::global)? },
+            CloneGlobal => D::CloneGlobal { dst: self.register()?, global: self.comma_then(Self::global)? },
+            AddU | SubU | MulU | DivU | RemU | AddS | SubS | MulS | DivS | RemS | AddF | SubF | MulF
+            | DivF | And | Or | Xor | ShiftLeft | LogicalShiftRight | ArithmeticShiftRight | EqI | NeqI
+            | EqF | NeqF | LtU | GtU | LteU | GteU | LtS | GtS | LteS | GteS | LtF | GtF | LteF | GteF
+            | CheckedAddU | CheckedSubU | CheckedMulU | CheckedAddS | CheckedSubS | CheckedMulS | CheckedDivS
+            | SaturatingAddU | SaturatingSubU | SaturatingMulU | SaturatingAddS | SaturatingSubS | SaturatingMulS
+            | SaturatingDivS | RotateLeft | RotateRight | RemF | MinF | MaxF | CopySignF => {
+                D::Binary {
+                    instruction,
+                    dst: self.register()?,
+                    lhs: self.comma_then(Self::register)?,
+                    rhs: self.comma_then(Self::register)?,
+                }
+            }
+            Not | ByteSwap | IsNull | IsNaN | IsInfinity | PopCount | CountLeadingZeros | CountTrailingZeros
+            | BitReverse | NegF | AbsF | SqrtF | FloorF | CeilF | TruncF | RoundF => {
+                D::Unary { instruction, dst: self.register()?, src: self.comma_then(Self::register)? }
+            }
+            CreateObject => D::CreateObject {
+                dst: self.register()?,
+                type_id: self.comma_then(Self::number)?,
+                variant: self.comma_then(Self::number)?,
+            },
+            GetField | CopyField | TakeField => {
+                let dst = self.register()?;
+                let object = self.comma_then(Self::register)?;
+                let field = self.comma_then(Self::number)?;
+                match instruction {
+                    GetField => D::GetField { dst, object, field },
+                    CopyField => D::CopyField { dst, object, field },
+                    _ => D::TakeField { dst, object, field },
+                }
+            }
+            SetField | MoveField | PlaceField => {
+                let object = self.register()?;
+                let field = self.comma_then(Self::number)?;
+                let src = self.comma_then(Self::register)?;
+                match instruction {
+                    SetField => D::SetField { object, field, src },
+                    MoveField => D::MoveField { object, field, src },
+                    _ => D::PlaceField { object, field, src },
+                }
+            }
+            Call => D::Call { function: self.number()?, arguments: self.arguments()? },
+            CallTail => D::CallTail { function: self.number()?, arguments: self.arguments()? },
+            Invoke => D::Invoke { closure: self.register()?, arguments: self.arguments()? },
+            InvokeTail => D::InvokeTail { closure: self.register()?, arguments: self.arguments()? },
+            Return => D::Return { src: self.register()? },
+            ReturnTail => D::ReturnTail,
+            ReturnUnit => D::ReturnUnit,
+            ReturnTailUnit => D::ReturnTailUnit,
+            CreateClosure => D::CreateClosure {
+                dst: self.register()?,
+                function: self.comma_then(Self::number)?,
+                captures: self.arguments()?,
+            },
+            CreateFnObject => D::CreateFnObject { dst: self.register()?, function: self.comma_then(Self::number)? },
+            Jump => D::Jump { branch: self.branch()? },
+            If => D::If {
+                condition: self.register()?,
+                then_branch: self.comma_then(Self::branch)?,
+                else_branch: self.comma_then(Self::branch)?,
+            },
+            Switch => {
+                let src = self.register()?;
+                let mut default = None;
+                let mut cases = Vec::new();
+                self.cases(|parser| {
+                    if parser.peek() == Some(&Token::Word("_".to_string())) {
+                        parser.position += 1;
+                        parser.arrow()?;
+                        if default.replace(parser.branch()?).is_some() {
+                            return Err("switch has more than one default case".to_string());
+                        }
+                    } else {
+                        let value = parser.immediate(64)?;
+                        parser.arrow()?;
+                        cases.push(SwitchCase { value, branch: parser.branch()? });
+                    }
+                    Ok(())
+                })?;
+                let default = default.ok_or("switch is missing a `_` default case")?;
+                D::Switch { src, default, cases }
+            }
+            Match => {
+                let src = self.register()?;
+                let mut cases = Vec::new();
+                self.cases(|parser| {
+                    let tag = parser.tag()?;
+                    parser.arrow()?;
+                    cases.push(MatchCase { tag, branch: parser.branch()? });
+                    Ok(())
+                })?;
+                D::Match { src, cases }
+            }
+            StartBlock => D::StartBlock { id: self.number()? },
+            SignExtend | ZeroExtend | Truncate | IntToFloatS | IntToFloatU | FloatToIntSat | FloatToInt
+            | FloatToFloat => D::Convert {
+                instruction,
+                dst: self.register()?,
+                src: self.comma_then(Self::register)?,
+                ty: self.comma_then(Self::numeric_type)?,
+            },
+            FmaF => D::FmaF {
+                dst: self.register()?,
+                lhs: self.comma_then(Self::register)?,
+                rhs: self.comma_then(Self::register)?,
+                addend: self.comma_then(Self::register)?,
+            },
+            Handle => D::Handle {
+                effect: self.number()?,
+                handler: self.comma_then(Self::register)?,
+                body: self.comma_then(Self::register)?,
+            },
+            Perform => D::Perform { effect: self.number()?, arguments: self.arguments()? },
+            Resume => D::Resume { continuation: self.register()?, value: self.comma_then(Self::register)? },
+            DropContinuation => D::DropContinuation { continuation: self.register()? },
+            CreateArray => D::CreateArray {
+                dst: self.register()?,
+                len: self.comma_then(Self::register)?,
+                fill: self.comma_then(Self::register)?,
+            },
+            GetElement => D::GetElement {
+                dst: self.register()?,
+                array: self.comma_then(Self::register)?,
+                index: self.comma_then(Self::register)?,
+            },
+            SetElement => D::SetElement {
+                array: self.register()?,
+                index: self.comma_then(Self::register)?,
+                src: self.comma_then(Self::register)?,
+            },
+            ArrayLength => D::ArrayLength { dst: self.register()?, array: self.comma_then(Self::register)? },
+            SliceArray => D::SliceArray {
+                dst: self.register()?,
+                array: self.comma_then(Self::register)?,
+                start: self.comma_then(Self::register)?,
+                end: self.comma_then(Self::register)?,
+            },
+            CopyElements => D::CopyElements {
+                dst: self.register()?,
+                dst_start: self.comma_then(Self::register)?,
+                src: self.comma_then(Self::register)?,
+                src_start: self.comma_then(Self::register)?,
+                count: self.comma_then(Self::register)?,
+            },
+            LoadString => D::LoadString { dst: self.register()?, string: self.comma_then(Self::number)? },
+            ConcatString => D::ConcatString {
+                dst: self.register()?,
+                lhs: self.comma_then(Self::register)?,
+                rhs: self.comma_then(Self::register)?,
+            },
+            StringLength => D::StringLength { dst: self.register()?, src: self.comma_then(Self::register)? },
+            StringByte => D::StringByte {
+                dst: self.register()?,
+                string: self.comma_then(Self::register)?,
+                index: self.comma_then(Self::register)?,
+            },
+            StringChar => D::StringChar {
+                dst: self.register()?,
+                string: self.comma_then(Self::register)?,
+                index: self.comma_then(Self::register)?,
+            },
+            SliceString => D::SliceString {
+                dst: self.register()?,
+                string: self.comma_then(Self::register)?,
+                start: self.comma_then(Self::register)?,
+                end: self.comma_then(Self::register)?,
+            },
+            CompareString => D::CompareString {
+                dst: self.register()?,
+                lhs: self.comma_then(Self::register)?,
+                rhs: self.comma_then(Self::register)?,
+            },
+        };
+        Ok(decoded)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.tokens.get(self.position).cloned().ok_or("unexpected end of line")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn end(&self) -> Result<(), String> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(format!("unexpected {:?} at end of line", token)),
+        }
+    }
+
+    fn punct(&mut self, expected: char) -> Result<(), String> {
+        match self.next()? {
+            Token::Punct(c) if c == expected => Ok(()),
+            token => Err(format!("expected `{}` but found {:?}", expected, token)),
+        }
+    }
+
+    fn arrow(&mut self) -> Result<(), String> {
+        match self.next()? {
+            Token::Arrow => Ok(()),
+            token => Err(format!("expected `->` but found {:?}", token)),
+        }
+    }
+
+    fn comma_then<T>(&mut self, parse: fn(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        self.punct(',')?;
+        parse(self)
+    }
+
+    fn word(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Word(word) => Ok(word),
+            token => Err(format!("expected a word but found {:?}", token)),
+        }
+    }
+
+    fn prefixed(&mut self, prefix: &str, what: &str) -> Result<u32, String> {
+        let word = self.word()?;
+        let number = word.strip_prefix(prefix).ok_or(format!("expected a {} but found `{}`", what, word))?;
+        parse_integer(number)
+    }
+
+    fn register(&mut self) -> Result<Register, String> {
+        self.prefixed("r", "register")
+    }
+
+    fn global(&mut self) -> Result<Id, String> {
+        self.prefixed("g", "global")
+    }
+
+    fn numeric_type(&mut self) -> Result<NumericType, String> {
+        let word = self.word()?;
+        NumericType::ALL.into_iter().find(|ty| ty.name() == word).ok_or(format!("unknown numeric type `{}`", word))
+    }
+
+    fn number<T: TryFrom<u64>>(&mut self) -> Result<T, String> {
+        match self.next()? {
+            Token::Number(text) => parse_integer(&text),
+            token => Err(format!("expected a number but found {:?}", token)),
+        }
+    }
+
+    /// Parses an integer of `width` bits, which may be negative.
+    fn immediate(&mut self, width: u32) -> Result<u64, String> {
+        let text = match self.next()? {
+            Token::Number(text) => text,
+            token => return Err(format!("expected a number but found {:?}", token)),
+        };
+        let mask = u64::MAX >> (64 - width);
+        let value = match text.strip_prefix('-') {
+            Some(magnitude) => parse_integer::<u64>(magnitude)
+                .ok()
+                .filter(|magnitude| *magnitude <= 1 << (width - 1))
+                .map(|magnitude| magnitude.wrapping_neg() & mask),
+            None => parse_integer::<u64>(&text).ok().filter(|value| *value <= mask),
+        };
+        value.ok_or(format!("invalid or out of range number `{}`", text))
+    }
+
+    /// Parses a float, or the bits of a float of `width` bits in hex.
+    fn float<T, E>(
+        &mut self,
+        width: u32,
+        from_bits: fn(u64) -> T,
+        parse: fn(&str) -> Result<T, E>,
+    ) -> Result<T, String> {
+        let negative = self.peek() == Some(&Token::Punct('-'));
+        if negative {
+            self.position += 1;
+        }
+        let text = match self.next()? {
+            Token::Number(text) | Token::Word(text) => text,
+            token => return Err(format!("expected a float but found {:?}", token)),
+        };
+        if let Some(hex) = text.strip_prefix("0x") {
+            let bits = u64::from_str_radix(hex, 16).map_err(|_| format!("invalid float bits `{}`", text))?;
+            if width < 64 && bits >> width != 0 {
+                return Err(format!("float bits `{}` are wider than {} bits", text, width));
+            }
+            return Ok(from_bits(bits));
+        }
+        let text = if negative { format!("-{}", text) } else { text };
+        parse(&text).map_err(|_| format!("invalid float `{}`", text))
+    }
+
+    fn tag(&mut self) -> Result<Id, String> {
+        match self.next()? {
+            Token::Number(text) => parse_integer(&text),
+            Token::Word(name) => self.tags.get(&name).ok_or(format!("unknown tag `{}`", name)),
+            token => Err(format!("expected a tag but found {:?}", token)),
+        }
+    }
+
+    fn branch(&mut self) -> Result<JumpBranch, String> {
+        self.punct('@')?;
+        let block_id = self.prefixed("block", "block")?;
+        let offset = match self.peek() {
+            Some(Token::Number(text)) if text.starts_with(['+', '-']) => {
+                let text = text.clone();
+                self.position += 1;
+                text.parse::<i32>().map_err(|_| format!("invalid branch offset `{}`", text))?
+            }
+            _ => 0,
+        };
+        Ok(JumpBranch { block_id, offset })
+    }
+
+    fn arguments(&mut self) -> Result<Vec<CallArgument>, String> {
+        self.punct('(')?;
+        let mut arguments = Vec::new();
+        if self.peek() == Some(&Token::Punct(')')) {
+            self.position += 1;
+            return Ok(arguments);
+        }
+        loop {
+            let increment_ref = self.peek() == Some(&Token::Punct('+'));
+            if increment_ref {
+                self.position += 1;
+            }
+            arguments.push(CallArgument { increment_ref, register: self.register()? });
+            match self.next()? {
+                Token::Punct(',') => {}
+                Token::Punct(')') => return Ok(arguments),
+                token => return Err(format!("expected `,` or `)` but found {:?}", token)),
+            }
+        }
+    }
+
+    /// Parses `{ case, case, ... }`.
+    fn cases(&mut self, mut case: impl FnMut(&mut Self) -> Result<(), String>) -> Result<(), String> {
+        self.punct('{')?;
+        if self.peek() == Some(&Token::Punct('}')) {
+            self.position += 1;
+            return Ok(());
+        }
+        loop {
+            case(self)?;
+            match self.next()? {
+                Token::Punct(',') if self.peek() == Some(&Token::Punct('}')) => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                Token::Punct(',') => {}
+                Token::Punct('}') => return Ok(()),
+                token => return Err(format!("expected `,` or `}}` but found {:?}", token)),
+            }
+        }
+    }
+}
+
+fn parse_integer<T: TryFrom<u64>>(text: &str) -> Result<T, String> {
+    let value = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse::<u64>().ok(),
+    };
+    value.and_then(|value| T::try_from(value).ok())
+        .ok_or(format!("invalid or out of range number `{}`", text))
+}
@@ -0,0 +1,358 @@
+//! Control-flow graphs of function bodies.
+//!
+//! A function body is split into basic blocks at every `StartBlock`, at
+//! every branch target and after every instruction that ends a block.
+//! Basic blocks are numbered in the order they appear in the body, so the
+//! entry block is always block 0.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    ops::Range,
+};
+
+use crate::{DecodeError, DecodedInstruction, Encoding, Id, Instructions, JumpBranch};
+
+/// An error in the control flow of a function body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgError {
+    Decode(DecodeError),
+    /// Two `StartBlock`s share an id.
+    DuplicateBlock { offset: usize, block_id: Id },
+    UnknownBlock { offset: usize, block_id: Id },
+    /// The branch does not land on an instruction.
+    BranchOutOfRange { offset: usize, block_id: Id, branch_offset: i32 },
+}
+
+impl fmt::Display for CfgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CfgError::Decode(error) => write!(f, "{}", error),
+            CfgError::DuplicateBlock { offset, block_id } => {
+                write!(f, "block {} at offset {} was already started", block_id, offset)
+            }
+            CfgError::UnknownBlock { offset, block_id } => {
+                write!(f, "branch at offset {} targets unknown block {}", offset, block_id)
+            }
+            CfgError::BranchOutOfRange { offset, block_id, branch_offset } => write!(
+                f,
+                "branch at offset {} to block {}{:+} does not land on an instruction",
+                offset, block_id, branch_offset,
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CfgError {}
+
+impl From<DecodeError> for CfgError {
+    fn from(error: DecodeError) -> Self {
+        CfgError::Decode(error)
+    }
+}
+
+/// A straight-line run of instructions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// The indices of the block's instructions in `Cfg::instructions`.
+    pub instructions: Range<usize>,
+    /// The byte offset of the first instruction.
+    pub start: usize,
+    /// The byte offset one past the last instruction.
+    pub end: usize,
+    pub successors: Vec<usize>,
+    pub predecessors: Vec<usize>,
+}
+
+/// A natural loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    /// The block every entry into the loop goes through.
+    pub header: usize,
+    /// The blocks of the loop in ascending order, including the header.
+    pub blocks: Vec<usize>,
+    /// The innermost loop containing this one, as an index into `Cfg::loops`.
+    pub parent: Option<usize>,
+    /// How many loops contain this one, counting itself.
+    pub depth: usize,
+}
+
+/// The control-flow graph of a function body.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    instructions: Vec<(usize, DecodedInstruction)>,
+    blocks: Vec<BasicBlock>,
+    /// The immediate dominator of each block. The entry block and unreachable
+    /// blocks have none.
+    dominators: Vec<Option<usize>>,
+    /// Outer loops come before the loops they contain.
+    loops: Vec<Loop>,
+}
+
+impl Cfg {
+    pub fn new(code: &[u8]) -> Result<Self, CfgError> {
+        Self::with_encoding(code, Encoding::Fixed)
+    }
+
+    /// Builds the graph of a function body laid out in `encoding`.
+    pub fn with_encoding(code: &[u8], encoding: Encoding) -> Result<Self, CfgError> {
+        let instructions = Instructions::with_encoding(code, encoding).collect::<Result<Vec<_>, _>>()?;
+        let mut cfg = Cfg { instructions, blocks: Vec::new(), dominators: Vec::new(), loops: Vec::new() };
+        cfg.split_blocks(code.len())?;
+        cfg.compute_dominators();
+        cfg.compute_loops();
+        Ok(cfg)
+    }
+
+    /// The decoded instructions of the body with their offsets.
+    pub fn instructions(&self) -> &[(usize, DecodedInstruction)] {
+        &self.instructions
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    /// The instructions of one basic block.
+    pub fn block_instructions(&self, block: usize) -> &[(usize, DecodedInstruction)] {
+        &self.instructions[self.blocks[block].instructions.clone()]
+    }
+
+    /// The basic block containing the instruction at `offset`.
+    pub fn block_at(&self, offset: usize) -> Option<usize> {
+        let block = self.blocks.partition_point(|block| block.end <= offset);
+        self.blocks.get(block).filter(|block| block.start <= offset).map(|_| block)
+    }
+
+    pub fn is_reachable(&self, block: usize) -> bool {
+        block == 0 || self.dominators[block].is_some()
+    }
+
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        self.dominators[block]
+    }
+
+    /// Whether every path from the entry to `block` goes through `dominator`.
+    pub fn dominates(&self, dominator: usize, block: usize) -> bool {
+        if !self.is_reachable(block) {
+            return false;
+        }
+        let mut current = Some(block);
+        while let Some(block) = current {
+            if block == dominator {
+                return true;
+            }
+            current = self.dominators[block];
+        }
+        false
+    }
+
+    pub fn loops(&self) -> &[Loop] {
+        &self.loops
+    }
+
+    /// The innermost loop containing `block`, as an index into `loops`.
+    pub fn innermost_loop(&self, block: usize) -> Option<usize> {
+        (0..self.loops.len()).rev().find(|&index| self.loops[index].blocks.binary_search(&block).is_ok())
+    }
+
+    /// The number of loops containing `block`.
+    pub fn loop_depth(&self, block: usize) -> usize {
+        self.innermost_loop(block).map_or(0, |index| self.loops[index].depth)
+    }
+
+    fn split_blocks(&mut self, len: usize) -> Result<(), CfgError> {
+        let mut block_starts = HashMap::new();
+        let mut index_of = HashMap::new();
+        for (index, (offset, instruction)) in self.instructions.iter().enumerate() {
+            index_of.insert(*offset, index);
+            if let DecodedInstruction::StartBlock { id } = instruction {
+                let next = self.instructions.get(index + 1).map_or(len, |(offset, _)| *offset);
+                if block_starts.insert(*id, next).is_some() {
+                    return Err(CfgError::DuplicateBlock { offset: *offset, block_id: *id });
+                }
+            }
+        }
+
+        let mut leaders = BTreeSet::new();
+        let mut targets = Vec::with_capacity(self.instructions.len());
+        for (index, (offset, instruction)) in self.instructions.iter().enumerate() {
+            if index == 0 || matches!(instruction, DecodedInstruction::StartBlock { .. }) {
+                leaders.insert(index);
+            }
+            if instruction.instruction().is_terminator() && index + 1 < self.instructions.len() {
+                leaders.insert(index + 1);
+            }
+            let mut resolved = Vec::new();
+            for branch in branches(instruction) {
+                let start = *block_starts
+                    .get(&branch.block_id)
+                    .ok_or(CfgError::UnknownBlock { offset: *offset, block_id: branch.block_id })?;
+                let target = start.checked_add_signed(branch.offset as isize)
+                    .and_then(|target| index_of.get(&target))
+                    .ok_or(CfgError::BranchOutOfRange {
+                        offset: *offset,
+                        block_id: branch.block_id,
+                        branch_offset: branch.offset,
+                    })?;
+                // `StartBlock` does nothing, so a branch to the instruction
+                // after one enters the block that starts there.
+                let target = match target.checked_sub(1).map(|index| &self.instructions[index].1) {
+                    Some(DecodedInstruction::StartBlock { .. }) => target - 1,
+                    _ => *target,
+                };
+                leaders.insert(target);
+                resolved.push(target);
+            }
+            targets.push(resolved);
+        }
+
+        let leaders = leaders.into_iter().collect::<Vec<_>>();
+        let block_of = |index: usize| leaders.binary_search(&index).expect("branch targets start blocks");
+        for (block, &first) in leaders.iter().enumerate() {
+            let last = leaders.get(block + 1).map_or(self.instructions.len(), |next| *next) - 1;
+            let mut successors = Vec::new();
+            for target in &targets[last] {
+                let target = block_of(*target);
+                if !successors.contains(&target) {
+                    successors.push(target);
+                }
+            }
+            if !self.instructions[last].1.instruction().is_terminator() && block + 1 < leaders.len() && !successors.contains(&(block + 1)) {
+                successors.push(block + 1);
+            }
+            self.blocks.push(BasicBlock {
+                instructions: first..last + 1,
+                start: self.instructions[first].0,
+                end: self.instructions.get(last + 1).map_or(len, |(offset, _)| *offset),
+                successors,
+                predecessors: Vec::new(),
+            });
+        }
+        for block in 0..self.blocks.len() {
+            for successor in self.blocks[block].successors.clone() {
+                self.blocks[successor].predecessors.push(block);
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes immediate dominators with the Cooper, Harvey and Kennedy
+    /// iteration over reverse postorder.
+    fn compute_dominators(&mut self) {
+        self.dominators = vec![None; self.blocks.len()];
+        if self.blocks.is_empty() {
+            return;
+        }
+        let order = self.reverse_postorder();
+        let mut rank = vec![usize::MAX; self.blocks.len()];
+        for (index, block) in order.iter().enumerate() {
+            rank[*block] = index;
+        }
+        // The entry points at itself while iterating so walks stop there.
+        self.dominators[0] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &order[1..] {
+                let mut dominator = None;
+                for &predecessor in &self.blocks[block].predecessors {
+                    if self.dominators[predecessor].is_none() {
+                        continue;
+                    }
+                    dominator = Some(match dominator {
+                        None => predecessor,
+                        Some(dominator) => self.intersect(&rank, dominator, predecessor),
+                    });
+                }
+                if dominator != self.dominators[block] {
+                    self.dominators[block] = dominator;
+                    changed = true;
+                }
+            }
+        }
+        self.dominators[0] = None;
+    }
+
+    fn intersect(&self, rank: &[usize], mut a: usize, mut b: usize) -> usize {
+        while a != b {
+            while rank[a] > rank[b] {
+                a = self.dominators[a].expect("processed blocks have a dominator");
+            }
+            while rank[b] > rank[a] {
+                b = self.dominators[b].expect("processed blocks have a dominator");
+            }
+        }
+        a
+    }
+
+    /// The blocks reachable from the entry in reverse postorder.
+    fn reverse_postorder(&self) -> Vec<usize> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut order = Vec::new();
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            match self.blocks[block].successors.get(next) {
+                Some(&successor) => {
+                    stack.push((block, next + 1));
+                    if !visited[successor] {
+                        visited[successor] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => order.push(block),
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Finds the natural loop of every back edge, merging loops that share
+    /// a header, and nests them.
+    fn compute_loops(&mut self) {
+        let mut bodies: Vec<(usize, BTreeSet<usize>)> = Vec::new();
+        for block in 0..self.blocks.len() {
+            for &header in &self.blocks[block].successors {
+                if !self.dominates(header, block) {
+                    continue;
+                }
+                let index = match bodies.iter().position(|(other, _)| *other == header) {
+                    Some(index) => index,
+                    None => {
+                        bodies.push((header, BTreeSet::from([header])));
+                        bodies.len() - 1
+                    }
+                };
+                let body = &mut bodies[index].1;
+                let mut stack = vec![block];
+                while let Some(block) = stack.pop() {
+                    if body.insert(block) {
+                        stack.extend(self.blocks[block].predecessors.iter().filter(|&&p| self.is_reachable(p)));
+                    }
+                }
+            }
+        }
+
+        // Sorting by size puts every loop after the loops containing it.
+        bodies.sort_by(|(a_header, a), (b_header, b)| b.len().cmp(&a.len()).then(a_header.cmp(b_header)));
+        for (header, blocks) in bodies {
+            let parent = (0..self.loops.len()).rev().find(|&index| self.loops[index].blocks.binary_search(&header).is_ok());
+            let depth = parent.map_or(1, |parent| self.loops[parent].depth + 1);
+            self.loops.push(Loop { header, blocks: blocks.into_iter().collect(), parent, depth });
+        }
+    }
+}
+
+fn branches(instruction: &DecodedInstruction) -> Vec<JumpBranch> {
+    use DecodedInstruction as D;
+    match instruction {
+        D::Jump { branch } => vec![*branch],
+        D::If { then_branch, else_branch, .. } => vec![*then_branch, *else_branch],
+        D::Switch { default, cases, .. } => {
+            cases.iter().map(|case| case.branch).chain(std::iter::once(*default)).collect()
+        }
+        D::Match { cases, .. } => cases.iter().map(|case| case.branch).collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,495 @@
+use crate::{
+    CallArgument, DecodeError, Encoding, Id, Instruction, JumpBranch, MatchCase, NumericType, OperandKind, Register,
+    SwitchCase, decode_call_argument, decode_id, decode_instruction, decode_jump_branch, decode_length,
+    decode_match_case, decode_numeric_type, decode_switch_case,
+};
+
+/// An instruction together with its operands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodedInstruction {
+    Load8 { dst: Register, value: u8 },
+    Load16 { dst: Register, value: u16 },
+    Load32 { dst: Register, value: u32 },
+    Load64 { dst: Register, value: u64 },
+    Loadf32 { dst: Register, value: f32 },
+    Loadf64 { dst: Register, value: f64 },
+    Copy { dst: Register, src: Register },
+    Clone { dst: Register, src: Register },
+    Move { dst: Register, src: Register },
+    Clear { register: Register },
+    Destroy { register: Register },
+    Forget { register: Register },
+    LoadReturn { dst: Register },
+    FetchRef { dst: Register, src: Register },
+    MakeShared { register: Register },
+    SetGlobal { global: Id, src: Register },
+    CopyGlobal { dst: Register, global: Id },
+    CloneGlobal { dst: Register, global: Id },
+    /// The arithmetic, bitwise, shift and comparison instructions.
+    Binary { instruction: Instruction, dst: Register, lhs: Register, rhs: Register },
+    /// The bitwise and test instructions with a single operand, such as `Not` and `IsNaN`.
+    Unary { instruction: Instruction, dst: Register, src: Register },
+    CreateObject { dst: Register, type_id: Id, variant: Id },
+    GetField { dst: Register, object: Register, field: Id },
+    CopyField { dst: Register, object: Register, field: Id },
+    TakeField { dst: Register, object: Register, field: Id },
+    SetField { object: Register, field: Id, src: Register },
+    MoveField { object: Register, field: Id, src: Register },
+    PlaceField { object: Register, field: Id, src: Register },
+    Call { function: Id, arguments: Vec<CallArgument> },
+    CallTail { function: Id, arguments: Vec<CallArgument> },
+    Invoke { closure: Register, arguments: Vec<CallArgument> },
+    InvokeTail { closure: Register, arguments: Vec<CallArgument> },
+    Return { src: Register },
+    ReturnTail,
+    ReturnUnit,
+    ReturnTailUnit,
+    CreateClosure { dst: Register, function: Id, captures: Vec<CallArgument> },
+    CreateFnObject { dst: Register, function: Id },
+    Jump { branch: JumpBranch },
+    If { condition: Register, then_branch: JumpBranch, else_branch: JumpBranch },
+    Switch { src: Register, default: JumpBranch, cases: Vec<SwitchCase> },
+    Match { src: Register, cases: Vec<MatchCase> },
+    StartBlock { id: Id },
+    /// The conversion instructions.
+    Convert { instruction: Instruction, dst: Register, src: Register, ty: NumericType },
+    FmaF { dst: Register, lhs: Register, rhs: Register, addend: Register },
+    Handle { effect: Id, handler: Register, body: Register },
+    Perform { effect: Id, arguments: Vec<CallArgument> },
+    Resume { continuation: Register, value: Register },
+    DropContinuation { continuation: Register },
+    CreateArray { dst: Register, len: Register, fill: Register },
+    GetElement { dst: Register, array: Register, index: Register },
+    SetElement { array: Register, index: Register, src: Register },
+    ArrayLength { dst: Register, array: Register },
+    SliceArray { dst: Register, array: Register, start: Register, end: Register },
+    CopyElements { dst: Register, dst_start: Register, src: Register, src_start: Register, count: Register },
+    LoadString { dst: Register, string: Id },
+    ConcatString { dst: Register, lhs: Register, rhs: Register },
+    StringLength { dst: Register, src: Register },
+    StringByte { dst: Register, string: Register, index: Register },
+    StringChar { dst: Register, string: Register, index: Register },
+    SliceString { dst: Register, string: Register, start: Register, end: Register },
+    CompareString { dst: Register, lhs: Register, rhs: Register },
+}
+
+impl DecodedInstruction {
+    /// Decodes the instruction at the start of `bytes`.
+    ///
+    /// Returns the instruction and the number of bytes it occupies.
+    pub fn decode(bytes: &[u8]) -> Result<(DecodedInstruction, usize), DecodeError> {
+        Self::decode_at(bytes, 0)
+    }
+
+    /// Decodes the instruction at `offset` in `bytes`.
+    ///
+    /// Returns the instruction and the number of bytes it occupies.
+    /// Offsets in errors are relative to the start of `bytes`.
+    pub fn decode_at(bytes: &[u8], offset: usize) -> Result<(DecodedInstruction, usize), DecodeError> {
+        Self::decode_with(bytes, offset, Encoding::Fixed)
+    }
+
+    /// Decodes the instruction at `offset` in `bytes` laid out in `encoding`.
+    pub fn decode_with(bytes: &[u8], offset: usize, encoding: Encoding) -> Result<(DecodedInstruction, usize), DecodeError> {
+        let mut reader = Reader { bytes, offset, encoding };
+        let instruction = reader.instruction()?;
+        let decoded = reader.operands(instruction)?;
+        Ok((decoded, reader.offset - offset))
+    }
+
+    /// The opcode of this instruction.
+    pub fn instruction(&self) -> Instruction {
+        use DecodedInstruction as D;
+        match self {
+            D::Load8 { .. } => Instruction::Load8,
+            D::Load16 { .. } => Instruction::Load16,
+            D::Load32 { .. } => Instruction::Load32,
+            D::Load64 { .. } => Instruction::Load64,
+            D::Loadf32 { .. } => Instruction::Loadf32,
+            D::Loadf64 { .. } => Instruction::Loadf64,
+            D::Copy { .. } => Instruction::Copy,
+            D::Clone { .. } => Instruction::Clone,
+            D::Move { .. } => Instruction::Move,
+            D::Clear { .. } => Instruction::Clear,
+            D::Destroy { .. } => Instruction::Destroy,
+            D::Forget { .. } => Instruction::Forget,
+            D::LoadReturn { .. } => Instruction::LoadReturn,
+            D::FetchRef { .. } => Instruction::FetchRef,
+            D::MakeShared { .. } => Instruction::MakeShared,
+            D::SetGlobal { .. } => Instruction::SetGlobal,
+            D::CopyGlobal { .. } => Instruction::CopyGlobal,
+            D::CloneGlobal { .. } => Instruction::CloneGlobal,
+            D::Binary { instruction, .. } | D::Unary { instruction, .. } | D::Convert { instruction, .. } => {
+                *instruction
+            }
+            D::FmaF { .. } => Instruction::FmaF,
+            D::Handle { .. } => Instruction::Handle,
+            D::Perform { .. } => Instruction::Perform,
+            D::Resume { .. } => Instruction::Resume,
+            D::DropContinuation { .. } => Instruction::DropContinuation,
+            D::CreateArray { .. } => Instruction::CreateArray,
+            D::GetElement { .. } => Instruction::GetElement,
+            D::SetElement { .. } => Instruction::SetElement,
+            D::ArrayLength { .. } => Instruction::ArrayLength,
+            D::SliceArray { .. } => Instruction::SliceArray,
+            D::CopyElements { .. } => Instruction::CopyElements,
+            D::LoadString { .. } => Instruction::LoadString,
+            D::ConcatString { .. } => Instruction::ConcatString,
+            D::StringLength { .. } => Instruction::StringLength,
+            D::StringByte { .. } => Instruction::StringByte,
+            D::StringChar { .. } => Instruction::StringChar,
+            D::SliceString { .. } => Instruction::SliceString,
+            D::CompareString { .. } => Instruction::CompareString,
+            D::CreateObject { .. } => Instruction::CreateObject,
+            D::GetField { .. } => Instruction::GetField,
+            D::CopyField { .. } => Instruction::CopyField,
+            D::TakeField { .. } => Instruction::TakeField,
+            D::SetField { .. } => Instruction::SetField,
+            D::MoveField { .. } => Instruction::MoveField,
+            D::PlaceField { .. } => Instruction::PlaceField,
+            D::Call { .. } => Instruction::Call,
+            D::CallTail { .. } => Instruction::CallTail,
+            D::Invoke { .. } => Instruction::Invoke,
+            D::InvokeTail { .. } => Instruction::InvokeTail,
+            D::Return { .. } => Instruction::Return,
+            D::ReturnTail => Instruction::ReturnTail,
+            D::ReturnUnit => Instruction::ReturnUnit,
+            D::ReturnTailUnit => Instruction::ReturnTailUnit,
+            D::CreateClosure { .. } => Instruction::CreateClosure,
+            D::CreateFnObject { .. } => Instruction::CreateFnObject,
+            D::Jump { .. } => Instruction::Jump,
+            D::If { .. } => Instruction::If,
+            D::Switch { .. } => Instruction::Switch,
+            D::Match { .. } => Instruction::Match,
+            D::StartBlock { .. } => Instruction::StartBlock,
+        }
+    }
+}
+
+/// Iterates over the instructions of a function body.
+///
+/// Yields the byte offset of each instruction along with the instruction.
+/// Iteration ends after the last instruction or after the first error.
+pub struct Instructions<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    encoding: Encoding,
+    failed: bool,
+}
+
+impl<'a> Instructions<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self::with_encoding(bytes, Encoding::Fixed)
+    }
+
+    pub fn with_encoding(bytes: &'a [u8], encoding: Encoding) -> Self {
+        Instructions { bytes, offset: 0, encoding, failed: false }
+    }
+
+    /// The offset of the next instruction to decode.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Iterator for Instructions<'_> {
+    type Item = Result<(usize, DecodedInstruction), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.offset >= self.bytes.len() {
+            return None;
+        }
+        let offset = self.offset;
+        match DecodedInstruction::decode_with(self.bytes, offset, self.encoding) {
+            Ok((instruction, len)) => {
+                self.offset += len;
+                Some(Ok((offset, instruction)))
+            }
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Decodes the instructions of a function body.
+pub fn instructions(bytes: &[u8]) -> Instructions<'_> {
+    Instructions::new(bytes)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    encoding: Encoding,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, operand: OperandKind, len: usize) -> Result<&'a [u8], DecodeError> {
+        let truncated = DecodeError::TruncatedOperand { offset: self.offset, operand };
+        let end = self.offset.checked_add(len).ok_or(truncated)?;
+        let bytes = self.bytes.get(self.offset..end).ok_or(truncated)?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read<T>(&mut self, operand: OperandKind, decode: fn(&[u8]) -> Result<T, DecodeError>) -> Result<T, DecodeError> {
+        let start = self.offset;
+        let bytes = self.take(operand, operand.size())?;
+        decode(bytes).map_err(|error| error.offset_by(start))
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, DecodeError> {
+        let opcode = *self.bytes.get(self.offset)
+            .ok_or(DecodeError::MissingInstruction { offset: self.offset })?;
+        let instruction = decode_instruction(opcode).map_err(|error| error.offset_by(self.offset))?;
+        self.offset += 1;
+        Ok(instruction)
+    }
+
+    /// Reads an unsigned LEB128 value of at most `max`.
+    fn uleb(&mut self, operand: OperandKind, max: u64) -> Result<u64, DecodeError> {
+        let start = self.offset;
+        let mut value = 0u128;
+        for shift in (0..70).step_by(7) {
+            let byte = *self.bytes.get(self.offset)
+                .ok_or(DecodeError::TruncatedOperand { offset: start, operand })?;
+            self.offset += 1;
+            value |= ((byte & 0x7f) as u128) << shift;
+            if byte & 0x80 == 0 {
+                return u64::try_from(value).ok()
+                    .filter(|value| *value <= max)
+                    .ok_or(DecodeError::InvalidOperand { offset: start, operand });
+            }
+        }
+        Err(DecodeError::InvalidOperand { offset: start, operand })
+    }
+
+    /// Reads a signed LEB128 value that fits in an `i32`.
+    fn sleb(&mut self, operand: OperandKind) -> Result<i32, DecodeError> {
+        let start = self.offset;
+        let mut value = 0i64;
+        for shift in (0..35).step_by(7) {
+            let byte = *self.bytes.get(self.offset)
+                .ok_or(DecodeError::TruncatedOperand { offset: start, operand })?;
+            self.offset += 1;
+            value |= ((byte & 0x7f) as i64) << shift;
+            if byte & 0x80 == 0 {
+                if byte & 0x40 != 0 {
+                    value |= -1 << (shift + 7);
+                }
+                return i32::try_from(value).map_err(|_| DecodeError::InvalidOperand { offset: start, operand });
+            }
+        }
+        Err(DecodeError::InvalidOperand { offset: start, operand })
+    }
+
+    fn uleb32(&mut self, operand: OperandKind) -> Result<u32, DecodeError> {
+        Ok(self.uleb(operand, u32::MAX as u64)? as u32)
+    }
+
+    fn register(&mut self) -> Result<Register, DecodeError> {
+        match self.encoding {
+            Encoding::Fixed => {
+                let bytes = self.take(OperandKind::Register, 4)?;
+                Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            Encoding::Compact => self.uleb32(OperandKind::Register),
+        }
+    }
+
+    fn id(&mut self) -> Result<Id, DecodeError> {
+        match self.encoding {
+            Encoding::Fixed => self.read(OperandKind::Id, decode_id),
+            Encoding::Compact => self.uleb32(OperandKind::Id),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(OperandKind::Imm8, 1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes(self.take(OperandKind::Imm16, 2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(OperandKind::Imm32, 4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take(OperandKind::Imm64, 8)?.try_into().unwrap()))
+    }
+
+    fn numeric_type(&mut self) -> Result<NumericType, DecodeError> {
+        self.read(OperandKind::Type, decode_numeric_type)
+    }
+
+    fn branch(&mut self) -> Result<JumpBranch, DecodeError> {
+        match self.encoding {
+            Encoding::Fixed => self.read(OperandKind::JumpBranch, decode_jump_branch),
+            Encoding::Compact => Ok(JumpBranch {
+                block_id: self.uleb32(OperandKind::JumpBranch)?,
+                offset: self.sleb(OperandKind::JumpBranch)?,
+            }),
+        }
+    }
+
+    fn call_argument(&mut self) -> Result<CallArgument, DecodeError> {
+        match self.encoding {
+            Encoding::Fixed => self.read(OperandKind::CallArgument, decode_call_argument),
+            Encoding::Compact => {
+                let value = self.uleb(OperandKind::CallArgument, (u32::MAX as u64) << 1 | 1)?;
+                Ok(CallArgument { increment_ref: value & 1 != 0, register: (value >> 1) as u32 })
+            }
+        }
+    }
+
+    fn switch_case(&mut self) -> Result<SwitchCase, DecodeError> {
+        match self.encoding {
+            Encoding::Fixed => self.read(OperandKind::SwitchCase, decode_switch_case),
+            Encoding::Compact => {
+                let value = u64::from_le_bytes(self.take(OperandKind::SwitchCase, 8)?.try_into().unwrap());
+                Ok(SwitchCase { value, branch: self.branch()? })
+            }
+        }
+    }
+
+    fn match_case(&mut self) -> Result<MatchCase, DecodeError> {
+        match self.encoding {
+            Encoding::Fixed => self.read(OperandKind::MatchCase, decode_match_case),
+            Encoding::Compact => Ok(MatchCase { tag: self.uleb32(OperandKind::MatchCase)?, branch: self.branch()? }),
+        }
+    }
+
+    /// Reads a count followed by that many entries.
+    fn table<T>(&mut self, operand: OperandKind, entry: fn(&mut Self) -> Result<T, DecodeError>) -> Result<Vec<T>, DecodeError> {
+        let count = match self.encoding {
+            Encoding::Fixed => self.read(OperandKind::Count, decode_length)?,
+            Encoding::Compact => self.uleb32(OperandKind::Count)?,
+        } as usize;
+        // Check the whole table up front so a bogus count can't allocate
+        let size = operand.min_size(self.encoding);
+        let available = (self.bytes.len() - self.offset) / size;
+        if count > available {
+            let offset = self.offset + available * size;
+            return Err(DecodeError::TruncatedOperand { offset, operand });
+        }
+        (0..count).map(|_| entry(self)).collect()
+    }
+
+    fn call_arguments(&mut self) -> Result<Vec<CallArgument>, DecodeError> {
+        self.table(OperandKind::CallArgument, Self::call_argument)
+    }
+
+    fn operands(&mut self, instruction: Instruction) -> Result<DecodedInstruction, DecodeError> {
+        use DecodedInstruction as D;
+        use Instruction::*;
+        let decoded = match instruction {
+            Load8 => D::Load8 { dst: self.register()?, value: self.u8()? },
+            Load16 => D::Load16 { dst: self.register()?, value: self.u16()? },
+            Load32 => D::Load32 { dst: self.register()?, value: self.u32()? },
+            Load64 => D::Load64 { dst: self.register()?, value: self.u64()? },
+            Loadf32 => D::Loadf32 { dst: self.register()?, value: f32::from_bits(self.u32()?) },
+            Loadf64 => D::Loadf64 { dst: self.register()?, value: f64::from_bits(self.u64()?) },
+            Copy => D::Copy { dst: self.register()?, src: self.register()? },
+            Clone => D::Clone { dst: self.register()?, src: self.register()? },
+            Move => D::Move { dst: self.register()?, src: self.register()? },
+            Clear => D::Clear { register: self.register()? },
+            Destroy => D::Destroy { register: self.register()? },
+            Forget => D::Forget { register: self.register()? },
+            LoadReturn => D::LoadReturn { dst: self.register()? },
+            FetchRef => D::FetchRef { dst: self.register()?, src: self.register()? },
+            MakeShared => D::MakeShared { register: self.register()? },
+            SetGlobal => D::SetGlobal { global: self.id()?, src: self.register()? },
+            CopyGlobal => D::CopyGlobal { dst: self.register()?, global: self.id()? },
+            CloneGlobal => D::CloneGlobal { dst: self.register()?, global: self.id()? },
+            AddU | SubU | MulU | DivU | RemU | AddS | SubS | MulS | DivS | RemS | AddF | SubF | MulF
+            | DivF | And | Or | Xor | ShiftLeft | LogicalShiftRight | ArithmeticShiftRight | EqI | NeqI
+            | EqF | NeqF | LtU | GtU | LteU | GteU | LtS | GtS | LteS | GteS | LtF | GtF | LteF | GteF
+            | CheckedAddU | CheckedSubU | CheckedMulU | CheckedAddS | CheckedSubS | CheckedMulS | CheckedDivS
+            | SaturatingAddU | SaturatingSubU | SaturatingMulU | SaturatingAddS | SaturatingSubS | SaturatingMulS
+            | SaturatingDivS | RotateLeft | RotateRight | RemF | MinF | MaxF | CopySignF => {
+                D::Binary { instruction, dst: self.register()?, lhs: self.register()?, rhs: self.register()? }
+            }
+            Not | ByteSwap | IsNull | IsNaN | IsInfinity | PopCount | CountLeadingZeros | CountTrailingZeros
+            | BitReverse | NegF | AbsF | SqrtF | FloorF | CeilF | TruncF | RoundF => {
+                D::Unary { instruction, dst: self.register()?, src: self.register()? }
+            }
+            CreateObject => D::CreateObject { dst: self.register()?, type_id: self.id()?, variant: self.id()? },
+            GetField => D::GetField { dst: self.register()?, object: self.register()?, field: self.id()? },
+            CopyField => D::CopyField { dst: self.register()?, object: self.register()?, field: self.id()? },
+            TakeField => D::TakeField { dst: self.register()?, object: self.register()?, field: self.id()? },
+            SetField => D::SetField { object: self.register()?, field: self.id()?, src: self.register()? },
+            MoveField => D::MoveField { object: self.register()?, field: self.id()?, src: self.register()? },
+            PlaceField => D::PlaceField { object: self.register()?, field: self.id()?, src: self.register()? },
+            Call => D::Call { function: self.id()?, arguments: self.call_arguments()? },
+            CallTail => D::CallTail { function: self.id()?, arguments: self.call_arguments()? },
+            Invoke => D::Invoke { closure: self.register()?, arguments: self.call_arguments()? },
+            InvokeTail => D::InvokeTail { closure: self.register()?, arguments: self.call_arguments()? },
+            Return => D::Return { src: self.register()? },
+            ReturnTail => D::ReturnTail,
+            ReturnUnit => D::ReturnUnit,
+            ReturnTailUnit => D::ReturnTailUnit,
+            CreateClosure => D::CreateClosure {
+                dst: self.register()?,
+                function: self.id()?,
+                captures: self.call_arguments()?,
+            },
+            CreateFnObject => D::CreateFnObject { dst: self.register()?, function: self.id()? },
+            Jump => D::Jump { branch: self.branch()? },
+            If => D::If { condition: self.register()?, then_branch: self.branch()?, else_branch: self.branch()? },
+            Switch => D::Switch {
+                src: self.register()?,
+                default: self.branch()?,
+                cases: self.table(OperandKind::SwitchCase, Self::switch_case)?,
+            },
+            Match => D::Match { src: self.register()?, cases: self.table(OperandKind::MatchCase, Self::match_case)? },
+            StartBlock => D::StartBlock { id: self.id()? },
+            SignExtend | ZeroExtend | Truncate | IntToFloatS | IntToFloatU | FloatToIntSat | FloatToInt
+            | FloatToFloat => {
+                D::Convert { instruction, dst: self.register()?, src: self.register()?, ty: self.numeric_type()? }
+            }
+            FmaF => D::FmaF {
+                dst: self.register()?,
+                lhs: self.register()?,
+                rhs: self.register()?,
+                addend: self.register()?,
+            },
+            Handle => D::Handle { effect: self.id()?, handler: self.register()?, body: self.register()? },
+            Perform => D::Perform { effect: self.id()?, arguments: self.call_arguments()? },
+            Resume => D::Resume { continuation: self.register()?, value: self.register()? },
+            DropContinuation => D::DropContinuation { continuation: self.register()? },
+            CreateArray => D::CreateArray { dst: self.register()?, len: self.register()?, fill: self.register()? },
+            GetElement => D::GetElement { dst: self.register()?, array: self.register()?, index: self.register()? },
+            SetElement => D::SetElement { array: self.register()?, index: self.register()?, src: self.register()? },
+            ArrayLength => D::ArrayLength { dst: self.register()?, array: self.register()? },
+            SliceArray => D::SliceArray {
+                dst: self.register()?,
+                array: self.register()?,
+                start: self.register()?,
+                end: self.register()?,
+            },
+            CopyElements => D::CopyElements {
+                dst: self.register()?,
+                dst_start: self.register()?,
+                src: self.register()?,
+                src_start: self.register()?,
+                count: self.register()?,
+            },
+            LoadString => D::LoadString { dst: self.register()?, string: self.id()? },
+            ConcatString => D::ConcatString { dst: self.register()?, lhs: self.register()?, rhs: self.register()? },
+            StringLength => D::StringLength { dst: self.register()?, src: self.register()? },
+            StringByte => D::StringByte { dst: self.register()?, string: self.register()?, index: self.register()? },
+            StringChar => D::StringChar { dst: self.register()?, string: self.register()?, index: self.register()? },
+            SliceString => D::SliceString {
+                dst: self.register()?,
+                string: self.register()?,
+                start: self.register()?,
+                end: self.register()?,
+            },
+            CompareString => D::CompareString { dst: self.register()?, lhs: self.register()?, rhs: self.register()? },
+        };
+        Ok(decoded)
+    }
+}
@@ -0,0 +1,386 @@
+use std::fmt;
+
+use crate::{CallArgument, DecodedInstruction, Encoding, Id, Instruction, JumpBranch, MatchCase, Register, SwitchCase};
+
+/// Encodes an instruction into its byte value
+pub fn encode_instruction(instruction: Instruction, bytes: &mut Vec<u8>) {
+    bytes.push(instruction.into());
+}
+
+/// Encodes an `Id`, register or length
+pub fn encode_id(id: Id, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&id.to_le_bytes());
+}
+
+/// Encodes a `CallArgument`
+pub fn encode_call_argument(argument: &CallArgument, bytes: &mut Vec<u8>) {
+    bytes.push(argument.increment_ref as u8);
+    encode_id(argument.register, bytes);
+}
+
+/// Encodes a `JumpBranch`
+pub fn encode_jump_branch(branch: &JumpBranch, bytes: &mut Vec<u8>) {
+    encode_id(branch.block_id, bytes);
+    bytes.extend_from_slice(&branch.offset.to_le_bytes());
+}
+
+/// Encodes a `SwitchCase`
+pub fn encode_switch_case(case: &SwitchCase, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&case.value.to_le_bytes());
+    encode_jump_branch(&case.branch, bytes);
+}
+
+/// Encodes a `MatchCase`
+pub fn encode_match_case(case: &MatchCase, bytes: &mut Vec<u8>) {
+    encode_id(case.tag, bytes);
+    encode_jump_branch(&case.branch, bytes);
+}
+
+/// Encodes an unsigned LEB128 value
+pub fn encode_uleb128(mut value: u64, bytes: &mut Vec<u8>) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Encodes a signed LEB128 value
+pub fn encode_sleb128(mut value: i64, bytes: &mut Vec<u8>) {
+    loop {
+        let byte = value as u8 & 0x7f;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+impl DecodedInstruction {
+    /// Appends the encoding of this instruction to `bytes`.
+    pub fn encode(&self, bytes: &mut Vec<u8>) {
+        self.encode_with(bytes, Encoding::Fixed);
+    }
+
+    /// Appends the encoding of this instruction in `encoding` to `bytes`.
+    pub fn encode_with(&self, bytes: &mut Vec<u8>, encoding: Encoding) {
+        use DecodedInstruction as D;
+        encode_instruction(self.instruction(), bytes);
+        let mut w = Writer { bytes, encoding };
+        match self {
+            D::Load8 { dst, value } => {
+                w.id(*dst);
+                w.bytes.push(*value);
+            }
+            D::Load16 { dst, value } => {
+                w.id(*dst);
+                w.bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            D::Load32 { dst, value } => {
+                w.id(*dst);
+                w.bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            D::Load64 { dst, value } => {
+                w.id(*dst);
+                w.bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            D::Loadf32 { dst, value } => {
+                w.id(*dst);
+                w.bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+            }
+            D::Loadf64 { dst, value } => {
+                w.id(*dst);
+                w.bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+            }
+            D::Copy { dst, src }
+            | D::Clone { dst, src }
+            | D::Move { dst, src }
+            | D::FetchRef { dst, src }
+            | D::Unary { dst, src, .. } => w.ids(&[*dst, *src]),
+            D::Clear { register } | D::Destroy { register } | D::Forget { register } | D::MakeShared { register } => {
+                w.id(*register);
+            }
+            D::LoadReturn { dst } => w.id(*dst),
+            D::SetGlobal { global, src } => w.ids(&[*global, *src]),
+            D::CopyGlobal { dst, global } | D::CloneGlobal { dst, global } => {
+                w.ids(&[*dst, *global]);
+            }
+            D::Binary { dst, lhs, rhs, .. } => w.ids(&[*dst, *lhs, *rhs]),
+            D::CreateObject { dst, type_id, variant } => w.ids(&[*dst, *type_id, *variant]),
+            D::GetField { dst, object, field }
+            | D::CopyField { dst, object, field }
+            | D::TakeField { dst, object, field } => w.ids(&[*dst, *object, *field]),
+            D::SetField { object, field, src }
+            | D::MoveField { object, field, src }
+            | D::PlaceField { object, field, src } => w.ids(&[*object, *field, *src]),
+            D::Call { function, arguments }
+            | D::CallTail { function, arguments }
+            | D::Perform { effect: function, arguments } => {
+                w.id(*function);
+                w.table(arguments, Writer::call_argument);
+            }
+            D::Invoke { closure, arguments } | D::InvokeTail { closure, arguments } => {
+                w.id(*closure);
+                w.table(arguments, Writer::call_argument);
+            }
+            D::Return { src } => w.id(*src),
+            D::ReturnTail | D::ReturnUnit | D::ReturnTailUnit => {}
+            D::CreateClosure { dst, function, captures } => {
+                w.ids(&[*dst, *function]);
+                w.table(captures, Writer::call_argument);
+            }
+            D::CreateFnObject { dst, function } => w.ids(&[*dst, *function]),
+            D::Jump { branch } => w.branch(branch),
+            D::If { condition, then_branch, else_branch } => {
+                w.id(*condition);
+                w.branch(then_branch);
+                w.branch(else_branch);
+            }
+            D::Switch { src, default, cases } => {
+                w.id(*src);
+                w.branch(default);
+                w.table(cases, Writer::switch_case);
+            }
+            D::Match { src, cases } => {
+                w.id(*src);
+                w.table(cases, Writer::match_case);
+            }
+            D::StartBlock { id } => w.id(*id),
+            D::FmaF { dst, lhs, rhs, addend } => w.ids(&[*dst, *lhs, *rhs, *addend]),
+            D::Handle { effect, handler, body } => w.ids(&[*effect, *handler, *body]),
+            D::Resume { continuation, value } => w.ids(&[*continuation, *value]),
+            D::DropContinuation { continuation } => w.id(*continuation),
+            D::CreateArray { dst, len, fill } => w.ids(&[*dst, *len, *fill]),
+            D::GetElement { dst, array, index } => w.ids(&[*dst, *array, *index]),
+            D::SetElement { array, index, src } => w.ids(&[*array, *index, *src]),
+            D::ArrayLength { dst, array } => w.ids(&[*dst, *array]),
+            D::SliceArray { dst, array, start, end } => w.ids(&[*dst, *array, *start, *end]),
+            D::CopyElements { dst, dst_start, src, src_start, count } => {
+                w.ids(&[*dst, *dst_start, *src, *src_start, *count])
+            }
+            D::LoadString { dst, string } => w.ids(&[*dst, *string]),
+            D::ConcatString { dst, lhs, rhs } | D::CompareString { dst, lhs, rhs } => w.ids(&[*dst, *lhs, *rhs]),
+            D::StringLength { dst, src } => w.ids(&[*dst, *src]),
+            D::StringByte { dst, string, index } | D::StringChar { dst, string, index } => {
+                w.ids(&[*dst, *string, *index])
+            }
+            D::SliceString { dst, string, start, end } => w.ids(&[*dst, *string, *start, *end]),
+            D::Convert { dst, src, ty, .. } => {
+                w.ids(&[*dst, *src]);
+                w.bytes.push((*ty).into());
+            }
+        }
+    }
+}
+
+/// Writes operands in an encoding.
+struct Writer<'a> {
+    bytes: &'a mut Vec<u8>,
+    encoding: Encoding,
+}
+
+impl Writer<'_> {
+    fn id(&mut self, id: Id) {
+        match self.encoding {
+            Encoding::Fixed => encode_id(id, self.bytes),
+            Encoding::Compact => encode_uleb128(id as u64, self.bytes),
+        }
+    }
+
+    fn ids(&mut self, ids: &[Id]) {
+        for id in ids {
+            self.id(*id);
+        }
+    }
+
+    fn call_argument(&mut self, argument: &CallArgument) {
+        match self.encoding {
+            Encoding::Fixed => encode_call_argument(argument, self.bytes),
+            Encoding::Compact => {
+                encode_uleb128((argument.register as u64) << 1 | argument.increment_ref as u64, self.bytes);
+            }
+        }
+    }
+
+    fn branch(&mut self, branch: &JumpBranch) {
+        match self.encoding {
+            Encoding::Fixed => encode_jump_branch(branch, self.bytes),
+            Encoding::Compact => {
+                encode_uleb128(branch.block_id as u64, self.bytes);
+                encode_sleb128(branch.offset as i64, self.bytes);
+            }
+        }
+    }
+
+    fn switch_case(&mut self, case: &SwitchCase) {
+        self.bytes.extend_from_slice(&case.value.to_le_bytes());
+        self.branch(&case.branch);
+    }
+
+    fn match_case(&mut self, case: &MatchCase) {
+        self.id(case.tag);
+        self.branch(&case.branch);
+    }
+
+    fn table<T>(&mut self, entries: &[T], encode: fn(&mut Self, &T)) {
+        self.id(entries.len() as u32);
+        for entry in entries {
+            encode(self, entry);
+        }
+    }
+}
+
+/// A block that has not been placed yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(u32);
+
+/// An error raised when finishing a `FunctionBuilder`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// A branch targets a label that was never passed to `start_block`.
+    UnplacedLabel(Label),
+    /// A label was passed to `start_block` more than once.
+    DuplicateLabel(Label),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::UnplacedLabel(label) => write!(f, "label {} is branched to but never placed", label.0),
+            BuildError::DuplicateLabel(label) => write!(f, "label {} is placed more than once", label.0),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Builds the bytecode of a function.
+///
+/// Branches target `Label`s, which become blocks when they are placed with
+/// `start_block`. Block ids are handed out in placement order and written
+/// into every branch by `finish`. In the compact encoding, a branch to a
+/// label that is not placed yet reserves a five byte LEB128 block id.
+#[derive(Debug, Default)]
+pub struct FunctionBuilder {
+    bytes: Vec<u8>,
+    encoding: Encoding,
+    /// The block id of each label once it is placed.
+    labels: Vec<Option<Id>>,
+    /// The position of every `JumpBranch` waiting on a label.
+    fixups: Vec<(usize, Label)>,
+    next_block: Id,
+    error: Option<BuildError>,
+}
+
+impl FunctionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_encoding(encoding: Encoding) -> Self {
+        FunctionBuilder { encoding, ..Self::default() }
+    }
+
+    /// Creates a label that can be branched to before it is placed.
+    pub fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() as u32 - 1)
+    }
+
+    /// Places `label` by starting a new block at the current position.
+    pub fn start_block(&mut self, label: Label) {
+        let slot = &mut self.labels[label.0 as usize];
+        if slot.is_some() {
+            self.error.get_or_insert(BuildError::DuplicateLabel(label));
+            return;
+        }
+        let id = self.next_block;
+        self.next_block += 1;
+        *slot = Some(id);
+        self.emit(DecodedInstruction::StartBlock { id });
+    }
+
+    /// The offset the next instruction will be written at.
+    pub fn offset(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Emits an instruction as is.
+    pub fn emit(&mut self, instruction: DecodedInstruction) {
+        instruction.encode_with(&mut self.bytes, self.encoding);
+    }
+
+    pub fn jump(&mut self, target: Label) {
+        encode_instruction(Instruction::Jump, &mut self.bytes);
+        self.branch(target);
+    }
+
+    pub fn branch_if(&mut self, condition: Register, then_label: Label, else_label: Label) {
+        encode_instruction(Instruction::If, &mut self.bytes);
+        self.id(condition);
+        self.branch(then_label);
+        self.branch(else_label);
+    }
+
+    pub fn switch(&mut self, src: Register, default: Label, cases: &[(u64, Label)]) {
+        encode_instruction(Instruction::Switch, &mut self.bytes);
+        self.id(src);
+        self.branch(default);
+        self.id(cases.len() as u32);
+        for (value, label) in cases {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+            self.branch(*label);
+        }
+    }
+
+    pub fn match_variant(&mut self, src: Register, cases: &[(Id, Label)]) {
+        encode_instruction(Instruction::Match, &mut self.bytes);
+        self.id(src);
+        self.id(cases.len() as u32);
+        for (tag, label) in cases {
+            self.id(*tag);
+            self.branch(*label);
+        }
+    }
+
+    fn id(&mut self, id: Id) {
+        Writer { bytes: &mut self.bytes, encoding: self.encoding }.id(id);
+    }
+
+    fn branch(&mut self, label: Label) {
+        let placed = self.labels[label.0 as usize];
+        let mut writer = Writer { bytes: &mut self.bytes, encoding: self.encoding };
+        match (self.encoding, placed) {
+            (Encoding::Compact, Some(block_id)) => writer.branch(&JumpBranch { block_id, offset: 0 }),
+            (Encoding::Compact, None) => {
+                self.fixups.push((self.bytes.len(), label));
+                self.bytes.extend_from_slice(&[0x80, 0x80, 0x80, 0x80, 0x00, 0x00]);
+            }
+            (Encoding::Fixed, _) => {
+                self.fixups.push((self.bytes.len(), label));
+                encode_jump_branch(&JumpBranch { block_id: 0, offset: 0 }, &mut self.bytes);
+            }
+        }
+    }
+
+    /// Resolves every branch and returns the encoded function.
+    pub fn finish(mut self) -> Result<Box<[u8]>, BuildError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        for (position, label) in self.fixups {
+            let block_id = self.labels[label.0 as usize].ok_or(BuildError::UnplacedLabel(label))?;
+            match self.encoding {
+                Encoding::Fixed => self.bytes[position..position + 4].copy_from_slice(&block_id.to_le_bytes()),
+                Encoding::Compact => {
+                    for (i, byte) in self.bytes[position..position + 5].iter_mut().enumerate() {
+                        *byte = (block_id >> (7 * i)) as u8 & 0x7f | if i < 4 { 0x80 } else { 0 };
+                    }
+                }
+            }
+        }
+        Ok(self.bytes.into_boxed_slice())
+    }
+}
@@ -1,92 +1,317 @@
+mod assembly;
+pub mod cfg;
+mod decoder;
+mod encoder;
+mod metadata;
 
+pub use assembly::*;
+pub use decoder::*;
+pub use encoder::*;
+pub use metadata::*;
 
+/// An opcode of the Maru bytecode.
+///
+/// Every instruction is encoded as a single opcode byte followed by its operands.
+/// How the operands are laid out depends on the `Encoding` of the function body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Instruction {
+    /// `Load8 dst, imm8` loads an 8-bit immediate into `dst` as a `U8`.
     Load8,
+    /// `Load16 dst, imm16` loads a 16-bit immediate into `dst` as a `U16`.
     Load16,
+    /// `Load32 dst, imm32` loads a 32-bit immediate into `dst` as a `U32`.
     Load32,
+    /// `Load64 dst, imm64` loads a 64-bit immediate into `dst` as a `U64`.
     Load64,
+    /// `Loadf32 dst, imm32` loads the bits of an `f32` into `dst` as a `F32`.
     Loadf32,
+    /// `Loadf64 dst, imm64` loads the bits of an `f64` into `dst` as a `F64`.
     Loadf64,
+    /// `Copy dst, src` copies the bits of `src` into `dst` without touching reference counts.
     Copy,
+    /// `Clone dst, src` copies `src` into `dst` and increments the reference count of objects.
     Clone,
+    /// `Move dst, src` moves `src` into `dst` and clears `src`.
     Move,
+    /// `Clear reg` resets `reg` to unit without touching reference counts.
     Clear,
+    /// `Destroy reg` decrements the reference count of the object in `reg`, freeing it at zero, and clears `reg`.
     Destroy,
+    /// `Forget reg` hands the memory of the object in `reg` back to the allocator without releasing its fields.
     Forget,
+    /// `LoadReturn dst` moves the value returned by the last call into `dst`.
     LoadReturn,
+    /// `FetchRef dst, src` loads the reference count of the object in `src` into `dst` as an `I64`.
     FetchRef,
+    /// `MakeShared reg` switches the object in `reg` to atomic reference counting.
     MakeShared,
+    /// `SetGlobal global, src` moves `src` into `global`, releasing the previous value.
     SetGlobal,
+    /// `CopyGlobal dst, global` copies the bits of `global` into `dst`.
     CopyGlobal,
+    /// `CloneGlobal dst, global` copies `global` into `dst` and increments the reference count of objects.
     CloneGlobal,
+    /// `AddU dst, lhs, rhs` adds two unsigned integers, wrapping on overflow.
     AddU,
+    /// `SubU dst, lhs, rhs` subtracts two unsigned integers, wrapping on overflow.
     SubU,
+    /// `MulU dst, lhs, rhs` multiplies two unsigned integers, wrapping on overflow.
     MulU,
+    /// `DivU dst, lhs, rhs` divides two unsigned integers. Division by zero traps.
     DivU,
+    /// `RemU dst, lhs, rhs` takes the remainder of two unsigned integers. Division by zero traps.
     RemU,
+    /// `AddS dst, lhs, rhs` adds two signed integers, wrapping on overflow.
     AddS,
+    /// `SubS dst, lhs, rhs` subtracts two signed integers, wrapping on overflow.
     SubS,
+    /// `MulS dst, lhs, rhs` multiplies two signed integers, wrapping on overflow.
     MulS,
+    /// `DivS dst, lhs, rhs` divides two signed integers, rounding toward zero.
+    /// Division by zero traps and `MIN / -1` wraps to `MIN`.
     DivS,
+    /// `RemS dst, lhs, rhs` takes the remainder of two signed integers, with the sign of `lhs`.
+    /// Division by zero traps and `MIN % -1` is 0.
     RemS,
+    /// `AddF dst, lhs, rhs` adds two floats.
     AddF,
+    /// `SubF dst, lhs, rhs` subtracts two floats.
     SubF,
+    /// `MulF dst, lhs, rhs` multiplies two floats.
     MulF,
+    /// `DivF dst, lhs, rhs` divides two floats.
     DivF,
+    /// `And dst, lhs, rhs` is a bitwise and.
     And,
+    /// `Or dst, lhs, rhs` is a bitwise or.
     Or,
+    /// `Xor dst, lhs, rhs` is a bitwise exclusive or.
     Xor,
+    /// `Not dst, src` is a bitwise not, or a logical not on a `Bool`.
     Not,
+    /// `ShiftLeft dst, lhs, rhs` shifts `lhs` left by `rhs` modulo its width.
     ShiftLeft,
+    /// `LogicalShiftRight dst, lhs, rhs` shifts `lhs` right, filling with zeros.
     LogicalShiftRight,
+    /// `ArithmeticShiftRight dst, lhs, rhs` shifts `lhs` right, filling with the sign bit.
     ArithmeticShiftRight,
+    /// `ByteSwap dst, src` reverses the bytes of `src`.
     ByteSwap,
+    /// `EqI dst, lhs, rhs` compares two integers for equality.
     EqI,
+    /// `NeqI dst, lhs, rhs` compares two integers for inequality.
     NeqI,
+    /// `EqF dst, lhs, rhs` compares two floats for equality.
     EqF,
+    /// `NeqF dst, lhs, rhs` compares two floats for inequality.
     NeqF,
+    /// `LtU dst, lhs, rhs` is an unsigned `<`.
     LtU,
+    /// `GtU dst, lhs, rhs` is an unsigned `>`.
     GtU,
+    /// `LteU dst, lhs, rhs` is an unsigned `<=`.
     LteU,
+    /// `GteU dst, lhs, rhs` is an unsigned `>=`.
     GteU,
+    /// `LtS dst, lhs, rhs` is a signed `<`.
     LtS,
+    /// `GtS dst, lhs, rhs` is a signed `>`.
     GtS,
+    /// `LteS dst, lhs, rhs` is a signed `<=`.
     LteS,
+    /// `GteS dst, lhs, rhs` is a signed `>=`.
     GteS,
+    /// `LtF dst, lhs, rhs` is a float `<`.
     LtF,
+    /// `GtF dst, lhs, rhs` is a float `>`.
     GtF,
+    /// `LteF dst, lhs, rhs` is a float `<=`.
     LteF,
+    /// `GteF dst, lhs, rhs` is a float `>=`.
     GteF,
+    /// `CreateObject dst, type, variant` allocates a zeroed object of the given type and variant.
     CreateObject,
+    /// `IsNull dst, src` checks whether the object in `src` is null.
     IsNull,
+    /// `IsNaN dst, src` checks whether the float in `src` is NaN.
     IsNaN,
+    /// `IsInfinity dst, src` checks whether the float in `src` is infinite.
     IsInfinity,
+    /// `GetField dst, object, field` copies a field into `dst` and increments the reference count of objects.
     GetField,
+    /// `CopyField dst, object, field` copies the bits of a field into `dst`.
     CopyField,
+    /// `TakeField dst, object, field` moves a field into `dst` and clears the field.
     TakeField,
+    /// `SetField object, field, src` releases the old field value and stores a clone of `src`.
     SetField,
+    /// `MoveField object, field, src` releases the old field value and moves `src` into the field.
     MoveField,
+    /// `PlaceField object, field, src` moves `src` into a field without releasing the old value.
     PlaceField,
+    /// `Call function, count, args...` calls a function, leaving its result for `LoadReturn`.
     Call,
+    /// `CallTail function, count, args...` calls a function in place of the current frame.
     CallTail,
+    /// `Invoke closure, count, args...` calls a closure, passing its captures before `args`.
     Invoke,
+    /// `InvokeTail closure, count, args...` invokes a closure in place of the current frame.
     InvokeTail,
+    /// `Return src` returns `src` to the caller.
     Return,
+    /// `ReturnTail` forwards the result of the last call to the caller.
     ReturnTail,
+    /// `ReturnUnit` returns unit to the caller.
     ReturnUnit,
+    /// `ReturnTailUnit` returns unit to the caller after a call whose result is discarded.
     ReturnTailUnit,
+    /// `CreateClosure dst, function, count, captures...` creates a closure capturing registers.
     CreateClosure,
+    /// `CreateFnObject dst, function` creates a closure with no captures.
     CreateFnObject,
+    /// `Jump branch` jumps unconditionally.
     Jump,
+    /// `If cond, then, else` branches on a `Bool`.
     If,
+    /// `Switch src, default, count, cases...` branches on the integer value in `src`.
     Switch,
+    /// `Match src, count, cases...` branches on the variant of the object in `src`.
     Match,
-    StartBlock
+    /// `StartBlock id` marks the start of a block that branches can target.
+    StartBlock,
+    /// `SignExtend dst, src, type` sign-extends the integer in `src` to an integer type at least as wide.
+    SignExtend,
+    /// `ZeroExtend dst, src, type` zero-extends the integer in `src` to an integer type at least as wide.
+    ZeroExtend,
+    /// `Truncate dst, src, type` keeps the low bits of the integer in `src` as an integer type at most as wide.
+    Truncate,
+    /// `IntToFloatS dst, src, type` converts the signed integer in `src` to the nearest float.
+    IntToFloatS,
+    /// `IntToFloatU dst, src, type` converts the unsigned integer in `src` to the nearest float.
+    IntToFloatU,
+    /// `FloatToIntSat dst, src, type` converts the float in `src` to an integer, rounding toward zero.
+    /// NaN becomes 0 and values out of range saturate to the minimum or maximum of the type.
+    FloatToIntSat,
+    /// `FloatToInt dst, src, type` converts the float in `src` to an integer, rounding toward zero.
+    /// NaN and values out of range trap.
+    FloatToInt,
+    /// `FloatToFloat dst, src, type` converts between `F32` and `F64`, rounding to the nearest value.
+    FloatToFloat,
+    /// `CheckedAddU dst, lhs, rhs` adds two unsigned integers, trapping on overflow.
+    CheckedAddU,
+    /// `CheckedSubU dst, lhs, rhs` subtracts two unsigned integers, trapping on overflow.
+    CheckedSubU,
+    /// `CheckedMulU dst, lhs, rhs` multiplies two unsigned integers, trapping on overflow.
+    CheckedMulU,
+    /// `CheckedAddS dst, lhs, rhs` adds two signed integers, trapping on overflow.
+    CheckedAddS,
+    /// `CheckedSubS dst, lhs, rhs` subtracts two signed integers, trapping on overflow.
+    CheckedSubS,
+    /// `CheckedMulS dst, lhs, rhs` multiplies two signed integers, trapping on overflow.
+    CheckedMulS,
+    /// `CheckedDivS dst, lhs, rhs` divides two signed integers, trapping on `MIN / -1`.
+    CheckedDivS,
+    /// `SaturatingAddU dst, lhs, rhs` adds two unsigned integers, clamping to the range of the type.
+    SaturatingAddU,
+    /// `SaturatingSubU dst, lhs, rhs` subtracts two unsigned integers, clamping to the range of the type.
+    SaturatingSubU,
+    /// `SaturatingMulU dst, lhs, rhs` multiplies two unsigned integers, clamping to the range of the type.
+    SaturatingMulU,
+    /// `SaturatingAddS dst, lhs, rhs` adds two signed integers, clamping to the range of the type.
+    SaturatingAddS,
+    /// `SaturatingSubS dst, lhs, rhs` subtracts two signed integers, clamping to the range of the type.
+    SaturatingSubS,
+    /// `SaturatingMulS dst, lhs, rhs` multiplies two signed integers, clamping to the range of the type.
+    SaturatingMulS,
+    /// `SaturatingDivS dst, lhs, rhs` divides two signed integers, clamping `MIN / -1` to `MAX`.
+    SaturatingDivS,
+    /// `PopCount dst, src` counts the set bits of `src`, giving a result of the same type.
+    PopCount,
+    /// `CountLeadingZeros dst, src` counts the zero bits above the highest set bit of `src` within its width.
+    CountLeadingZeros,
+    /// `CountTrailingZeros dst, src` counts the zero bits below the lowest set bit of `src`, or its width when it is 0.
+    CountTrailingZeros,
+    /// `RotateLeft dst, lhs, rhs` rotates `lhs` left by `rhs` modulo its width.
+    RotateLeft,
+    /// `RotateRight dst, lhs, rhs` rotates `lhs` right by `rhs` modulo its width.
+    RotateRight,
+    /// `BitReverse dst, src` reverses the order of the bits of `src` within its width.
+    BitReverse,
+    /// `RemF dst, lhs, rhs` takes the remainder of dividing two floats, with the sign of `lhs` like C's `fmod`.
+    RemF,
+    /// `NegF dst, src` flips the sign of a float, including zeros and NaNs.
+    NegF,
+    /// `AbsF dst, src` clears the sign of a float.
+    AbsF,
+    /// `MinF dst, lhs, rhs` takes the IEEE 754 `minimum` of two floats: NaN if either is NaN, and -0.0 is less than 0.0.
+    MinF,
+    /// `MaxF dst, lhs, rhs` takes the IEEE 754 `maximum` of two floats: NaN if either is NaN, and 0.0 is greater than -0.0.
+    MaxF,
+    /// `SqrtF dst, src` takes the square root of a float, which is NaN for negative numbers.
+    SqrtF,
+    /// `FloorF dst, src` rounds a float toward negative infinity.
+    FloorF,
+    /// `CeilF dst, src` rounds a float toward positive infinity.
+    CeilF,
+    /// `TruncF dst, src` rounds a float toward zero.
+    TruncF,
+    /// `RoundF dst, src` rounds a float to the nearest integer, with ties to even.
+    RoundF,
+    /// `FmaF dst, lhs, rhs, addend` computes `lhs * rhs + addend` with a single rounding.
+    FmaF,
+    /// `CopySignF dst, lhs, rhs` gives the magnitude of `lhs` with the sign of `rhs`.
+    CopySignF,
+    /// `Handle effect, handler, body` invokes the closure `body` with no arguments under a handler for `effect`.
+    /// When the body performs the effect, the closure `handler` runs in its place and its result
+    /// becomes the result of `Handle`, read with `LoadReturn` like a call's.
+    Handle,
+    /// `Perform effect (args...)` captures the frames up to and including the innermost body handling
+    /// `effect` as a continuation, and invokes the handler with the continuation followed by `args`.
+    Perform,
+    /// `Resume continuation, value` moves the captured frames of `continuation` back on top of the
+    /// current frame, where `Perform` returns `value`. The handled body's result becomes the result of
+    /// `Resume`. A continuation can only be resumed once.
+    Resume,
+    /// `DropContinuation continuation` frees the frames of a continuation without resuming it and
+    /// releases the register.
+    DropContinuation,
+    /// `CreateArray dst, len, fill` creates an array of `len` copies of the value in `fill`,
+    /// retaining an object `fill` once per element.
+    CreateArray,
+    /// `GetElement dst, array, index` copies an element into `dst`, retaining it if it is an object.
+    GetElement,
+    /// `SetElement array, index, src` stores a copy of `src` in an element, retaining the new value
+    /// and releasing the old one.
+    SetElement,
+    /// `ArrayLength dst, array` loads the number of elements of `array` as a `U64`.
+    ArrayLength,
+    /// `SliceArray dst, array, start, end` creates a new array from the elements `start..end` of `array`.
+    SliceArray,
+    /// `CopyElements dst, dst_start, src, src_start, count` copies `count` elements between arrays of the
+    /// same element type. The ranges may overlap.
+    CopyElements,
+    /// `LoadString dst, string` creates a string holding a copy of an entry of the string table.
+    LoadString,
+    /// `ConcatString dst, lhs, rhs` creates a string holding `lhs` followed by `rhs`.
+    ConcatString,
+    /// `StringLength dst, src` loads the length of a string in bytes as a `U64`.
+    StringLength,
+    /// `StringByte dst, string, index` loads the byte at `index` as a `U8`.
+    StringByte,
+    /// `StringChar dst, string, index` decodes the char starting at byte `index` as a `U32`.
+    StringChar,
+    /// `SliceString dst, string, start, end` creates a string from the bytes `start..end`, which
+    /// must lie on char boundaries.
+    SliceString,
+    /// `CompareString dst, lhs, rhs` compares two strings bytewise, loading -1, 0 or 1 as an `I32`.
+    CompareString,
 }
 
-impl From<u8> for Instruction {
-    fn from(value: u8) -> Self {
-        match value {
+impl TryFrom<u8> for Instruction {
+    type Error = DecodeError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let instruction = match value {
             0 => Instruction::Load8,
             1 => Instruction::Load16,
             2 => Instruction::Load32,
@@ -168,14 +393,72 @@ impl From<u8> for Instruction {
             78 => Instruction::Switch,
             79 => Instruction::Match,
             80 => Instruction::StartBlock,
-            _ => panic!("Invalid instruction value"),
-        }
+            81 => Instruction::SignExtend,
+            82 => Instruction::ZeroExtend,
+            83 => Instruction::Truncate,
+            84 => Instruction::IntToFloatS,
+            85 => Instruction::IntToFloatU,
+            86 => Instruction::FloatToIntSat,
+            87 => Instruction::FloatToInt,
+            88 => Instruction::FloatToFloat,
+            89 => Instruction::CheckedAddU,
+            90 => Instruction::CheckedSubU,
+            91 => Instruction::CheckedMulU,
+            92 => Instruction::CheckedAddS,
+            93 => Instruction::CheckedSubS,
+            94 => Instruction::CheckedMulS,
+            95 => Instruction::CheckedDivS,
+            96 => Instruction::SaturatingAddU,
+            97 => Instruction::SaturatingSubU,
+            98 => Instruction::SaturatingMulU,
+            99 => Instruction::SaturatingAddS,
+            100 => Instruction::SaturatingSubS,
+            101 => Instruction::SaturatingMulS,
+            102 => Instruction::SaturatingDivS,
+            103 => Instruction::PopCount,
+            104 => Instruction::CountLeadingZeros,
+            105 => Instruction::CountTrailingZeros,
+            106 => Instruction::RotateLeft,
+            107 => Instruction::RotateRight,
+            108 => Instruction::BitReverse,
+            109 => Instruction::RemF,
+            110 => Instruction::NegF,
+            111 => Instruction::AbsF,
+            112 => Instruction::MinF,
+            113 => Instruction::MaxF,
+            114 => Instruction::SqrtF,
+            115 => Instruction::FloorF,
+            116 => Instruction::CeilF,
+            117 => Instruction::TruncF,
+            118 => Instruction::RoundF,
+            119 => Instruction::FmaF,
+            120 => Instruction::CopySignF,
+            121 => Instruction::Handle,
+            122 => Instruction::Perform,
+            123 => Instruction::Resume,
+            124 => Instruction::DropContinuation,
+            125 => Instruction::CreateArray,
+            126 => Instruction::GetElement,
+            127 => Instruction::SetElement,
+            128 => Instruction::ArrayLength,
+            129 => Instruction::SliceArray,
+            130 => Instruction::CopyElements,
+            131 => Instruction::LoadString,
+            132 => Instruction::ConcatString,
+            133 => Instruction::StringLength,
+            134 => Instruction::StringByte,
+            135 => Instruction::StringChar,
+            136 => Instruction::SliceString,
+            137 => Instruction::CompareString,
+            _ => return Err(DecodeError::InvalidOpcode { offset: 0, opcode: value }),
+        };
+        Ok(instruction)
     }
 }
 
-impl Into<u8> for Instruction {
-    fn into(self) -> u8 {
-        match self {
+impl From<Instruction> for u8 {
+    fn from(value: Instruction) -> u8 {
+        match value {
             Instruction::Load8 => 0,
             Instruction::Load16 => 1,
             Instruction::Load32 => 2,
@@ -257,6 +540,63 @@ impl Into<u8> for Instruction {
             Instruction::Switch => 78,
             Instruction::Match => 79,
             Instruction::StartBlock => 80,
+            Instruction::SignExtend => 81,
+            Instruction::ZeroExtend => 82,
+            Instruction::Truncate => 83,
+            Instruction::IntToFloatS => 84,
+            Instruction::IntToFloatU => 85,
+            Instruction::FloatToIntSat => 86,
+            Instruction::FloatToInt => 87,
+            Instruction::FloatToFloat => 88,
+            Instruction::CheckedAddU => 89,
+            Instruction::CheckedSubU => 90,
+            Instruction::CheckedMulU => 91,
+            Instruction::CheckedAddS => 92,
+            Instruction::CheckedSubS => 93,
+            Instruction::CheckedMulS => 94,
+            Instruction::CheckedDivS => 95,
+            Instruction::SaturatingAddU => 96,
+            Instruction::SaturatingSubU => 97,
+            Instruction::SaturatingMulU => 98,
+            Instruction::SaturatingAddS => 99,
+            Instruction::SaturatingSubS => 100,
+            Instruction::SaturatingMulS => 101,
+            Instruction::SaturatingDivS => 102,
+            Instruction::PopCount => 103,
+            Instruction::CountLeadingZeros => 104,
+            Instruction::CountTrailingZeros => 105,
+            Instruction::RotateLeft => 106,
+            Instruction::RotateRight => 107,
+            Instruction::BitReverse => 108,
+            Instruction::RemF => 109,
+            Instruction::NegF => 110,
+            Instruction::AbsF => 111,
+            Instruction::MinF => 112,
+            Instruction::MaxF => 113,
+            Instruction::SqrtF => 114,
+            Instruction::FloorF => 115,
+            Instruction::CeilF => 116,
+            Instruction::TruncF => 117,
+            Instruction::RoundF => 118,
+            Instruction::FmaF => 119,
+            Instruction::CopySignF => 120,
+            Instruction::Handle => 121,
+            Instruction::Perform => 122,
+            Instruction::Resume => 123,
+            Instruction::DropContinuation => 124,
+            Instruction::CreateArray => 125,
+            Instruction::GetElement => 126,
+            Instruction::SetElement => 127,
+            Instruction::ArrayLength => 128,
+            Instruction::SliceArray => 129,
+            Instruction::CopyElements => 130,
+            Instruction::LoadString => 131,
+            Instruction::ConcatString => 132,
+            Instruction::StringLength => 133,
+            Instruction::StringByte => 134,
+            Instruction::StringChar => 135,
+            Instruction::SliceString => 136,
+            Instruction::CompareString => 137,
         }
     }
 }
@@ -267,6 +607,7 @@ pub type Register = u32;
 pub type Id = u32;
 
 /// A argument to the function call instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CallArgument {
     /// Whether or not to increment the reference count of the argument when passing it to the function
     pub increment_ref: bool,
@@ -275,12 +616,16 @@ pub struct CallArgument {
 }
 
 /// A branch option for the jump, if, switch, and match instructions
+///
+/// The target is `offset` bytes past the end of the `StartBlock` instruction of `block_id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct JumpBranch {
     pub block_id: Id,
     pub offset: i32,
 }
 
 /// A case for the switch instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SwitchCase {
     /// a constant value to compare against
     pub value: u64,
@@ -288,54 +633,273 @@ pub struct SwitchCase {
 }
 
 /// A case for the match instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MatchCase {
     /// The tag of the variant to match against
     pub tag: Id,
     pub branch: JumpBranch,
 }
 
+/// The target type of a conversion instruction
+///
+/// Encoded as a single byte using the same numbering as the type tags of Maru files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NumericType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+}
+
+impl NumericType {
+    pub const ALL: [NumericType; 10] = [
+        NumericType::U8,
+        NumericType::I8,
+        NumericType::U16,
+        NumericType::I16,
+        NumericType::U32,
+        NumericType::I32,
+        NumericType::U64,
+        NumericType::I64,
+        NumericType::F32,
+        NumericType::F64,
+    ];
+
+    /// The width of the type in bits
+    pub fn width(&self) -> u32 {
+        match self {
+            NumericType::U8 | NumericType::I8 => 8,
+            NumericType::U16 | NumericType::I16 => 16,
+            NumericType::U32 | NumericType::I32 | NumericType::F32 => 32,
+            NumericType::U64 | NumericType::I64 | NumericType::F64 => 64,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, NumericType::F32 | NumericType::F64)
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, NumericType::I8 | NumericType::I16 | NumericType::I32 | NumericType::I64)
+    }
+
+    /// The name of the type in assembly
+    pub fn name(&self) -> &'static str {
+        match self {
+            NumericType::U8 => "u8",
+            NumericType::I8 => "i8",
+            NumericType::U16 => "u16",
+            NumericType::I16 => "i16",
+            NumericType::U32 => "u32",
+            NumericType::I32 => "i32",
+            NumericType::U64 => "u64",
+            NumericType::I64 => "i64",
+            NumericType::F32 => "f32",
+            NumericType::F64 => "f64",
+        }
+    }
+}
+
+impl TryFrom<u8> for NumericType {
+    type Error = DecodeError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            2..=11 => Ok(NumericType::ALL[value as usize - 2]),
+            _ => Err(DecodeError::InvalidOperand { offset: 0, operand: OperandKind::Type }),
+        }
+    }
+}
+
+impl From<NumericType> for u8 {
+    fn from(value: NumericType) -> u8 {
+        NumericType::ALL.iter().position(|ty| *ty == value).unwrap() as u8 + 2
+    }
+}
+
+/// How the operands of the instructions in a function body are laid out
+///
+/// Immediates are stored at their natural width and types as a single byte in
+/// both encodings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Encoding {
+    /// Registers, `Id`s, counts and block ids are little-endian `u32`s and
+    /// branch offsets little-endian `i32`s.
+    #[default]
+    Fixed,
+    /// Registers, `Id`s, counts and block ids are unsigned LEB128 and branch
+    /// offsets signed LEB128. A call argument is its register shifted left by
+    /// one with `increment_ref` in the low bit.
+    Compact,
+}
+
+/// The kind of an operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperandKind {
+    Register,
+    Id,
+    /// The number of entries in a table of call arguments or cases
+    Count,
+    Imm8,
+    Imm16,
+    Imm32,
+    Imm64,
+    CallArgument,
+    JumpBranch,
+    SwitchCase,
+    MatchCase,
+    /// A `NumericType`
+    Type,
+}
+
+impl OperandKind {
+    /// The number of bytes the operand occupies in the fixed encoding
+    pub fn size(&self) -> usize {
+        match self {
+            OperandKind::Imm8 | OperandKind::Type => 1,
+            OperandKind::Imm16 => 2,
+            OperandKind::Register | OperandKind::Id | OperandKind::Count | OperandKind::Imm32 => 4,
+            OperandKind::CallArgument => 5,
+            OperandKind::Imm64 | OperandKind::JumpBranch => 8,
+            OperandKind::MatchCase => 12,
+            OperandKind::SwitchCase => 16,
+        }
+    }
+
+    /// The fewest bytes the operand can occupy in `encoding`
+    pub fn min_size(&self, encoding: Encoding) -> usize {
+        match (encoding, self) {
+            (Encoding::Fixed, _) => self.size(),
+            (Encoding::Compact, OperandKind::Register | OperandKind::Id | OperandKind::Count) => 1,
+            (Encoding::Compact, OperandKind::CallArgument) => 1,
+            (Encoding::Compact, OperandKind::JumpBranch) => 2,
+            (Encoding::Compact, OperandKind::MatchCase) => 3,
+            (Encoding::Compact, OperandKind::SwitchCase) => 10,
+            (Encoding::Compact, _) => self.size(),
+        }
+    }
+}
+
+/// An error raised while decoding bytecode
+///
+/// Offsets are relative to the start of the bytes being decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The byte at `offset` is not a valid opcode
+    InvalidOpcode { offset: usize, opcode: u8 },
+    /// An instruction was expected at `offset` but the bytecode ended
+    MissingInstruction { offset: usize },
+    /// The bytecode ended in the middle of the operand starting at `offset`
+    TruncatedOperand { offset: usize, operand: OperandKind },
+    /// The operand starting at `offset` holds a value the operand can't take
+    InvalidOperand { offset: usize, operand: OperandKind },
+}
+
+impl DecodeError {
+    /// The offset the error occurred at
+    pub fn offset(&self) -> usize {
+        match self {
+            DecodeError::InvalidOpcode { offset, .. }
+            | DecodeError::MissingInstruction { offset }
+            | DecodeError::TruncatedOperand { offset, .. }
+            | DecodeError::InvalidOperand { offset, .. } => *offset,
+        }
+    }
+
+    /// Moves the error `base` bytes further into the bytecode
+    pub fn offset_by(mut self, base: usize) -> Self {
+        match &mut self {
+            DecodeError::InvalidOpcode { offset, .. }
+            | DecodeError::MissingInstruction { offset }
+            | DecodeError::TruncatedOperand { offset, .. }
+            | DecodeError::InvalidOperand { offset, .. } => *offset += base,
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::InvalidOpcode { offset, opcode } => {
+                write!(f, "invalid opcode {} at offset {}", opcode, offset)
+            }
+            DecodeError::MissingInstruction { offset } => {
+                write!(f, "expected an instruction at offset {}", offset)
+            }
+            DecodeError::TruncatedOperand { offset, operand } => {
+                write!(f, "truncated {:?} operand at offset {}", operand, offset)
+            }
+            DecodeError::InvalidOperand { offset, operand } => {
+                write!(f, "invalid {:?} operand at offset {}", operand, offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+fn read<const N: usize>(bytes: &[u8], operand: OperandKind) -> Result<[u8; N], DecodeError> {
+    bytes.get(..N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(DecodeError::TruncatedOperand { offset: 0, operand })
+}
+
 /// Decodes an instruction from a byte value
-pub fn decode_instruction(value: u8) -> Instruction {
-    value.into()
+pub fn decode_instruction(value: u8) -> Result<Instruction, DecodeError> {
+    value.try_into()
+}
+
+/// Decodes a `NumericType` from a byte slice
+pub fn decode_numeric_type(bytes: &[u8]) -> Result<NumericType, DecodeError> {
+    let [value] = read::<1>(bytes, OperandKind::Type)?;
+    value.try_into()
 }
 
 /// Decodes a length from a byte slice
-pub fn decode_length(bytes: &[u8]) -> u32 {
-    let length = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-    length
+pub fn decode_length(bytes: &[u8]) -> Result<u32, DecodeError> {
+    Ok(u32::from_le_bytes(read(bytes, OperandKind::Count)?))
 }
 
 /// Decodes a `CallArgument` from a byte slice
-pub fn decode_call_argument(bytes: &[u8]) -> CallArgument {
-    let increment_ref = bytes[0] != 0;
-    let register = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
-    CallArgument { increment_ref, register }
+pub fn decode_call_argument(bytes: &[u8]) -> Result<CallArgument, DecodeError> {
+    let [increment_ref, register @ ..] = read::<5>(bytes, OperandKind::CallArgument)?;
+    Ok(CallArgument { increment_ref: increment_ref != 0, register: u32::from_le_bytes(register) })
 }
 
 /// Decodes a `JumpBranch` from a byte slice
-pub fn decode_jump_branch(bytes: &[u8]) -> JumpBranch {
+pub fn decode_jump_branch(bytes: &[u8]) -> Result<JumpBranch, DecodeError> {
+    let bytes = read::<8>(bytes, OperandKind::JumpBranch)?;
     let block_id = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
     let offset = i32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-    JumpBranch { block_id, offset }
+    Ok(JumpBranch { block_id, offset })
 }
 
 /// Decodes a `SwitchCase` from a byte slice
-pub fn decode_switch_case(bytes: &[u8]) -> SwitchCase {
+pub fn decode_switch_case(bytes: &[u8]) -> Result<SwitchCase, DecodeError> {
+    let bytes = read::<16>(bytes, OperandKind::SwitchCase)?;
     let value = u64::from_le_bytes([
         bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
     ]);
-    let branch = decode_jump_branch(&bytes[8..]);
-    SwitchCase { value, branch }
+    let branch = decode_jump_branch(&bytes[8..])?;
+    Ok(SwitchCase { value, branch })
 }
 
 /// Decodes a `MatchCase` from a byte slice
-pub fn decode_match_case(bytes: &[u8]) -> MatchCase {
+pub fn decode_match_case(bytes: &[u8]) -> Result<MatchCase, DecodeError> {
+    let bytes = read::<12>(bytes, OperandKind::MatchCase)?;
     let tag = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-    let branch = decode_jump_branch(&bytes[4..]);
-    MatchCase { tag, branch }
+    let branch = decode_jump_branch(&bytes[4..])?;
+    Ok(MatchCase { tag, branch })
 }
 
 /// Decodes an `Id` from a byte slice
-pub fn decode_id(bytes: &[u8]) -> Id {
-    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
-}
\ No newline at end of file
+pub fn decode_id(bytes: &[u8]) -> Result<Id, DecodeError> {
+    Ok(u32::from_le_bytes(read(bytes, OperandKind::Id)?))
+}
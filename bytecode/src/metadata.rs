@@ -0,0 +1,284 @@
+use crate::{DecodedInstruction, Instruction, OperandKind, Register};
+
+use OperandKind::*;
+
+/// Static facts about an instruction
+///
+/// In `operands`, a `Count` is followed by the kind of the entries it counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionInfo {
+    /// The name of the instruction in assembly
+    pub mnemonic: &'static str,
+    /// The operands in encoding order
+    pub operands: &'static [OperandKind],
+    /// Control never falls through to the next instruction
+    pub terminator: bool,
+    /// The instruction may read or write objects, including their reference counts
+    pub heap: bool,
+    /// The instruction may read or write globals
+    pub globals: bool,
+}
+
+const NONE: &[OperandKind] = &[];
+const REG: &[OperandKind] = &[Register];
+const REG_REG: &[OperandKind] = &[Register, Register];
+const REG_REG_REG: &[OperandKind] = &[Register, Register, Register];
+const CONVERT: &[OperandKind] = &[Register, Register, Type];
+const CALL: &[OperandKind] = &[Id, Count, CallArgument];
+const INVOKE: &[OperandKind] = &[Register, Count, CallArgument];
+
+const fn info(mnemonic: &'static str, operands: &'static [OperandKind]) -> InstructionInfo {
+    InstructionInfo { mnemonic, operands, terminator: false, heap: false, globals: false }
+}
+
+const fn heap(mnemonic: &'static str, operands: &'static [OperandKind]) -> InstructionInfo {
+    InstructionInfo { heap: true, ..info(mnemonic, operands) }
+}
+
+const fn terminator(mnemonic: &'static str, operands: &'static [OperandKind]) -> InstructionInfo {
+    InstructionInfo { terminator: true, ..info(mnemonic, operands) }
+}
+
+/// Calls may do anything the callee does.
+const fn call(mnemonic: &'static str, operands: &'static [OperandKind], tail: bool) -> InstructionInfo {
+    InstructionInfo { mnemonic, operands, terminator: tail, heap: true, globals: true }
+}
+
+impl Instruction {
+    /// Every instruction, in opcode order
+    pub fn all() -> impl Iterator<Item = Instruction> {
+        (0..=u8::MAX).map_while(|opcode| Instruction::try_from(opcode).ok())
+    }
+
+    pub const fn info(self) -> InstructionInfo {
+        use Instruction as I;
+        match self {
+            I::Load8 => info("load8", &[Register, Imm8]),
+            I::Load16 => info("load16", &[Register, Imm16]),
+            I::Load32 => info("load32", &[Register, Imm32]),
+            I::Load64 => info("load64", &[Register, Imm64]),
+            I::Loadf32 => info("loadf32", &[Register, Imm32]),
+            I::Loadf64 => info("loadf64", &[Register, Imm64]),
+            I::Copy => info("copy", REG_REG),
+            I::Clone => heap("clone", REG_REG),
+            I::Move => info("move", REG_REG),
+            I::Clear => info("clear", REG),
+            I::Destroy => heap("destroy", REG),
+            I::Forget => heap("forget", REG),
+            I::LoadReturn => info("loadreturn", REG),
+            I::FetchRef => heap("fetchref", REG_REG),
+            I::MakeShared => heap("makeshared", REG),
+            I::SetGlobal => InstructionInfo { globals: true, ..heap("setglobal", &[Id, Register]) },
+            I::CopyGlobal => InstructionInfo { globals: true, ..info("copyglobal", &[Register, Id]) },
+            I::CloneGlobal => InstructionInfo { globals: true, ..heap("cloneglobal", &[Register, Id]) },
+            I::AddU => info("addu", REG_REG_REG),
+            I::SubU => info("subu", REG_REG_REG),
+            I::MulU => info("mulu", REG_REG_REG),
+            I::DivU => info("divu", REG_REG_REG),
+            I::RemU => info("remu", REG_REG_REG),
+            I::AddS => info("adds", REG_REG_REG),
+            I::SubS => info("subs", REG_REG_REG),
+            I::MulS => info("muls", REG_REG_REG),
+            I::DivS => info("divs", REG_REG_REG),
+            I::RemS => info("rems", REG_REG_REG),
+            I::AddF => info("addf", REG_REG_REG),
+            I::SubF => info("subf", REG_REG_REG),
+            I::MulF => info("mulf", REG_REG_REG),
+            I::DivF => info("divf", REG_REG_REG),
+            I::And => info("and", REG_REG_REG),
+            I::Or => info("or", REG_REG_REG),
+            I::Xor => info("xor", REG_REG_REG),
+            I::Not => info("not", REG_REG),
+            I::ShiftLeft => info("shiftleft", REG_REG_REG),
+            I::LogicalShiftRight => info("logicalshiftright", REG_REG_REG),
+            I::ArithmeticShiftRight => info("arithmeticshiftright", REG_REG_REG),
+            I::ByteSwap => info("byteswap", REG_REG),
+            I::EqI => info("eqi", REG_REG_REG),
+            I::NeqI => info("neqi", REG_REG_REG),
+            I::EqF => info("eqf", REG_REG_REG),
+            I::NeqF => info("neqf", REG_REG_REG),
+            I::LtU => info("ltu", REG_REG_REG),
+            I::GtU => info("gtu", REG_REG_REG),
+            I::LteU => info("lteu", REG_REG_REG),
+            I::GteU => info("gteu", REG_REG_REG),
+            I::LtS => info("lts", REG_REG_REG),
+            I::GtS => info("gts", REG_REG_REG),
+            I::LteS => info("ltes", REG_REG_REG),
+            I::GteS => info("gtes", REG_REG_REG),
+            I::LtF => info("ltf", REG_REG_REG),
+            I::GtF => info("gtf", REG_REG_REG),
+            I::LteF => info("ltef", REG_REG_REG),
+            I::GteF => info("gtef", REG_REG_REG),
+            I::CreateObject => heap("createobject", &[Register, Id, Id]),
+            I::IsNull => info("isnull", REG_REG),
+            I::IsNaN => info("isnan", REG_REG),
+            I::IsInfinity => info("isinfinity", REG_REG),
+            I::GetField => heap("getfield", &[Register, Register, Id]),
+            I::CopyField => heap("copyfield", &[Register, Register, Id]),
+            I::TakeField => heap("takefield", &[Register, Register, Id]),
+            I::SetField => heap("setfield", &[Register, Id, Register]),
+            I::MoveField => heap("movefield", &[Register, Id, Register]),
+            I::PlaceField => heap("placefield", &[Register, Id, Register]),
+            I::Call => call("call", CALL, false),
+            I::CallTail => call("calltail", CALL, true),
+            I::Invoke => call("invoke", INVOKE, false),
+            I::InvokeTail => call("invoketail", INVOKE, true),
+            I::Return => terminator("return", REG),
+            I::ReturnTail => terminator("returntail", NONE),
+            I::ReturnUnit => terminator("returnunit", NONE),
+            I::ReturnTailUnit => terminator("returntailunit", NONE),
+            I::CreateClosure => heap("createclosure", &[Register, Id, Count, CallArgument]),
+            I::CreateFnObject => heap("createfnobject", &[Register, Id]),
+            I::Jump => terminator("jump", &[JumpBranch]),
+            I::If => terminator("if", &[Register, JumpBranch, JumpBranch]),
+            I::Switch => terminator("switch", &[Register, JumpBranch, Count, SwitchCase]),
+            I::Match => InstructionInfo { heap: true, ..terminator("match", &[Register, Count, MatchCase]) },
+            I::StartBlock => info("startblock", &[Id]),
+            I::SignExtend => info("signextend", CONVERT),
+            I::ZeroExtend => info("zeroextend", CONVERT),
+            I::Truncate => info("truncate", CONVERT),
+            I::IntToFloatS => info("inttofloats", CONVERT),
+            I::IntToFloatU => info("inttofloatu", CONVERT),
+            I::FloatToIntSat => info("floattointsat", CONVERT),
+            I::FloatToInt => info("floattoint", CONVERT),
+            I::FloatToFloat => info("floattofloat", CONVERT),
+            I::CheckedAddU => info("checkedaddu", REG_REG_REG),
+            I::CheckedSubU => info("checkedsubu", REG_REG_REG),
+            I::CheckedMulU => info("checkedmulu", REG_REG_REG),
+            I::CheckedAddS => info("checkedadds", REG_REG_REG),
+            I::CheckedSubS => info("checkedsubs", REG_REG_REG),
+            I::CheckedMulS => info("checkedmuls", REG_REG_REG),
+            I::CheckedDivS => info("checkeddivs", REG_REG_REG),
+            I::SaturatingAddU => info("saturatingaddu", REG_REG_REG),
+            I::SaturatingSubU => info("saturatingsubu", REG_REG_REG),
+            I::SaturatingMulU => info("saturatingmulu", REG_REG_REG),
+            I::SaturatingAddS => info("saturatingadds", REG_REG_REG),
+            I::SaturatingSubS => info("saturatingsubs", REG_REG_REG),
+            I::SaturatingMulS => info("saturatingmuls", REG_REG_REG),
+            I::SaturatingDivS => info("saturatingdivs", REG_REG_REG),
+            I::PopCount => info("popcount", REG_REG),
+            I::CountLeadingZeros => info("countleadingzeros", REG_REG),
+            I::CountTrailingZeros => info("counttrailingzeros", REG_REG),
+            I::RotateLeft => info("rotateleft", REG_REG_REG),
+            I::RotateRight => info("rotateright", REG_REG_REG),
+            I::BitReverse => info("bitreverse", REG_REG),
+            I::RemF => info("remf", REG_REG_REG),
+            I::NegF => info("negf", REG_REG),
+            I::AbsF => info("absf", REG_REG),
+            I::MinF => info("minf", REG_REG_REG),
+            I::MaxF => info("maxf", REG_REG_REG),
+            I::SqrtF => info("sqrtf", REG_REG),
+            I::FloorF => info("floorf", REG_REG),
+            I::CeilF => info("ceilf", REG_REG),
+            I::TruncF => info("truncf", REG_REG),
+            I::RoundF => info("roundf", REG_REG),
+            I::FmaF => info("fmaf", &[Register, Register, Register, Register]),
+            I::CopySignF => info("copysignf", REG_REG_REG),
+            I::Handle => call("handle", &[Id, Register, Register], false),
+            I::Perform => call("perform", CALL, false),
+            I::Resume => call("resume", REG_REG, false),
+            I::DropContinuation => heap("dropcontinuation", REG),
+            I::CreateArray => heap("createarray", REG_REG_REG),
+            I::GetElement => heap("getelement", REG_REG_REG),
+            I::SetElement => heap("setelement", REG_REG_REG),
+            I::ArrayLength => heap("arraylength", REG_REG),
+            I::SliceArray => heap("slicearray", &[Register, Register, Register, Register]),
+            I::CopyElements => heap("copyelements", &[Register, Register, Register, Register, Register]),
+            I::LoadString => heap("loadstring", &[Register, Id]),
+            I::ConcatString => heap("concatstring", REG_REG_REG),
+            I::StringLength => heap("stringlength", REG_REG),
+            I::StringByte => heap("stringbyte", REG_REG_REG),
+            I::StringChar => heap("stringchar", REG_REG_REG),
+            I::SliceString => heap("slicestring", &[Register, Register, Register, Register]),
+            I::CompareString => heap("comparestring", REG_REG_REG),
+        }
+    }
+
+    pub const fn mnemonic(self) -> &'static str {
+        self.info().mnemonic
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Instruction> {
+        Instruction::all().find(|instruction| instruction.mnemonic() == mnemonic)
+    }
+
+    pub const fn is_terminator(self) -> bool {
+        self.info().terminator
+    }
+}
+
+impl DecodedInstruction {
+    /// The registers whose values the instruction uses
+    pub fn reads(&self) -> Vec<Register> {
+        use DecodedInstruction as D;
+        match self {
+            D::Load8 { .. } | D::Load16 { .. } | D::Load32 { .. } | D::Load64 { .. } => vec![],
+            D::Loadf32 { .. } | D::Loadf64 { .. } | D::LoadReturn { .. } => vec![],
+            D::Clear { .. } | D::CopyGlobal { .. } | D::CloneGlobal { .. } | D::CreateObject { .. } => vec![],
+            D::CreateFnObject { .. } | D::ReturnTail | D::ReturnUnit | D::ReturnTailUnit => vec![],
+            D::Jump { .. } | D::StartBlock { .. } => vec![],
+            D::Copy { src, .. } | D::Clone { src, .. } | D::Move { src, .. } | D::FetchRef { src, .. } => vec![*src],
+            D::Unary { src, .. } | D::Convert { src, .. } | D::SetGlobal { src, .. } | D::Return { src } => vec![*src],
+            D::Switch { src, .. } | D::Match { src, .. } => vec![*src],
+            D::Destroy { register } | D::Forget { register } | D::MakeShared { register } => vec![*register],
+            D::Binary { lhs, rhs, .. } => vec![*lhs, *rhs],
+            D::FmaF { lhs, rhs, addend, .. } => vec![*lhs, *rhs, *addend],
+            D::Handle { handler, body, .. } => vec![*handler, *body],
+            D::Resume { continuation, value } => vec![*continuation, *value],
+            D::DropContinuation { continuation } => vec![*continuation],
+            D::CreateArray { len, fill, .. } => vec![*len, *fill],
+            D::GetElement { array, index, .. } => vec![*array, *index],
+            D::SetElement { array, index, src } => vec![*array, *index, *src],
+            D::ArrayLength { array, .. } => vec![*array],
+            D::SliceArray { array, start, end, .. } => vec![*array, *start, *end],
+            D::CopyElements { dst, dst_start, src, src_start, count } => vec![*dst, *dst_start, *src, *src_start, *count],
+            D::LoadString { .. } => vec![],
+            D::ConcatString { lhs, rhs, .. } | D::CompareString { lhs, rhs, .. } => vec![*lhs, *rhs],
+            D::StringLength { src, .. } => vec![*src],
+            D::StringByte { string, index, .. } | D::StringChar { string, index, .. } => vec![*string, *index],
+            D::SliceString { string, start, end, .. } => vec![*string, *start, *end],
+            D::GetField { object, .. } | D::CopyField { object, .. } | D::TakeField { object, .. } => vec![*object],
+            D::SetField { object, src, .. } | D::MoveField { object, src, .. } | D::PlaceField { object, src, .. } => {
+                vec![*object, *src]
+            }
+            D::Call { arguments, .. } | D::CallTail { arguments, .. } | D::Perform { arguments, .. } => {
+                arguments.iter().map(|argument| argument.register).collect()
+            }
+            D::Invoke { closure, arguments } | D::InvokeTail { closure, arguments } => {
+                std::iter::once(*closure).chain(arguments.iter().map(|argument| argument.register)).collect()
+            }
+            D::CreateClosure { captures, .. } => captures.iter().map(|capture| capture.register).collect(),
+            D::If { condition, .. } => vec![*condition],
+        }
+    }
+
+    /// The registers the instruction assigns, including registers it clears
+    /// by moving their value out
+    pub fn writes(&self) -> Vec<Register> {
+        use DecodedInstruction as D;
+        match self {
+            D::Load8 { dst, .. } | D::Load16 { dst, .. } | D::Load32 { dst, .. } | D::Load64 { dst, .. } => vec![*dst],
+            D::Loadf32 { dst, .. } | D::Loadf64 { dst, .. } | D::LoadReturn { dst } => vec![*dst],
+            D::Copy { dst, .. } | D::Clone { dst, .. } | D::FetchRef { dst, .. } => vec![*dst],
+            D::CopyGlobal { dst, .. } | D::CloneGlobal { dst, .. } => vec![*dst],
+            D::Binary { dst, .. } | D::Unary { dst, .. } | D::Convert { dst, .. } => vec![*dst],
+            D::FmaF { dst, .. } | D::CreateObject { dst, .. } => vec![*dst],
+            D::GetField { dst, .. } | D::CopyField { dst, .. } | D::TakeField { dst, .. } => vec![*dst],
+            D::CreateClosure { dst, .. } | D::CreateFnObject { dst, .. } => vec![*dst],
+            D::Move { dst, src } => vec![*dst, *src],
+            D::Clear { register } | D::Destroy { register } | D::Forget { register } => vec![*register],
+            D::SetGlobal { src, .. } | D::MoveField { src, .. } | D::PlaceField { src, .. } => vec![*src],
+            D::MakeShared { .. } | D::SetField { .. } | D::Call { .. } | D::CallTail { .. } => vec![],
+            D::Invoke { .. } | D::InvokeTail { .. } | D::Return { .. } | D::ReturnTail => vec![],
+            D::ReturnUnit | D::ReturnTailUnit | D::Jump { .. } | D::If { .. } => vec![],
+            D::Switch { .. } | D::Match { .. } | D::StartBlock { .. } => vec![],
+            D::Handle { .. } | D::Perform { .. } => vec![],
+            D::Resume { continuation, .. } | D::DropContinuation { continuation } => vec![*continuation],
+            D::CreateArray { dst, .. } | D::GetElement { dst, .. } | D::ArrayLength { dst, .. } => vec![*dst],
+            D::SliceArray { dst, .. } | D::LoadString { dst, .. } | D::ConcatString { dst, .. } => vec![*dst],
+            D::StringLength { dst, .. } | D::StringByte { dst, .. } | D::StringChar { dst, .. } => vec![*dst],
+            D::SliceString { dst, .. } | D::CompareString { dst, .. } => vec![*dst],
+            D::SetElement { .. } | D::CopyElements { .. } => vec![],
+        }
+    }
+}
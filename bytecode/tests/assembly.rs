@@ -0,0 +1,91 @@
+use bytecode::*;
+
+const SOURCE: &str = "    load8 r0, 255
+    load64 r1, 18446744073709551615
+    loadf32 r2, 1.5
+    loadf64 r3, -2.5e-7
+    loadf64 r4, -inf
+    loadf32 r5, 0x7fc00001
+    move r0, r1
+    destroy r6
+    setglobal g3, r1
+    cloneglobal r1, g3
+    adds r3, r1, r2
+    byteswap r0, r1
+    signextend r2, r0, i64
+    floattointsat r1, r3, u16
+    fmaf r3, r2, r1, r0
+    createobject r0, 4, 1
+    takefield r1, r0, 2
+    placefield r0, 2, r1
+    calltail 9 (+r2, r0)
+    invoke r4 ()
+    createclosure r4, 1 (r2, +r3)
+    handle 2, r4, r5
+    perform 2 (+r1)
+    resume r1, r0
+    setelement r0, r1, r2
+    copyelements r0, r1, r2, r3, r4
+    loadstring r0, 5
+    slicestring r1, r0, r2, r3
+block0:
+    jump @block2+4
+    if r0, @block1, @block2-8
+    switch r0 { 3 -> @block1, 5 -> @block2, _ -> @block0 }
+    match r0 { 1 -> @block2, 0 -> @block1 }
+    return r0
+    returntailunit
+";
+
+#[test]
+fn test_assembly_roundtrip() {
+    let bytes = assemble(SOURCE).expect("assemble");
+    let text = disassemble(&bytes).expect("disassemble");
+    assert_eq!(text, SOURCE);
+    assert_eq!(&*assemble(&text).expect("reassemble"), &*bytes);
+
+    let compact = assemble_with(SOURCE, Encoding::Compact).expect("assemble");
+    assert_eq!(disassemble_with(&compact, Encoding::Compact).expect("disassemble"), SOURCE);
+    assert!(compact.len() < bytes.len());
+}
+
+#[test]
+fn test_assembly_accepts_comments_and_tags() {
+    let source = "
+        ; Option has the variants `None` and `Some`
+        .tag None 0
+        .tag Some 1
+        match r0 { Some -> @block1, None -> @block2, }  ; trailing comma
+    ";
+    let bytes = assemble(source).expect("assemble");
+    assert_eq!(disassemble(&bytes).expect("disassemble"), "    match r0 { 1 -> @block1, 0 -> @block2 }\n");
+
+    // with the declared names, the text comes back as written
+    let source = ".tag None 0\n.tag Some 1\n    match r0 { Some -> @block1, None -> @block2 }\n    match r0 { 2 -> @block1 }\n";
+    let (bytes, tags) = assemble_tagged(source, Encoding::Compact).expect("assemble");
+    assert_eq!(tags.get("Some"), Some(1));
+    assert_eq!(disassemble_tagged(&bytes, Encoding::Compact, &tags).expect("disassemble"), source);
+}
+
+#[test]
+fn test_assembly_accepts_negative_immediates() {
+    let source = "load8 r0, -1\nload16 r1, -32768\nload64 r2, -0x10\nswitch r0 { -2 -> @block1, _ -> @block0 }";
+    let expected = "    load8 r0, 255
+    load16 r1, 32768
+    load64 r2, 18446744073709551600
+    switch r0 { 18446744073709551614 -> @block1, _ -> @block0 }
+";
+    assert_eq!(disassemble(&assemble(source).expect("assemble")).expect("disassemble"), expected);
+}
+
+#[test]
+fn test_assembly_errors_report_lines() {
+    let error = |source: &str| assemble(source).err().map(|error| error.to_string());
+    assert_eq!(error("returnunit\nfrobnicate r0"), Some("line 2: unknown mnemonic `frobnicate`".to_string()));
+    assert_eq!(error("load8 r0, 256"), Some("line 1: invalid or out of range number `256`".to_string()));
+    assert_eq!(error("\n\nmatch r0 { Some -> @block1 }"), Some("line 3: unknown tag `Some`".to_string()));
+    assert_eq!(error("switch r0 { 1 -> @block1 }"), Some("line 1: switch is missing a `_` default case".to_string()));
+    assert_eq!(error("load8 r0, -129"), Some("line 1: invalid or out of range number `-129`".to_string()));
+    assert_eq!(error("loadf32 r0, 0x1ffffffff"), Some("line 1: float bits `0x1ffffffff` are wider than 32 bits".to_string()));
+    assert_eq!(error("copy r0 r1"), Some("line 1: expected `,` but found Word(\"r1\")".to_string()));
+}
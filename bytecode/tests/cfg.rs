@@ -0,0 +1,78 @@
+use bytecode::{
+    assemble,
+    cfg::{Cfg, CfgError},
+};
+
+fn cfg(source: &str) -> Cfg {
+    Cfg::new(&assemble(source).expect("assemble")).expect("cfg")
+}
+
+#[test]
+fn test_cfg_splits_blocks_and_links_edges() {
+    let cfg = cfg("
+        load8 r0, 1
+    block0:
+        if r0, @block1, @block2
+    block1:
+        load8 r1, 2
+        jump @block3
+    block2:
+        load8 r1, 3
+    block3:
+        return r1
+        returnunit
+    ");
+    let edges = cfg.blocks().iter()
+        .map(|block| (block.successors.clone(), block.predecessors.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(edges, vec![
+        (vec![1], vec![]),
+        (vec![2, 3], vec![0]),
+        (vec![4], vec![1]),
+        (vec![4], vec![1]),
+        (vec![], vec![2, 3]),
+        (vec![], vec![]),
+    ]);
+    assert_eq!(cfg.block_instructions(2).len(), 3);
+    assert_eq!(cfg.block_at(cfg.blocks()[2].start + 6), Some(2));
+
+    let dominators = (0..cfg.blocks().len()).map(|block| cfg.immediate_dominator(block)).collect::<Vec<_>>();
+    assert_eq!(dominators, vec![None, Some(0), Some(1), Some(1), Some(1), None]);
+    assert!(cfg.dominates(1, 4));
+    assert!(!cfg.dominates(2, 4));
+    assert!(!cfg.is_reachable(5));
+    assert!(cfg.loops().is_empty());
+}
+
+#[test]
+fn test_cfg_finds_nested_loops() {
+    let cfg = cfg("
+    block0:
+        if r0, @block1, @block4
+    block1:
+        if r1, @block2, @block3
+    block2:
+        jump @block1
+    block3:
+        jump @block0
+    block4:
+        returnunit
+    ");
+    let loops = cfg.loops().iter()
+        .map(|l| (l.header, l.blocks.clone(), l.parent, l.depth))
+        .collect::<Vec<_>>();
+    assert_eq!(loops, vec![(0, vec![0, 1, 2, 3], None, 1), (1, vec![1, 2], Some(0), 2)]);
+    let depths = (0..5).map(|block| cfg.loop_depth(block)).collect::<Vec<_>>();
+    assert_eq!(depths, vec![1, 2, 2, 1, 0]);
+    assert_eq!(cfg.innermost_loop(2), Some(1));
+}
+
+#[test]
+fn test_cfg_rejects_bad_branches() {
+    let code = assemble("jump @block3").unwrap();
+    assert_eq!(Cfg::new(&code).err(), Some(CfgError::UnknownBlock { offset: 0, block_id: 3 }));
+    let code = assemble("block0:\njump @block0+1").unwrap();
+    assert_eq!(Cfg::new(&code).err(), Some(CfgError::BranchOutOfRange { offset: 5, block_id: 0, branch_offset: 1 }));
+    let code = assemble("block0:\nblock0:\nreturnunit").unwrap();
+    assert_eq!(Cfg::new(&code).err(), Some(CfgError::DuplicateBlock { offset: 5, block_id: 0 }));
+}
@@ -0,0 +1,91 @@
+use bytecode::*;
+
+fn bytes(parts: &[&[u8]]) -> Vec<u8> {
+    parts.concat()
+}
+
+#[test]
+fn test_decode_operands_and_offsets() {
+    let code = bytes(&[
+        &[Instruction::Load32.into()], &3u32.to_le_bytes(), &7u32.to_le_bytes(),
+        &[Instruction::AddU.into()], &0u32.to_le_bytes(), &1u32.to_le_bytes(), &2u32.to_le_bytes(),
+        &[Instruction::Call.into()], &4u32.to_le_bytes(), &2u32.to_le_bytes(),
+        &[1], &0u32.to_le_bytes(), &[0], &3u32.to_le_bytes(),
+        &[Instruction::ReturnUnit.into()],
+    ]);
+    let decoded = instructions(&code).collect::<Result<Vec<_>, _>>().expect("decode");
+    assert_eq!(decoded, vec![
+        (0, DecodedInstruction::Load32 { dst: 3, value: 7 }),
+        (9, DecodedInstruction::Binary { instruction: Instruction::AddU, dst: 0, lhs: 1, rhs: 2 }),
+        (22, DecodedInstruction::Call {
+            function: 4,
+            arguments: vec![
+                CallArgument { increment_ref: true, register: 0 },
+                CallArgument { increment_ref: false, register: 3 },
+            ],
+        }),
+        (41, DecodedInstruction::ReturnUnit),
+    ]);
+}
+
+#[test]
+fn test_decode_switch_and_match_tables() {
+    let code = bytes(&[
+        &[Instruction::Switch.into()], &1u32.to_le_bytes(),
+        &9u32.to_le_bytes(), &0i32.to_le_bytes(),
+        &1u32.to_le_bytes(), &42u64.to_le_bytes(), &2u32.to_le_bytes(), &(-4i32).to_le_bytes(),
+        &[Instruction::Match.into()], &5u32.to_le_bytes(), &1u32.to_le_bytes(),
+        &3u32.to_le_bytes(), &6u32.to_le_bytes(), &8i32.to_le_bytes(),
+    ]);
+    let (switch, len) = DecodedInstruction::decode(&code).expect("switch");
+    assert_eq!(switch, DecodedInstruction::Switch {
+        src: 1,
+        default: JumpBranch { block_id: 9, offset: 0 },
+        cases: vec![SwitchCase { value: 42, branch: JumpBranch { block_id: 2, offset: -4 } }],
+    });
+    assert_eq!(switch.instruction(), Instruction::Switch);
+    let (matched, _) = DecodedInstruction::decode(&code[len..]).expect("match");
+    assert_eq!(matched, DecodedInstruction::Match {
+        src: 5,
+        cases: vec![MatchCase { tag: 3, branch: JumpBranch { block_id: 6, offset: 8 } }],
+    });
+}
+
+#[test]
+fn test_truncated_operand_reports_offset() {
+    let code = bytes(&[
+        &[Instruction::ReturnUnit.into()],
+        &[Instruction::Load64.into()], &0u32.to_le_bytes(), &[1, 2],
+    ]);
+    let mut iter = instructions(&code);
+    assert_eq!(iter.next(), Some(Ok((0, DecodedInstruction::ReturnUnit))));
+    assert_eq!(iter.next(), Some(Err(DecodeError::TruncatedOperand { offset: 6, operand: OperandKind::Imm64 })));
+    assert_eq!(iter.next(), None);
+
+    // A call claiming more arguments than there are bytes
+    let code = bytes(&[&[Instruction::Call.into()], &0u32.to_le_bytes(), &u32::MAX.to_le_bytes(), &[0; 7]]);
+    assert_eq!(
+        DecodedInstruction::decode(&code).err(),
+        Some(DecodeError::TruncatedOperand { offset: 14, operand: OperandKind::CallArgument }),
+    );
+}
+
+#[test]
+fn test_invalid_opcode_is_an_error() {
+    assert!(Instruction::try_from(138).is_err());
+    assert_eq!(Instruction::try_from(137), Ok(Instruction::CompareString));
+
+    let code = [Instruction::ReturnUnit.into(), 0xFF];
+    let result = instructions(&code).collect::<Result<Vec<_>, _>>();
+    assert_eq!(result, Err(DecodeError::InvalidOpcode { offset: 1, opcode: 0xFF }));
+    assert_eq!(DecodedInstruction::decode(&[]).err(), Some(DecodeError::MissingInstruction { offset: 0 }));
+}
+
+#[test]
+fn test_operand_helpers_check_length() {
+    assert_eq!(decode_id(&[1, 0]), Err(DecodeError::TruncatedOperand { offset: 0, operand: OperandKind::Id }));
+    assert_eq!(decode_jump_branch(&[0; 7]).err(), Some(DecodeError::TruncatedOperand { offset: 0, operand: OperandKind::JumpBranch }));
+    assert_eq!(decode_call_argument(&[1, 2, 0, 0, 0]), Ok(CallArgument { increment_ref: true, register: 2 }));
+    assert_eq!(decode_numeric_type(&[11]), Ok(NumericType::F64));
+    assert_eq!(decode_numeric_type(&[12]), Err(DecodeError::InvalidOperand { offset: 0, operand: OperandKind::Type }));
+}
@@ -0,0 +1,126 @@
+use bytecode::*;
+
+fn branch(block_id: Id, offset: i32) -> JumpBranch {
+    JumpBranch { block_id, offset }
+}
+
+#[test]
+fn test_encode_decode_roundtrip() {
+    let argument = CallArgument { increment_ref: true, register: 2 };
+    let program = vec![
+        DecodedInstruction::Load8 { dst: 0, value: 255 },
+        DecodedInstruction::Load16 { dst: 1, value: 0xBEEF },
+        DecodedInstruction::Load32 { dst: 2, value: 7 },
+        DecodedInstruction::Load64 { dst: 3, value: u64::MAX },
+        DecodedInstruction::Loadf32 { dst: 4, value: 1.5 },
+        DecodedInstruction::Loadf64 { dst: 5, value: -2.25 },
+        DecodedInstruction::Move { dst: 0, src: 1 },
+        DecodedInstruction::Copy { dst: 300, src: u32::MAX },
+        DecodedInstruction::Destroy { register: 6 },
+        DecodedInstruction::LoadReturn { dst: 6 },
+        DecodedInstruction::SetGlobal { global: 3, src: 1 },
+        DecodedInstruction::CloneGlobal { dst: 1, global: 3 },
+        DecodedInstruction::Binary { instruction: Instruction::LtS, dst: 0, lhs: 1, rhs: 2 },
+        DecodedInstruction::Unary { instruction: Instruction::ByteSwap, dst: 0, src: 1 },
+        DecodedInstruction::CreateObject { dst: 0, type_id: 4, variant: 1 },
+        DecodedInstruction::TakeField { dst: 1, object: 0, field: 2 },
+        DecodedInstruction::PlaceField { object: 0, field: 2, src: 1 },
+        DecodedInstruction::CallTail { function: 9, arguments: vec![argument] },
+        DecodedInstruction::Invoke { closure: 4, arguments: vec![] },
+        DecodedInstruction::CreateClosure { dst: 4, function: 1, captures: vec![argument, argument] },
+        DecodedInstruction::CreateFnObject { dst: 4, function: 1 },
+        DecodedInstruction::StartBlock { id: 0 },
+        DecodedInstruction::Jump { branch: branch(0, 0) },
+        DecodedInstruction::If { condition: 0, then_branch: branch(1, 4), else_branch: branch(2, -4) },
+        DecodedInstruction::If { condition: 0, then_branch: branch(200, i32::MAX), else_branch: branch(2, i32::MIN) },
+        DecodedInstruction::Switch {
+            src: 0,
+            default: branch(0, 0),
+            cases: vec![SwitchCase { value: 3, branch: branch(1, 0) }],
+        },
+        DecodedInstruction::Match { src: 0, cases: vec![MatchCase { tag: 1, branch: branch(2, 0) }] },
+        DecodedInstruction::Return { src: 0 },
+        DecodedInstruction::ReturnTailUnit,
+    ];
+    let mut sizes = Vec::new();
+    for encoding in [Encoding::Fixed, Encoding::Compact] {
+        let mut bytes = Vec::new();
+        for instruction in &program {
+            instruction.encode_with(&mut bytes, encoding);
+        }
+        let decoded = Instructions::with_encoding(&bytes, encoding)
+            .map(|result| result.map(|(_, instruction)| instruction))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("decode");
+        assert_eq!(decoded, program, "{:?}", encoding);
+        sizes.push(bytes.len());
+    }
+    assert!(sizes[1] * 2 < sizes[0], "{:?}", sizes);
+}
+
+#[test]
+fn test_compact_operands_reject_overlong_values() {
+    // `copy` with a register that doesn't fit in a `u32`
+    let code = [Instruction::Copy.into(), 0x80, 0x80, 0x80, 0x80, 0x10, 0];
+    assert_eq!(
+        DecodedInstruction::decode_with(&code, 0, Encoding::Compact),
+        Err(DecodeError::InvalidOperand { offset: 1, operand: OperandKind::Register }),
+    );
+    let code = [Instruction::Copy.into(), 0, 0x80];
+    assert_eq!(
+        DecodedInstruction::decode_with(&code, 0, Encoding::Compact),
+        Err(DecodeError::TruncatedOperand { offset: 2, operand: OperandKind::Register }),
+    );
+}
+
+#[test]
+fn test_builder_resolves_forward_branches() {
+    for encoding in [Encoding::Fixed, Encoding::Compact] {
+        let mut builder = FunctionBuilder::with_encoding(encoding);
+        let entry = builder.label();
+        let exit = builder.label();
+        let body = builder.label();
+        builder.start_block(entry);
+        builder.branch_if(0, body, exit);
+        builder.start_block(body);
+        builder.switch(1, exit, &[(5, entry)]);
+        builder.start_block(exit);
+        builder.emit(DecodedInstruction::ReturnUnit);
+        let code = builder.finish().expect("finish");
+
+        let decoded = Instructions::with_encoding(&code, encoding)
+            .map(|result| result.map(|(_, instruction)| instruction))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("decode");
+        assert_eq!(decoded, resolved_branches(), "{:?}", encoding);
+    }
+}
+
+fn resolved_branches() -> Vec<DecodedInstruction> {
+    vec![
+        DecodedInstruction::StartBlock { id: 0 },
+        DecodedInstruction::If { condition: 0, then_branch: branch(1, 0), else_branch: branch(2, 0) },
+        DecodedInstruction::StartBlock { id: 1 },
+        DecodedInstruction::Switch {
+            src: 1,
+            default: branch(2, 0),
+            cases: vec![SwitchCase { value: 5, branch: branch(0, 0) }],
+        },
+        DecodedInstruction::StartBlock { id: 2 },
+        DecodedInstruction::ReturnUnit,
+    ]
+}
+
+#[test]
+fn test_builder_rejects_bad_labels() {
+    let mut builder = FunctionBuilder::new();
+    let missing = builder.label();
+    builder.jump(missing);
+    assert_eq!(builder.finish().err(), Some(BuildError::UnplacedLabel(missing)));
+
+    let mut builder = FunctionBuilder::new();
+    let twice = builder.label();
+    builder.start_block(twice);
+    builder.start_block(twice);
+    assert_eq!(builder.finish().err(), Some(BuildError::DuplicateLabel(twice)));
+}
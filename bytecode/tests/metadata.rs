@@ -0,0 +1,62 @@
+use bytecode::*;
+
+#[test]
+fn test_operand_signatures_match_the_decoder() {
+    assert_eq!(Instruction::all().count(), 138);
+    for instruction in Instruction::all() {
+        let operands = instruction.info().operands;
+        // With every count zero, the entries after a count take no space.
+        let size = operands.iter()
+            .enumerate()
+            .filter(|(i, _)| *i == 0 || operands[i - 1] != OperandKind::Count)
+            .map(|(_, operand)| operand.size())
+            .sum::<usize>();
+        let mut bytes = vec![instruction.into()];
+        bytes.resize(1 + size, 0);
+        // Zero is not a valid type, so conversions get a real one.
+        if operands.last() == Some(&OperandKind::Type) {
+            bytes[size] = NumericType::U8.into();
+        }
+        let (_, len) = DecodedInstruction::decode(&bytes).expect("decode");
+        assert_eq!(len, bytes.len(), "{:?}", instruction);
+        assert_eq!(Instruction::from_mnemonic(instruction.mnemonic()), Some(instruction));
+    }
+}
+
+#[test]
+fn test_terminators_and_effects() {
+    let terminators = Instruction::all().filter(|instruction| instruction.is_terminator()).collect::<Vec<_>>();
+    assert_eq!(terminators, vec![
+        Instruction::CallTail,
+        Instruction::InvokeTail,
+        Instruction::Return,
+        Instruction::ReturnTail,
+        Instruction::ReturnUnit,
+        Instruction::ReturnTailUnit,
+        Instruction::Jump,
+        Instruction::If,
+        Instruction::Switch,
+        Instruction::Match,
+    ]);
+    assert!(Instruction::CreateObject.info().heap);
+    assert!(Instruction::Destroy.info().heap);
+    assert!(!Instruction::AddU.info().heap);
+    assert!(Instruction::CopyGlobal.info().globals);
+    assert!(!Instruction::SetField.info().globals);
+}
+
+#[test]
+fn test_register_reads_and_writes() {
+    let argument = |register| CallArgument { increment_ref: false, register };
+    let cases = [
+        (DecodedInstruction::Move { dst: 1, src: 2 }, vec![2], vec![1, 2]),
+        (DecodedInstruction::Binary { instruction: Instruction::AddU, dst: 0, lhs: 1, rhs: 2 }, vec![1, 2], vec![0]),
+        (DecodedInstruction::PlaceField { object: 3, field: 0, src: 4 }, vec![3, 4], vec![4]),
+        (DecodedInstruction::Invoke { closure: 5, arguments: vec![argument(6), argument(7)] }, vec![5, 6, 7], vec![]),
+        (DecodedInstruction::LoadReturn { dst: 8 }, vec![], vec![8]),
+    ];
+    for (instruction, reads, writes) in cases {
+        assert_eq!(instruction.reads(), reads, "{:?}", instruction);
+        assert_eq!(instruction.writes(), writes, "{:?}", instruction);
+    }
+}
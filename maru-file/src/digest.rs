@@ -0,0 +1,77 @@
+use std::io::{self, Write};
+
+/// The CRC-32 (IEEE) lookup table.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A writer that hashes the bytes written to it with both CRC-32 and 64 bit
+/// FNV-1a.
+pub(crate) struct Digest {
+    crc: u32,
+    fnv: u64,
+}
+
+impl Digest {
+    pub fn new() -> Self {
+        Digest { crc: !0, fnv: FNV_OFFSET }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.crc = CRC_TABLE[((self.crc ^ *byte as u32) & 0xff) as usize] ^ (self.crc >> 8);
+            self.fnv = (self.fnv ^ *byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn crc32(&self) -> u32 {
+        !self.crc
+    }
+
+    pub fn fnv1a(&self) -> u64 {
+        self.fnv
+    }
+}
+
+impl Write for Digest {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.update(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut digest = Digest::new();
+    digest.update(bytes);
+    digest.crc32()
+}
+
+/// The 64 bit FNV-1a hash of a module name followed by the bytes of each of
+/// its sections, which is stored as the content hash.
+pub(crate) fn content_hash<'a>(module_name: u32, sections: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    let mut digest = Digest::new();
+    digest.update(&module_name.to_le_bytes());
+    for section in sections {
+        digest.update(section);
+    }
+    digest.fnv1a()
+}
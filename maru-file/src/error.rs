@@ -0,0 +1,122 @@
+use std::fmt;
+
+use crate::Section;
+
+/// An error raised while parsing a Maru file.
+///
+/// `index` is the entry of `section` being parsed, or `None` while parsing
+/// the section's own fields. `offset` is where the bad field starts, counted
+/// from the start of the bytes passed to `from_binary`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaruFileError {
+    /// The file doesn't start with `0x4D`
+    InvalidMagic { found: u8 },
+    /// A field needs `expected` bytes but only `actual` bytes are left
+    Truncated { section: Section, index: Option<u32>, offset: usize, expected: usize, actual: usize },
+    /// The byte at `offset` is not a known `MaruTypeTag`
+    UnknownTypeTag { section: Section, index: Option<u32>, offset: usize, tag: u8 },
+    /// The byte at `offset` is not a known `MaruSymbolKind`
+    UnknownSymbolKind { section: Section, index: Option<u32>, offset: usize, kind: u8 },
+    /// The byte at `offset` is not a known `MaruVersionReq` operator
+    UnknownVersionOp { section: Section, index: Option<u32>, offset: usize, op: u8 },
+    /// The string starting at `offset` is not valid UTF-8
+    InvalidUtf8 { section: Section, index: Option<u32>, offset: usize },
+    /// The string starting at `offset` runs to the end of the file without a NUL
+    UnterminatedString { section: Section, index: Option<u32>, offset: usize },
+    /// The type tag at `offset` nests more than `MAX_TYPE_DEPTH` array types
+    TypeTooDeep { section: Section, index: Option<u32>, offset: usize },
+    /// Entry `index` of the section directory repeats an earlier section
+    DuplicateSection { index: u32, offset: usize, section: Section },
+    /// The checksum at `offset` doesn't match the bytes that follow it
+    ChecksumMismatch { offset: usize, expected: u32, found: u32 },
+    /// The content hash at `offset` doesn't match the contents of the file
+    ContentHashMismatch { offset: usize, expected: u64, found: u64 },
+}
+
+impl MaruFileError {
+    /// The section the error occurred in
+    pub fn section(&self) -> Section {
+        match self {
+            MaruFileError::InvalidMagic { .. }
+            | MaruFileError::DuplicateSection { .. }
+            | MaruFileError::ChecksumMismatch { .. }
+            | MaruFileError::ContentHashMismatch { .. } => Section::Header,
+            MaruFileError::Truncated { section, .. }
+            | MaruFileError::UnknownTypeTag { section, .. }
+            | MaruFileError::UnknownSymbolKind { section, .. }
+            | MaruFileError::UnknownVersionOp { section, .. }
+            | MaruFileError::InvalidUtf8 { section, .. }
+            | MaruFileError::UnterminatedString { section, .. }
+            | MaruFileError::TypeTooDeep { section, .. } => *section,
+        }
+    }
+
+    /// The entry of the section the error occurred in
+    pub fn index(&self) -> Option<u32> {
+        match self {
+            MaruFileError::InvalidMagic { .. }
+            | MaruFileError::ChecksumMismatch { .. }
+            | MaruFileError::ContentHashMismatch { .. } => None,
+            MaruFileError::DuplicateSection { index, .. } => Some(*index),
+            MaruFileError::Truncated { index, .. }
+            | MaruFileError::UnknownTypeTag { index, .. }
+            | MaruFileError::UnknownSymbolKind { index, .. }
+            | MaruFileError::UnknownVersionOp { index, .. }
+            | MaruFileError::InvalidUtf8 { index, .. }
+            | MaruFileError::UnterminatedString { index, .. }
+            | MaruFileError::TypeTooDeep { index, .. } => *index,
+        }
+    }
+
+    /// The offset the error occurred at
+    pub fn offset(&self) -> usize {
+        match self {
+            MaruFileError::InvalidMagic { .. } => 0,
+            MaruFileError::Truncated { offset, .. }
+            | MaruFileError::UnknownTypeTag { offset, .. }
+            | MaruFileError::UnknownSymbolKind { offset, .. }
+            | MaruFileError::UnknownVersionOp { offset, .. }
+            | MaruFileError::InvalidUtf8 { offset, .. }
+            | MaruFileError::UnterminatedString { offset, .. }
+            | MaruFileError::TypeTooDeep { offset, .. }
+            | MaruFileError::DuplicateSection { offset, .. }
+            | MaruFileError::ChecksumMismatch { offset, .. }
+            | MaruFileError::ContentHashMismatch { offset, .. } => *offset,
+        }
+    }
+}
+
+impl fmt::Display for MaruFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let MaruFileError::InvalidMagic { found } = self {
+            return write!(f, "invalid magic number {:#04x}, expected 0x4d", found);
+        }
+        match self.index() {
+            Some(index) => write!(f, "{} entry {} at offset {}: ", self.section(), index, self.offset())?,
+            None => write!(f, "{} at offset {}: ", self.section(), self.offset())?,
+        }
+        match self {
+            MaruFileError::InvalidMagic { .. } => Ok(()),
+            MaruFileError::Truncated { expected, actual, .. } => {
+                write!(f, "expected {} bytes but only {} remain", expected, actual)
+            }
+            MaruFileError::UnknownTypeTag { tag, .. } => write!(f, "unknown type tag {}", tag),
+            MaruFileError::UnknownSymbolKind { kind, .. } => write!(f, "unknown symbol kind {}", kind),
+            MaruFileError::UnknownVersionOp { op, .. } => write!(f, "unknown version requirement operator {}", op),
+            MaruFileError::InvalidUtf8 { .. } => write!(f, "string is not valid UTF-8"),
+            MaruFileError::UnterminatedString { .. } => write!(f, "string is missing its NUL terminator"),
+            MaruFileError::TypeTooDeep { .. } => {
+                write!(f, "type tag nests more than {} array types", crate::MAX_TYPE_DEPTH)
+            }
+            MaruFileError::DuplicateSection { section, .. } => write!(f, "the {} section appears twice", section),
+            MaruFileError::ChecksumMismatch { expected, found, .. } => {
+                write!(f, "checksum is {:#010x} but the file hashes to {:#010x}", expected, found)
+            }
+            MaruFileError::ContentHashMismatch { expected, found, .. } => {
+                write!(f, "content hash is {:#018x} but the contents hash to {:#018x}", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MaruFileError {}
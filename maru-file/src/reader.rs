@@ -0,0 +1,172 @@
+use crate::{MaruFileError, Section};
+
+/// A cursor over the bytes of a Maru file that remembers where it is, so
+/// errors can name the section, entry and offset they occurred at.
+///
+/// Every read is bounds checked, so malformed input produces an error rather
+/// than a panic.
+pub(crate) struct Reader<'a> {
+    binary: &'a [u8],
+    offset: usize,
+    section: Section,
+    index: Option<u32>,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(binary: &'a [u8], section: Section) -> Self {
+        Reader { binary, offset: 0, section, index: None }
+    }
+
+    /// A reader over the rest of `binary` from `offset`.
+    pub fn at(binary: &'a [u8], offset: usize, section: Section) -> Self {
+        Reader { binary, offset: offset.min(binary.len()), section, index: None }
+    }
+
+    /// A reader over the `length` bytes at `offset`, which keeps reporting
+    /// offsets from the start of `binary`.
+    pub fn within(binary: &'a [u8], offset: usize, length: usize, section: Section) -> Option<Self> {
+        let end = offset.checked_add(length)?;
+        Some(Reader { binary: binary.get(..end)?, offset, section, index: None })
+    }
+
+    /// Parses a single value at the start of `binary` and returns it with the remaining bytes.
+    pub fn parse<T>(
+        binary: &'a [u8],
+        section: Section,
+        read: fn(&mut Reader<'a>) -> Result<T, MaruFileError>,
+    ) -> Result<(T, &'a [u8]), MaruFileError> {
+        let mut reader = Reader::new(binary, section);
+        let value = read(&mut reader)?;
+        Ok((value, reader.rest()))
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The bytes that haven't been read yet.
+    pub fn rest(&self) -> &'a [u8] {
+        &self.binary[self.offset..]
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], MaruFileError> {
+        let rest = self.rest();
+        let Some(bytes) = rest.get(..len) else {
+            return Err(MaruFileError::Truncated {
+                section: self.section,
+                index: self.index,
+                offset: self.offset,
+                expected: len,
+                actual: rest.len(),
+            });
+        };
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, MaruFileError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, MaruFileError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, MaruFileError> {
+        let bytes = self.bytes(8)?;
+        Ok(u64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, MaruFileError> {
+        Ok(self.u32()? as i32)
+    }
+
+    /// Reads a NUL terminated UTF-8 string.
+    pub fn string(&mut self) -> Result<String, MaruFileError> {
+        self.str().map(String::from)
+    }
+
+    /// Reads a NUL terminated UTF-8 string without copying it.
+    pub fn str(&mut self) -> Result<&'a str, MaruFileError> {
+        let start = self.offset;
+        let Some(len) = self.rest().iter().position(|byte| *byte == 0) else {
+            return Err(MaruFileError::UnterminatedString { section: self.section, index: self.index, offset: start });
+        };
+        let bytes = self.bytes(len + 1)?;
+        std::str::from_utf8(&bytes[..len])
+            .map_err(|_| MaruFileError::InvalidUtf8 { section: self.section, index: self.index, offset: start })
+    }
+
+    /// Reads a `u32` count of values that take at least a byte each.
+    ///
+    /// A count larger than the remaining bytes is reported before any value is read.
+    fn count(&mut self) -> Result<u32, MaruFileError> {
+        let len = self.u32()?;
+        let rest = self.rest().len();
+        if len as usize > rest {
+            return Err(MaruFileError::Truncated {
+                section: self.section,
+                index: self.index,
+                offset: self.offset,
+                expected: len as usize,
+                actual: rest,
+            });
+        }
+        Ok(len)
+    }
+
+    /// Reads a `u32` count followed by that many values.
+    pub fn list<T>(&mut self, mut read: impl FnMut(&mut Self) -> Result<T, MaruFileError>) -> Result<Vec<T>, MaruFileError> {
+        let len = self.count()?;
+        let mut values = Vec::new();
+        for _ in 0..len {
+            values.push(read(self)?);
+        }
+        Ok(values)
+    }
+
+    /// Reads a `u32` count and checks that many values with `skip`, without
+    /// keeping them.
+    pub fn skip_list(&mut self, mut skip: impl FnMut(&mut Self) -> Result<(), MaruFileError>) -> Result<(), MaruFileError> {
+        let len = self.count()?;
+        for _ in 0..len {
+            skip(self)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the entries of `section`, numbering them for errors.
+    pub fn section<T>(
+        &mut self,
+        section: Section,
+        read: fn(&mut Self) -> Result<T, MaruFileError>,
+    ) -> Result<Vec<T>, MaruFileError> {
+        self.section = section;
+        self.index = None;
+        let len = self.count()?;
+        let mut entries = Vec::new();
+        for index in 0..len {
+            self.index = Some(index);
+            entries.push(read(self)?);
+        }
+        self.index = None;
+        Ok(entries)
+    }
+
+    pub fn unknown_type_tag(&self, offset: usize, tag: u8) -> MaruFileError {
+        MaruFileError::UnknownTypeTag { section: self.section, index: self.index, offset, tag }
+    }
+
+    pub fn unknown_symbol_kind(&self, offset: usize, kind: u8) -> MaruFileError {
+        MaruFileError::UnknownSymbolKind { section: self.section, index: self.index, offset, kind }
+    }
+
+    pub fn unknown_version_op(&self, offset: usize, op: u8) -> MaruFileError {
+        MaruFileError::UnknownVersionOp { section: self.section, index: self.index, offset, op }
+    }
+
+    pub fn type_too_deep(&self, offset: usize) -> MaruFileError {
+        MaruFileError::TypeTooDeep { section: self.section, index: self.index, offset }
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+
+use crate::{MaruFileError, StringIndex, digest, reader::Reader};
+
+/// A part of a Maru file, in the order the parts are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Section {
+    /// The magic number, the version, the module name and the section directory.
+    Header,
+    Objects,
+    Functions,
+    Globals,
+    StringTable,
+    BytecodeTable,
+    LocationsMap,
+    Imports,
+    Exports,
+    /// The module's version and its dependencies.
+    Module,
+}
+
+impl Section {
+    /// The sections after the header, in the order they are written.
+    pub const ALL: [Section; 9] = [
+        Section::Objects,
+        Section::Functions,
+        Section::Globals,
+        Section::StringTable,
+        Section::BytecodeTable,
+        Section::LocationsMap,
+        Section::Imports,
+        Section::Exports,
+        Section::Module,
+    ];
+
+    /// The sections of files without a section directory, which have no
+    /// imports, exports, module version or dependencies.
+    pub const LEGACY: [Section; 6] = [
+        Section::Objects,
+        Section::Functions,
+        Section::Globals,
+        Section::StringTable,
+        Section::BytecodeTable,
+        Section::LocationsMap,
+    ];
+
+    /// The kind stored for the section in the section directory.
+    pub fn kind(self) -> u32 {
+        match self {
+            Section::Header => 0,
+            Section::Objects => 1,
+            Section::Functions => 2,
+            Section::Globals => 3,
+            Section::StringTable => 4,
+            Section::BytecodeTable => 5,
+            Section::LocationsMap => 6,
+            Section::Imports => 7,
+            Section::Exports => 8,
+            Section::Module => 9,
+        }
+    }
+
+    /// The section with the given directory kind, if it is one this version knows.
+    pub fn from_kind(kind: u32) -> Option<Section> {
+        Section::ALL.into_iter().find(|section| section.kind() == kind)
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Section::Header => "header",
+            Section::Objects => "objects",
+            Section::Functions => "functions",
+            Section::Globals => "globals",
+            Section::StringTable => "string table",
+            Section::BytecodeTable => "bytecode table",
+            Section::LocationsMap => "locations map",
+            Section::Imports => "imports",
+            Section::Exports => "exports",
+            Section::Module => "module",
+        })
+    }
+}
+
+/// An entry of the section directory.
+///
+/// `offset` is counted from the start of the file. Entries whose `kind` is
+/// unknown are skipped by readers, so newer files can add sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionEntry {
+    pub kind: u32,
+    pub offset: u32,
+    pub length: u32,
+}
+
+impl SectionEntry {
+    /// The number of bytes an entry takes in the directory.
+    pub const SIZE: usize = 12;
+
+    pub fn into_binary(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.kind.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.length.to_le_bytes());
+        bytes
+    }
+
+    pub(crate) fn read(reader: &mut Reader<'_>) -> Result<Self, MaruFileError> {
+        Ok(SectionEntry { kind: reader.u32()?, offset: reader.u32()?, length: reader.u32()? })
+    }
+}
+
+/// Calls `visit` with a reader at the start of each known section.
+///
+/// `reader` is just past the module name. Without a section directory the
+/// sections follow it back to back, so `visit` must read each one in full.
+pub(crate) fn visit<'a>(
+    binary: &'a [u8],
+    reader: &mut Reader<'a>,
+    sectioned: bool,
+    mut visit: impl FnMut(Section, &mut Reader<'a>) -> Result<(), MaruFileError>,
+) -> Result<(), MaruFileError> {
+    if !sectioned {
+        for section in Section::LEGACY {
+            visit(section, reader)?;
+        }
+        return Ok(());
+    }
+
+    let entries_offset = reader.offset() + 4;
+    let directory = reader.section(Section::Header, SectionEntry::read)?;
+    let mut seen = Vec::new();
+    for (index, entry) in directory.into_iter().enumerate() {
+        let Some(section) = Section::from_kind(entry.kind) else {
+            continue;
+        };
+        let mut section_reader = section_reader(binary, index, entry, section)?;
+        if seen.contains(&section) {
+            return Err(MaruFileError::DuplicateSection {
+                index: index as u32,
+                offset: entries_offset + index * SectionEntry::SIZE,
+                section,
+            });
+        }
+        seen.push(section);
+        visit(section, &mut section_reader)?;
+    }
+    Ok(())
+}
+
+/// Hashes `module_name` and the bytes of every section in the directory
+/// `reader` is at, in directory order, including sections of unknown kinds.
+pub(crate) fn content_hash(binary: &[u8], reader: &Reader<'_>, module_name: StringIndex) -> Result<u64, MaruFileError> {
+    let mut reader = Reader::at(binary, reader.offset(), Section::Header);
+    let directory = reader.section(Section::Header, SectionEntry::read)?;
+    let mut sections = Vec::with_capacity(directory.len());
+    for (index, entry) in directory.into_iter().enumerate() {
+        let section = Section::from_kind(entry.kind).unwrap_or(Section::Header);
+        sections.push(section_reader(binary, index, entry, section)?.rest());
+    }
+    Ok(digest::content_hash(module_name, sections))
+}
+
+/// A reader over the section at directory entry `index`.
+fn section_reader<'a>(binary: &'a [u8], index: usize, entry: SectionEntry, section: Section) -> Result<Reader<'a>, MaruFileError> {
+    let (offset, length) = (entry.offset as usize, entry.length as usize);
+    Reader::within(binary, offset, length, section).ok_or(MaruFileError::Truncated {
+        section: Section::Header,
+        index: Some(index as u32),
+        offset,
+        expected: length,
+        actual: binary.len().saturating_sub(offset),
+    })
+}
@@ -0,0 +1,144 @@
+use std::io::{self, Write};
+
+use crate::{MaruFileError, MaruTypeTag, Section, StringIndex, reader::Reader, to_binary, write_list};
+
+/// The kind of item a symbol names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MaruSymbolKind {
+    Function,
+    Object,
+    Global,
+}
+
+impl MaruSymbolKind {
+    fn tag(self) -> u8 {
+        match self {
+            MaruSymbolKind::Function => 0,
+            MaruSymbolKind::Object => 1,
+            MaruSymbolKind::Global => 2,
+        }
+    }
+
+    fn read(reader: &mut Reader<'_>) -> Result<Self, MaruFileError> {
+        let offset = reader.offset();
+        match reader.u8()? {
+            0 => Ok(MaruSymbolKind::Function),
+            1 => Ok(MaruSymbolKind::Object),
+            2 => Ok(MaruSymbolKind::Global),
+            kind => Err(reader.unknown_symbol_kind(offset, kind)),
+        }
+    }
+}
+
+/// What an import expects the symbol it names to be.
+pub enum MaruSignature {
+    Function { parameters: Vec<MaruTypeTag>, return_type: MaruTypeTag },
+    /// An object type, which is referred to by the symbol name.
+    Object,
+    Global(MaruTypeTag),
+}
+
+impl MaruSignature {
+    pub fn kind(&self) -> MaruSymbolKind {
+        match self {
+            MaruSignature::Function { .. } => MaruSymbolKind::Function,
+            MaruSignature::Object => MaruSymbolKind::Object,
+            MaruSignature::Global(_) => MaruSymbolKind::Global,
+        }
+    }
+}
+
+/// A symbol this module expects another module to export.
+///
+/// Function ids from `functions.len()` on refer to the function imports, in
+/// the order they appear among the imports.
+pub struct MaruImport {
+    /// The name of the module exporting the symbol.
+    pub module: StringIndex,
+    /// The name the symbol is exported under.
+    pub name: StringIndex,
+    pub signature: MaruSignature,
+}
+
+impl MaruImport {
+    pub fn into_binary(self) -> Vec<u8> {
+        to_binary(|bytes| self.write_to(bytes))
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.module.to_le_bytes())?;
+        writer.write_all(&self.name.to_le_bytes())?;
+        writer.write_all(&[self.signature.kind().tag()])?;
+        match &self.signature {
+            MaruSignature::Function { parameters, return_type } => {
+                write_list(writer, parameters, MaruTypeTag::write_to)?;
+                return_type.write_to(writer)
+            }
+            MaruSignature::Object => Ok(()),
+            MaruSignature::Global(type_tag) => type_tag.write_to(writer),
+        }
+    }
+
+    pub fn from_binary(binary: &[u8]) -> Result<(Self, &[u8]), MaruFileError> {
+        Reader::parse(binary, Section::Imports, Self::read)
+    }
+
+    pub(crate) fn read(reader: &mut Reader<'_>) -> Result<Self, MaruFileError> {
+        let module = reader.u32()?;
+        let name = reader.u32()?;
+        let signature = match MaruSymbolKind::read(reader)? {
+            MaruSymbolKind::Function => {
+                let parameters = reader.list(MaruTypeTag::read)?;
+                MaruSignature::Function { parameters, return_type: MaruTypeTag::read(reader)? }
+            }
+            MaruSymbolKind::Object => MaruSignature::Object,
+            MaruSymbolKind::Global => MaruSignature::Global(MaruTypeTag::read(reader)?),
+        };
+        Ok(MaruImport { module, name, signature })
+    }
+
+    pub(crate) fn skip(reader: &mut Reader<'_>) -> Result<(), MaruFileError> {
+        reader.u32()?;
+        reader.u32()?;
+        match MaruSymbolKind::read(reader)? {
+            MaruSymbolKind::Function => {
+                reader.skip_list(MaruTypeTag::skip)?;
+                MaruTypeTag::skip(reader)
+            }
+            MaruSymbolKind::Object => Ok(()),
+            MaruSymbolKind::Global => MaruTypeTag::skip(reader),
+        }
+    }
+}
+
+/// A function, object or global other modules may import.
+pub struct MaruExport {
+    /// The name other modules import the symbol under.
+    pub name: StringIndex,
+    pub kind: MaruSymbolKind,
+    /// The index of the item in this module's functions, objects or globals.
+    pub index: u32,
+}
+
+impl MaruExport {
+    pub fn into_binary(self) -> Vec<u8> {
+        to_binary(|bytes| self.write_to(bytes))
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.name.to_le_bytes())?;
+        writer.write_all(&[self.kind.tag()])?;
+        writer.write_all(&self.index.to_le_bytes())
+    }
+
+    pub fn from_binary(binary: &[u8]) -> Result<(Self, &[u8]), MaruFileError> {
+        Reader::parse(binary, Section::Exports, Self::read)
+    }
+
+    pub(crate) fn read(reader: &mut Reader<'_>) -> Result<Self, MaruFileError> {
+        let name = reader.u32()?;
+        let kind = MaruSymbolKind::read(reader)?;
+        let index = reader.u32()?;
+        Ok(MaruExport { name, kind, index })
+    }
+}
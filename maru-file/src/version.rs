@@ -0,0 +1,159 @@
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use crate::{MaruFileError, Section, StringIndex, reader::Reader, to_binary};
+
+/// The semantic version of a module.
+///
+/// This is the version of the module's contents, not of the file format,
+/// which is kept in `MaruFile::major_version` and the fields following it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaruVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl MaruVersion {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        MaruVersion { major, minor, patch }
+    }
+
+    pub fn into_binary(self) -> Vec<u8> {
+        to_binary(|bytes| self.write_to(bytes))
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.major.to_le_bytes())?;
+        writer.write_all(&self.minor.to_le_bytes())?;
+        writer.write_all(&self.patch.to_le_bytes())
+    }
+
+    pub fn from_binary(binary: &[u8]) -> Result<(Self, &[u8]), MaruFileError> {
+        Reader::parse(binary, Section::Module, Self::read)
+    }
+
+    pub(crate) fn read(reader: &mut Reader<'_>) -> Result<Self, MaruFileError> {
+        Ok(MaruVersion { major: reader.u32()?, minor: reader.u32()?, patch: reader.u32()? })
+    }
+}
+
+impl fmt::Display for MaruVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The versions of a dependency a module can be linked against.
+///
+/// These follow the meaning the operators have in Cargo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MaruVersionReq {
+    /// `*`: any version.
+    Any,
+    /// `=1.2.3`: only this version.
+    Exact(MaruVersion),
+    /// `>=1.2.3`: this version or any later one.
+    AtLeast(MaruVersion),
+    /// `^1.2.3`: a later version that doesn't change the leftmost non-zero
+    /// component, so `>=1.2.3, <2.0.0`, or `>=0.2.3, <0.3.0`.
+    Caret(MaruVersion),
+    /// `~1.2.3`: a later version with the same major and minor version.
+    Tilde(MaruVersion),
+}
+
+impl MaruVersionReq {
+    pub fn matches(&self, version: &MaruVersion) -> bool {
+        match *self {
+            MaruVersionReq::Any => true,
+            MaruVersionReq::Exact(required) => *version == required,
+            MaruVersionReq::AtLeast(required) => *version >= required,
+            MaruVersionReq::Caret(required) => {
+                let compatible = match (required.major, required.minor) {
+                    (0, 0) => version.major == 0 && version.minor == 0 && version.patch == required.patch,
+                    (0, minor) => version.major == 0 && version.minor == minor,
+                    (major, _) => version.major == major,
+                };
+                compatible && *version >= required
+            }
+            MaruVersionReq::Tilde(required) => {
+                version.major == required.major && version.minor == required.minor && *version >= required
+            }
+        }
+    }
+
+    pub fn into_binary(self) -> Vec<u8> {
+        to_binary(|bytes| self.write_to(bytes))
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let (op, version) = match self {
+            MaruVersionReq::Any => return writer.write_all(&[0]),
+            MaruVersionReq::Exact(version) => (1, version),
+            MaruVersionReq::AtLeast(version) => (2, version),
+            MaruVersionReq::Caret(version) => (3, version),
+            MaruVersionReq::Tilde(version) => (4, version),
+        };
+        writer.write_all(&[op])?;
+        version.write_to(writer)
+    }
+
+    pub fn from_binary(binary: &[u8]) -> Result<(Self, &[u8]), MaruFileError> {
+        Reader::parse(binary, Section::Module, Self::read)
+    }
+
+    pub(crate) fn read(reader: &mut Reader<'_>) -> Result<Self, MaruFileError> {
+        let offset = reader.offset();
+        let requirement = match reader.u8()? {
+            0 => MaruVersionReq::Any,
+            1 => MaruVersionReq::Exact(MaruVersion::read(reader)?),
+            2 => MaruVersionReq::AtLeast(MaruVersion::read(reader)?),
+            3 => MaruVersionReq::Caret(MaruVersion::read(reader)?),
+            4 => MaruVersionReq::Tilde(MaruVersion::read(reader)?),
+            op => return Err(reader.unknown_version_op(offset, op)),
+        };
+        Ok(requirement)
+    }
+}
+
+impl fmt::Display for MaruVersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaruVersionReq::Any => write!(f, "*"),
+            MaruVersionReq::Exact(version) => write!(f, "={}", version),
+            MaruVersionReq::AtLeast(version) => write!(f, ">={}", version),
+            MaruVersionReq::Caret(version) => write!(f, "^{}", version),
+            MaruVersionReq::Tilde(version) => write!(f, "~{}", version),
+        }
+    }
+}
+
+/// A module this module must be linked against.
+pub struct MaruDependency {
+    /// The name of the module.
+    pub module: StringIndex,
+    pub requirement: MaruVersionReq,
+}
+
+impl MaruDependency {
+    pub fn into_binary(self) -> Vec<u8> {
+        to_binary(|bytes| self.write_to(bytes))
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.module.to_le_bytes())?;
+        self.requirement.write_to(writer)
+    }
+
+    pub fn from_binary(binary: &[u8]) -> Result<(Self, &[u8]), MaruFileError> {
+        Reader::parse(binary, Section::Module, Self::read)
+    }
+
+    pub(crate) fn read(reader: &mut Reader<'_>) -> Result<Self, MaruFileError> {
+        let module = reader.u32()?;
+        let requirement = MaruVersionReq::read(reader)?;
+        Ok(MaruDependency { module, requirement })
+    }
+}
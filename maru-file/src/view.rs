@@ -0,0 +1,245 @@
+use crate::{
+    BytecodeIndex, MaruDependency, MaruExport, MaruFile, MaruFileError, MaruFunction, MaruGlobal, MaruImport, MaruLocation, MaruObject,
+    MaruVersion, Section, StringIndex, reader::Reader, section,
+};
+
+/// A Maru file read in place.
+///
+/// Unlike `MaruFile::from_binary`, strings and bytecode are borrowed from the
+/// input, which may be a memory-mapped file, and objects, functions, globals
+/// and locations are only decoded when they are asked for. The whole file is
+/// still checked when the view is created, so the accessors can't fail.
+pub struct MaruFileView<'a> {
+    binary: &'a [u8],
+    pub magic: u8,
+    pub major_version: u8,
+    pub minor_version: u8,
+    pub patch_version: u8,
+    pub module_name: StringIndex,
+    /// The `MaruFile::content_hash` stored in the header, if the version has one.
+    pub content_hash: Option<u64>,
+    pub module_version: MaruVersion,
+    /// The offset of each entry of the sections decoded on access.
+    objects: Vec<usize>,
+    functions: Vec<usize>,
+    globals: Vec<usize>,
+    locations: Vec<usize>,
+    imports: Vec<usize>,
+    exports: Vec<usize>,
+    dependencies: Vec<usize>,
+    strings: Vec<&'a str>,
+    bytecode: Vec<&'a [u8]>,
+}
+
+impl<'a> MaruFileView<'a> {
+    pub fn new(binary: &'a [u8]) -> Result<Self, MaruFileError> {
+        let mut reader = Reader::new(binary, Section::Header);
+        let (header, content_hash) = MaruFile::read_header(binary, &mut reader)?;
+        let mut view = MaruFileView {
+            binary,
+            magic: header.magic,
+            major_version: header.major_version,
+            minor_version: header.minor_version,
+            patch_version: header.patch_version,
+            module_name: header.module_name,
+            content_hash,
+            module_version: MaruVersion::default(),
+            objects: Vec::new(),
+            functions: Vec::new(),
+            globals: Vec::new(),
+            locations: Vec::new(),
+            imports: Vec::new(),
+            exports: Vec::new(),
+            dependencies: Vec::new(),
+            strings: Vec::new(),
+            bytecode: Vec::new(),
+        };
+        section::visit(binary, &mut reader, header.sectioned(), |section, reader| view.index_section(section, reader))?;
+        Ok(view)
+    }
+
+    /// Checks the entries of `section` and records where they are, without
+    /// decoding them.
+    fn index_section(&mut self, section: Section, reader: &mut Reader<'a>) -> Result<(), MaruFileError> {
+        match section {
+            Section::Header => {}
+            Section::Objects => self.objects = reader.section(section, |reader| entry(reader, MaruObject::skip))?,
+            Section::Functions => self.functions = reader.section(section, |reader| entry(reader, MaruFunction::skip))?,
+            Section::Globals => self.globals = reader.section(section, |reader| entry(reader, MaruGlobal::skip))?,
+            Section::StringTable => self.strings = reader.section(section, Reader::str)?,
+            Section::BytecodeTable => {
+                self.bytecode = reader.section(section, |reader| {
+                    let len = reader.u32()? as usize;
+                    reader.bytes(len)
+                })?
+            }
+            Section::LocationsMap => self.locations = reader.section(section, |reader| entry(reader, MaruLocation::skip))?,
+            Section::Imports => self.imports = reader.section(section, |reader| entry(reader, MaruImport::skip))?,
+            Section::Exports => self.exports = reader.section(section, |reader| entry(reader, MaruExport::read))?,
+            Section::Module => {
+                self.module_version = MaruVersion::read(reader)?;
+                self.dependencies = reader.section(section, |reader| entry(reader, MaruDependency::read))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode<T>(&self, offsets: &[usize], index: usize, section: Section, read: fn(&mut Reader<'a>) -> Result<T, MaruFileError>) -> Option<T> {
+        let offset = *offsets.get(index)?;
+        read(&mut Reader::at(self.binary, offset, section)).ok()
+    }
+
+    /// Whether the functions in this file are encoded with LEB128 operands.
+    pub fn compact_bytecode(&self) -> bool {
+        self.header().compact_bytecode()
+    }
+
+    fn header(&self) -> MaruFile {
+        MaruFile {
+            magic: self.magic,
+            major_version: self.major_version,
+            minor_version: self.minor_version,
+            patch_version: self.patch_version,
+            module_name: self.module_name,
+            ..MaruFile::new()
+        }
+    }
+
+    pub fn object_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn function_count(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn global_count(&self) -> usize {
+        self.globals.len()
+    }
+
+    pub fn string_count(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn bytecode_count(&self) -> usize {
+        self.bytecode.len()
+    }
+
+    pub fn import_count(&self) -> usize {
+        self.imports.len()
+    }
+
+    pub fn export_count(&self) -> usize {
+        self.exports.len()
+    }
+
+    pub fn dependency_count(&self) -> usize {
+        self.dependencies.len()
+    }
+
+    pub fn object(&self, index: usize) -> Option<MaruObject> {
+        self.decode(&self.objects, index, Section::Objects, MaruObject::read)
+    }
+
+    pub fn function(&self, index: usize) -> Option<MaruFunction> {
+        self.decode(&self.functions, index, Section::Functions, MaruFunction::read)
+    }
+
+    pub fn global(&self, index: usize) -> Option<MaruGlobal> {
+        self.decode(&self.globals, index, Section::Globals, MaruGlobal::read)
+    }
+
+    pub fn import(&self, index: usize) -> Option<MaruImport> {
+        self.decode(&self.imports, index, Section::Imports, MaruImport::read)
+    }
+
+    pub fn export(&self, index: usize) -> Option<MaruExport> {
+        self.decode(&self.exports, index, Section::Exports, MaruExport::read)
+    }
+
+    pub fn dependency(&self, index: usize) -> Option<MaruDependency> {
+        self.decode(&self.dependencies, index, Section::Module, MaruDependency::read)
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = MaruObject> + '_ {
+        (0..self.objects.len()).filter_map(|index| self.object(index))
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = MaruFunction> + '_ {
+        (0..self.functions.len()).filter_map(|index| self.function(index))
+    }
+
+    pub fn globals(&self) -> impl Iterator<Item = MaruGlobal> + '_ {
+        (0..self.globals.len()).filter_map(|index| self.global(index))
+    }
+
+    pub fn imports(&self) -> impl Iterator<Item = MaruImport> + '_ {
+        (0..self.imports.len()).filter_map(|index| self.import(index))
+    }
+
+    pub fn exports(&self) -> impl Iterator<Item = MaruExport> + '_ {
+        (0..self.exports.len()).filter_map(|index| self.export(index))
+    }
+
+    pub fn dependencies(&self) -> impl Iterator<Item = MaruDependency> + '_ {
+        (0..self.dependencies.len()).filter_map(|index| self.dependency(index))
+    }
+
+    pub fn strings(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.strings.iter().copied()
+    }
+
+    pub fn get_string(&self, index: StringIndex) -> Option<&'a str> {
+        self.strings.get(index as usize).copied()
+    }
+
+    pub fn get_bytecode(&self, index: BytecodeIndex) -> Option<&'a [u8]> {
+        self.bytecode.get(usize::try_from(index).ok()?).copied()
+    }
+
+    pub fn get_location(&self, index: BytecodeIndex) -> Option<MaruLocation> {
+        self.decode(&self.locations, usize::try_from(index).ok()?, Section::LocationsMap, MaruLocation::read)
+    }
+
+    pub fn get_object(&self, name: StringIndex) -> Option<MaruObject> {
+        self.find(&self.objects, name, Section::Objects, MaruObject::read)
+    }
+
+    pub fn get_function(&self, name: StringIndex) -> Option<MaruFunction> {
+        self.find(&self.functions, name, Section::Functions, MaruFunction::read)
+    }
+
+    pub fn get_global(&self, name: StringIndex) -> Option<MaruGlobal> {
+        self.find(&self.globals, name, Section::Globals, MaruGlobal::read)
+    }
+
+    /// Decodes the first entry whose name, which each of these entries
+    /// starts with, is `name`.
+    fn find<T>(&self, offsets: &[usize], name: StringIndex, section: Section, read: fn(&mut Reader<'a>) -> Result<T, MaruFileError>) -> Option<T> {
+        let index = offsets.iter().position(|offset| Reader::at(self.binary, *offset, section).u32().ok() == Some(name))?;
+        self.decode(offsets, index, section, read)
+    }
+
+    /// Copies the whole file out of the input.
+    pub fn to_file(&self) -> MaruFile {
+        let mut file = self.header();
+        file.objects = self.objects().collect();
+        file.functions = self.functions().collect();
+        file.globals = self.globals().collect();
+        file.string_table.entries = self.strings().map(String::from).collect();
+        file.bytecode_table.entries = self.bytecode.iter().map(|code| Box::from(*code)).collect();
+        file.locations_map.entries = (0..self.locations.len()).filter_map(|index| self.get_location(index as i32)).collect();
+        file.imports = self.imports().collect();
+        file.exports = self.exports().collect();
+        file.module_version = self.module_version;
+        file.dependencies = self.dependencies().collect();
+        file
+    }
+}
+
+/// Checks the entry `reader` is at and returns its offset.
+fn entry<'a, T>(reader: &mut Reader<'a>, read: fn(&mut Reader<'a>) -> Result<T, MaruFileError>) -> Result<usize, MaruFileError> {
+    let offset = reader.offset();
+    read(reader)?;
+    Ok(offset)
+}
@@ -0,0 +1,166 @@
+use maru_file::*;
+
+/// A file that uses every kind of entry, in each layout.
+fn samples() -> [Vec<u8>; 3] {
+    [sample(0), sample(SECTIONED_MINOR_VERSION), sample(CHECKSUM_MINOR_VERSION)]
+}
+
+fn sample(minor_version: u8) -> Vec<u8> {
+    let mut file = MaruFile::new();
+    file.minor_version = minor_version;
+    file.module_name = file.add_string("sample".into());
+    let name = file.add_string("Option".into());
+    let value = file.add_string("value".into());
+    file.add_object(MaruObject {
+        name,
+        type_name: name,
+        variants: vec![
+            MaruVariant { name, type_name: name, members: vec![] },
+            MaruVariant {
+                name,
+                type_name: name,
+                members: vec![(value, MaruTypeTag::Array(Box::new(MaruTypeTag::Object(name))))],
+            },
+        ],
+        internal: 0,
+    });
+    let code = file.add_bytecode(vec![1, 2, 3, 4].into_boxed_slice());
+    file.add_location(MaruLocation::new(value, vec![(0, 4), (4, 8)]));
+    file.add_function(MaruFunction {
+        name: value,
+        type_name: value,
+        parameters: vec![MaruTypeTag::String, MaruTypeTag::F64],
+        return_type: MaruTypeTag::Object(name),
+        bytecode_index: code,
+        variables: 2,
+    });
+    file.add_global(MaruGlobal { name, type_tag: MaruTypeTag::I32, init_index: -1 });
+    let signature = MaruSignature::Function { parameters: vec![MaruTypeTag::Object(name)], return_type: MaruTypeTag::Unit };
+    file.add_import(MaruImport { module: name, name: value, signature });
+    file.add_import(MaruImport { module: name, name, signature: MaruSignature::Global(MaruTypeTag::String) });
+    file.add_export(MaruExport { name: value, kind: MaruSymbolKind::Function, index: 0 });
+    file.module_version = MaruVersion::new(1, 0, 2);
+    file.add_dependency(MaruDependency { module: name, requirement: MaruVersionReq::Tilde(MaruVersion::new(0, 3, 1)) });
+    file.add_dependency(MaruDependency { module: value, requirement: MaruVersionReq::Any });
+    file.into_binary()
+}
+
+/// The CRC-32 (IEEE) of `bytes`, to fix up the checksum of an edited file.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// A small deterministic generator, so failures reproduce.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[test]
+fn test_every_truncation_is_an_error() {
+    for binary in samples() {
+        assert!(MaruFile::from_binary(&binary).is_ok());
+        for len in 0..binary.len() {
+            assert!(MaruFile::from_binary(&binary[..len]).is_err(), "prefix of {} bytes parsed", len);
+        }
+    }
+}
+
+#[test]
+fn test_mutated_files_never_panic() {
+    let mut random = XorShift(0x9E37_79B9_7F4A_7C15);
+    for binary in samples() {
+        for offset in 0..binary.len() {
+            for byte in [0x00, 0x01, 0x0c, 0x0d, 0x7f, 0x80, 0xff, binary[offset] ^ 0x01] {
+                let mut mutated = binary.clone();
+                mutated[offset] = byte;
+                let _ = MaruFile::from_binary(&mutated);
+            }
+        }
+        for _ in 0..2000 {
+            let mut mutated = binary.clone();
+            for _ in 0..random.next() % 8 + 1 {
+                let offset = random.next() as usize % mutated.len();
+                mutated[offset] = random.next() as u8;
+            }
+            mutated.truncate(random.next() as usize % (binary.len() + 1));
+            let _ = MaruFile::from_binary(&mutated);
+        }
+    }
+    for _ in 0..2000 {
+        let len = random.next() as usize % 64;
+        let mut noise: Vec<u8> = (0..len).map(|_| random.next() as u8).collect();
+        if let Some(magic) = noise.first_mut() {
+            *magic = 0x4D;
+        }
+        let _ = MaruFile::from_binary(&noise);
+    }
+}
+
+#[test]
+fn test_malformed_corpus() {
+    let error = |binary: &[u8]| MaruFile::from_binary(binary).err().expect("malformed file parsed");
+    let header = |rest: &[u8]| [&[0x4D, 0, 0, 0, 0, 0, 0, 0][..], rest].concat();
+    let truncated = |section, index, offset, expected, actual| MaruFileError::Truncated {
+        section,
+        index,
+        offset,
+        expected,
+        actual,
+    };
+
+    assert_eq!(error(&[]), truncated(Section::Header, None, 0, 1, 0));
+    assert_eq!(error(&[0x4D, 0, 0]), truncated(Section::Header, None, 3, 1, 0));
+    // the object count is missing
+    assert_eq!(error(&header(&[])), truncated(Section::Objects, None, 8, 4, 0));
+    // more objects than there are bytes left
+    assert_eq!(error(&header(&[0xff, 0xff, 0xff, 0xff, 0])), truncated(Section::Objects, None, 12, 0xffff_ffff, 1));
+    // a variant that ends after its type name, before the member count
+    let object = [1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0];
+    assert_eq!(error(&header(&[&[1, 0, 0, 0][..], &object].concat())), truncated(Section::Objects, Some(0), 32, 4, 0));
+    // a member name without a type
+    let object = [&object[..], &[1, 0, 0, 0, 7, 0, 0, 0]].concat();
+    assert_eq!(error(&header(&[&[1, 0, 0, 0][..], &object].concat())), truncated(Section::Objects, Some(0), 40, 1, 0));
+    // a global whose type nests arrays without end
+    let global = [&[0, 0, 0, 0][..], &[13; 10_000]].concat();
+    let globals = [&[0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0][..], &global].concat();
+    assert_eq!(error(&header(&globals)), MaruFileError::TypeTooDeep {
+        section: Section::Globals,
+        index: Some(0),
+        offset: 24 + MAX_TYPE_DEPTH + 1,
+    });
+    // a string without its NUL terminator
+    let strings = [&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0][..], b"ok\0no"].concat();
+    assert_eq!(error(&header(&strings)), MaruFileError::UnterminatedString {
+        section: Section::StringTable,
+        index: Some(1),
+        offset: 27,
+    });
+    let strings = [&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0][..], b"\xff\0"].concat();
+    assert_eq!(error(&header(&strings)), MaruFileError::InvalidUtf8 {
+        section: Section::StringTable,
+        index: Some(0),
+        offset: 24,
+    });
+
+    // a content hash that doesn't match the contents, behind a valid checksum
+    let mut binary = sample(CHECKSUM_MINOR_VERSION);
+    let expected = u64::from_le_bytes(binary[12..20].try_into().unwrap());
+    binary[12] ^= 0x01;
+    let checksum = crc32(&binary[12..]);
+    binary[8..12].copy_from_slice(&checksum.to_le_bytes());
+    assert_eq!(error(&binary), MaruFileError::ContentHashMismatch { offset: 12, expected: expected ^ 0x01, found: expected });
+}
@@ -16,6 +16,8 @@ fn test_maru_type_tag_roundtrip() {
         MaruTypeTag::F32,
         MaruTypeTag::F64,
         MaruTypeTag::Object(42),
+        MaruTypeTag::Array(Box::new(MaruTypeTag::Array(Box::new(MaruTypeTag::Object(7))))),
+        MaruTypeTag::String,
     ];
     for tag in tags {
         let b = tag.into_binary();
@@ -116,6 +118,7 @@ fn test_maru_file_roundtrip_stability() {
 #[test]
 fn test_invalid_maru_type_tag_binary() {
     assert!(MaruTypeTag::from_binary(&[]).is_err());
+    assert!(MaruTypeTag::from_binary(&[13]).is_err());
 }
 
 #[test]
@@ -146,7 +149,7 @@ fn test_bytecode_table_empty_entries() {
 fn test_invalid_magic_in_maru_file() {
     // bad magic (first byte != 0x4D)
     let bad = vec![0u8, 0, 0, 0, 0, 0, 0, 0];
-    assert!(MaruFile::from_binary(&bad).is_err());
+    assert_eq!(MaruFile::from_binary(&bad).err(), Some(MaruFileError::InvalidMagic { found: 0 }));
 }
 
 #[test]
@@ -157,9 +160,13 @@ fn test_corrupt_bytecode_entry_length() {
     v.extend_from_slice(&(10u32).to_le_bytes()); // entry_len
     v.extend_from_slice(&[1u8, 2u8]); // only 2 bytes of the entry
     let res = BytecodeTable::from_binary(&v);
-    assert!(res.is_err());
-    let err = res.err().unwrap();
-    assert!(err.contains("BytecodeTable"), "unexpected error: {}", err);
+    assert_eq!(res.err(), Some(MaruFileError::Truncated {
+        section: Section::BytecodeTable,
+        index: Some(0),
+        offset: 8,
+        expected: 10,
+        actual: 2,
+    }));
 }
 
 #[test]
@@ -172,12 +179,11 @@ fn test_corrupt_variant_invalid_type_tag() {
     v.extend_from_slice(&10u32.to_le_bytes()); // member name
     v.push(255u8); // invalid MaruTypeTag tag
     let res = MaruVariant::from_binary(&v);
-    assert!(res.is_err());
-    let err = res.err().unwrap();
-    assert!(err.contains("Unknown MaruTypeTag tag"), "unexpected error: {}", err);
+    assert_eq!(res.err(), Some(MaruFileError::UnknownTypeTag { section: Section::Objects, index: None, offset: 16, tag: 255 }));
 }
 
 #[test]
+#[allow(clippy::vec_init_then_push)]
 fn test_corrupt_maru_file_missing_object() {
     // MaruFile header with objects_len = 2 but only one object present
     let mut file_bytes = Vec::new();
@@ -198,8 +204,16 @@ fn test_corrupt_maru_file_missing_object() {
     // minimal empty string table
     file_bytes.extend_from_slice(&0u32.to_le_bytes());
 
-    let res = MaruFile::from_binary(&file_bytes);
-    assert!(res.is_err());
+    // the second object swallows the following counts and then runs out of bytes
+    let err = MaruFile::from_binary(&file_bytes).err().expect("missing object");
+    assert_eq!(err, MaruFileError::Truncated {
+        section: Section::Objects,
+        index: Some(1),
+        offset: 40,
+        expected: 4,
+        actual: 0,
+    });
+    assert_eq!(err.to_string(), "objects entry 1 at offset 40: expected 4 bytes but only 0 remain");
 }
 
 #[test]
@@ -212,7 +226,278 @@ fn test_corrupt_function_trailing_fields() {
     v.extend_from_slice(&MaruTypeTag::U8.into_binary()); // return type
     // leave off the required 8 trailing bytes
     let res = MaruFunction::from_binary(&v);
-    assert!(res.is_err());
-    let err = res.err().unwrap();
-    assert!(err.contains("MaruFunction"), "unexpected error: {}", err);
+    assert_eq!(res.err(), Some(MaruFileError::Truncated {
+        section: Section::Functions,
+        index: None,
+        offset: 13,
+        expected: 4,
+        actual: 0,
+    }));
+}
+
+/// A sectioned file with a string and a function, and the offset of its section directory.
+fn sectioned() -> (Vec<u8>, usize) {
+    let mut file = MaruFile::new();
+    file.minor_version = SECTIONED_MINOR_VERSION;
+    file.module_name = file.add_string("mod".into());
+    let code = file.add_bytecode(vec![1, 2, 3].into_boxed_slice());
+    file.add_function(MaruFunction { name: 0, type_name: 0, parameters: vec![], return_type: MaruTypeTag::Unit, bytecode_index: code, variables: 0 });
+    (file.into_binary(), 12)
+}
+
+/// Entry `index` of the section directory at `directory`.
+fn entry(binary: &[u8], directory: usize, index: usize) -> SectionEntry {
+    let field = |at: usize| u32::from_le_bytes(binary[at..at + 4].try_into().unwrap());
+    let at = directory + index * SectionEntry::SIZE;
+    SectionEntry { kind: field(at), offset: field(at + 4), length: field(at + 8) }
+}
+
+/// Adds `extra` to the end of the section directory at `directory` and `data` to the end of the file.
+fn with_section(binary: &[u8], directory: usize, extra: SectionEntry, data: &[u8]) -> Vec<u8> {
+    let mut extended = binary[..directory - 4].to_vec();
+    extended.extend_from_slice(&10u32.to_le_bytes());
+    for index in 0..9 {
+        let mut entry = entry(binary, directory, index);
+        entry.offset += SectionEntry::SIZE as u32;
+        extended.extend_from_slice(&entry.into_binary());
+    }
+    extended.extend_from_slice(&extra.into_binary());
+    extended.extend_from_slice(&binary[directory + 9 * SectionEntry::SIZE..]);
+    extended.extend_from_slice(data);
+    extended
+}
+
+/// The 64 bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3))
+}
+
+/// The CRC-32 (IEEE) of `bytes`.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[test]
+fn test_sectioned_layout_has_a_directory() {
+    let (binary, directory) = sectioned();
+    assert_eq!(&binary[8..directory], &9u32.to_le_bytes());
+    let bytecode = entry(&binary, directory, 4);
+    assert_eq!(Section::from_kind(bytecode.kind), Some(Section::BytecodeTable));
+    let start = bytecode.offset as usize;
+    assert_eq!(&binary[start..start + bytecode.length as usize], &[1, 0, 0, 0, 3, 0, 0, 0, 1, 2, 3]);
+
+    let file = MaruFile::from_binary(&binary).expect("from_binary");
+    assert_eq!(file.functions.len(), 1);
+    assert_eq!(file.get_string(0), "mod");
+    assert_eq!(file.into_binary(), binary);
+}
+
+#[test]
+fn test_sectioned_layout_ignores_unknown_sections() {
+    let (binary, directory) = sectioned();
+    // A section of a kind this version doesn't know is skipped
+    let unknown = SectionEntry { kind: 99, offset: binary.len() as u32 + 12, length: 3 };
+    let extended = with_section(&binary, directory, unknown, b"new");
+    let file = MaruFile::from_binary(&extended).expect("from_binary");
+    assert_eq!(file.into_binary(), binary);
+
+    // An unknown section isn't read, so its range isn't checked either
+    let extended = with_section(&binary, directory, SectionEntry { kind: 99, offset: u32::MAX, length: u32::MAX }, &[]);
+    let file = MaruFile::from_binary(&extended).expect("from_binary");
+    assert_eq!(file.into_binary(), binary);
+    assert!(MaruFileView::new(&extended).is_ok());
+
+    // Known sections may only appear once
+    let mut duplicated = binary.clone();
+    duplicated[directory + SectionEntry::SIZE..directory + 2 * SectionEntry::SIZE]
+        .copy_from_slice(&entry(&binary, directory, 0).into_binary());
+    assert_eq!(
+        MaruFile::from_binary(&duplicated).err(),
+        Some(MaruFileError::DuplicateSection { index: 1, offset: 24, section: Section::Objects }),
+    );
+
+    // The header of a checksummed file covers unknown sections too
+    let binary = checksummed(vec![1, 2, 3]).into_binary();
+    let directory = 24;
+    let unknown = SectionEntry { kind: 99, offset: binary.len() as u32 + 12, length: 3 };
+    let mut extended = with_section(&binary, directory, unknown, b"new");
+    let sections = &extended[directory + 10 * SectionEntry::SIZE..];
+    let hash = fnv1a(&[&binary[4..8], sections].concat());
+    extended[12..20].copy_from_slice(&hash.to_le_bytes());
+    let checksum = crc32(&extended[12..]);
+    extended[8..12].copy_from_slice(&checksum.to_le_bytes());
+    let file = MaruFile::from_binary(&extended).expect("from_binary");
+    assert_eq!(file.into_binary(), binary);
+    assert_eq!(MaruFileView::new(&extended).expect("view").content_hash, Some(hash));
+}
+
+#[test]
+fn test_write_to_and_read_from_io() {
+    for minor_version in [0, SECTIONED_MINOR_VERSION] {
+        let mut file = MaruFile::new();
+        file.minor_version = minor_version;
+        file.module_name = file.add_string("mod".into());
+        let code = file.add_bytecode(vec![1, 2, 3].into_boxed_slice());
+        file.add_location(MaruLocation::new(0, vec![(0, 3)]));
+        file.add_global(MaruGlobal { name: 0, type_tag: MaruTypeTag::Array(Box::new(MaruTypeTag::U8)), init_index: code });
+
+        let mut written = Vec::new();
+        file.write_to(&mut written).expect("write_to");
+        // the file is still usable after writing it
+        assert_eq!(file.get_bytecode(code), [1, 2, 3]);
+        assert_eq!(written, file.into_binary());
+
+        let read = MaruFile::read_from(written.as_slice()).expect("read_from");
+        assert_eq!(read.get_string(0), "mod");
+        assert_eq!(read.into_binary(), written);
+
+        let error = MaruFile::read_from(&written[..written.len() - 1]).err().expect("truncated file");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.get_ref().and_then(|error| error.downcast_ref::<MaruFileError>()).is_some());
+    }
+}
+
+fn checksummed(code: Vec<u8>) -> MaruFile {
+    let mut file = MaruFile::new();
+    file.minor_version = CHECKSUM_MINOR_VERSION;
+    file.module_name = file.add_string("mod".into());
+    file.add_bytecode(code.into_boxed_slice());
+    file
+}
+
+#[test]
+fn test_checksum_detects_corruption() {
+    let binary = checksummed(vec![1, 2, 3]).into_binary();
+    assert!(MaruFile::from_binary(&binary).is_ok());
+
+    // flip a byte of the bytecode
+    let mut corrupt = binary.clone();
+    let at = binary.windows(3).position(|bytes| bytes == [1, 2, 3]).expect("bytecode") + 1;
+    corrupt[at] ^= 0x04;
+    let error = MaruFile::from_binary(&corrupt).err().expect("corrupt file parsed");
+    assert!(matches!(error, MaruFileError::ChecksumMismatch { offset: 8, .. }), "{:?}", error);
+    assert!(MaruFileView::new(&corrupt).is_err());
+}
+
+#[test]
+fn test_content_hash_identifies_the_contents() {
+    let file = checksummed(vec![1, 2, 3]);
+    let hash = file.content_hash();
+    let binary = file.into_binary();
+    assert_eq!(&binary[12..20], &hash.to_le_bytes());
+    assert_eq!(MaruFileView::new(&binary).expect("view").content_hash, Some(hash));
+
+    // the hash doesn't depend on the version or the layout
+    let mut legacy = checksummed(vec![1, 2, 3]);
+    legacy.minor_version = 0;
+    assert_eq!(legacy.content_hash(), hash);
+    assert_eq!(MaruFileView::new(&legacy.into_binary()).expect("view").content_hash, None);
+
+    assert_ne!(checksummed(vec![1, 2, 4]).content_hash(), hash);
+}
+
+#[test]
+fn test_imports_and_exports_roundtrip() {
+    let mut file = MaruFile::new();
+    file.minor_version = CHECKSUM_MINOR_VERSION;
+    file.module_name = file.add_string("app".into());
+    let module = file.add_string("std".into());
+    let print = file.add_string("print".into());
+    let string = file.add_string("String".into());
+    let main = file.add_string("main".into());
+    file.add_function(MaruFunction { name: main, type_name: main, parameters: vec![], return_type: MaruTypeTag::Unit, bytecode_index: 0, variables: 0 });
+    let signature = MaruSignature::Function { parameters: vec![MaruTypeTag::Object(string)], return_type: MaruTypeTag::Unit };
+    assert_eq!(file.add_import(MaruImport { module, name: string, signature: MaruSignature::Object }), None);
+    assert_eq!(file.add_import(MaruImport { module, name: print, signature }), Some(1));
+    file.add_export(MaruExport { name: main, kind: MaruSymbolKind::Function, index: 0 });
+
+    let binary = file.into_binary();
+    let file = MaruFile::from_binary(&binary).expect("from_binary");
+    assert_eq!(file.imports.len(), 2);
+    assert_eq!(file.exports[0].kind, MaruSymbolKind::Function);
+    assert!(file.get_function_import(0).is_none());
+    let import = file.get_function_import(1).expect("function import");
+    assert_eq!(file.get_string(import.name), "print");
+    assert!(matches!(&import.signature, MaruSignature::Function { parameters, .. } if parameters.len() == 1));
+
+    let view = MaruFileView::new(&binary).expect("view");
+    assert_eq!(view.import_count(), 2);
+    assert_eq!(view.exports().map(|export| export.name).collect::<Vec<_>>(), [main]);
+    assert_eq!(view.to_file().into_binary(), binary);
+    assert_eq!(file.into_binary(), binary);
+
+    // the kind of an export must be one of the three
+    let export = [0, 0, 0, 0, 3, 0, 0, 0, 0];
+    assert_eq!(MaruExport::from_binary(&export).err(), Some(MaruFileError::UnknownSymbolKind {
+        section: Section::Exports,
+        index: None,
+        offset: 4,
+        kind: 3,
+    }));
+}
+
+#[test]
+fn test_module_version_and_dependencies_roundtrip() {
+    let mut file = MaruFile::new();
+    file.minor_version = CHECKSUM_MINOR_VERSION;
+    file.module_name = file.add_string("app".into());
+    file.module_version = MaruVersion::new(1, 4, 0);
+    let std = file.add_string("std".into());
+    let io = file.add_string("io".into());
+    file.add_dependency(MaruDependency { module: std, requirement: MaruVersionReq::Caret(MaruVersion::new(1, 2, 0)) });
+    file.add_dependency(MaruDependency { module: io, requirement: MaruVersionReq::Any });
+
+    let binary = file.into_binary();
+    let file = MaruFile::from_binary(&binary).expect("from_binary");
+    assert_eq!(file.module_version.to_string(), "1.4.0");
+    let requirements = file.dependencies.iter().map(|dependency| dependency.requirement.to_string()).collect::<Vec<_>>();
+    assert_eq!(requirements, ["^1.2.0", "*"]);
+    let view = MaruFileView::new(&binary).expect("view");
+    assert_eq!(view.module_version, MaruVersion::new(1, 4, 0));
+    assert_eq!(view.dependencies().map(|dependency| dependency.module).collect::<Vec<_>>(), [std, io]);
+    assert_eq!(view.to_file().into_binary(), binary);
+
+    // the legacy layout has no room for them
+    let mut legacy = MaruFile::from_binary(&binary).expect("from_binary");
+    legacy.minor_version = 0;
+    let legacy = MaruFile::from_binary(&legacy.into_binary()).expect("from_binary");
+    assert_eq!(legacy.module_version, MaruVersion::default());
+    assert!(legacy.dependencies.is_empty());
+
+    assert_eq!(MaruVersionReq::from_binary(&[9]).err(), Some(MaruFileError::UnknownVersionOp {
+        section: Section::Module,
+        index: None,
+        offset: 0,
+        op: 9,
+    }));
+}
+
+#[test]
+fn test_version_requirements_match_like_semver() {
+    let version = MaruVersion::new;
+    let cases = [
+        (MaruVersionReq::Any, version(0, 0, 0), true),
+        (MaruVersionReq::Exact(version(1, 2, 3)), version(1, 2, 3), true),
+        (MaruVersionReq::Exact(version(1, 2, 3)), version(1, 2, 4), false),
+        (MaruVersionReq::AtLeast(version(1, 2, 3)), version(3, 0, 0), true),
+        (MaruVersionReq::AtLeast(version(1, 2, 3)), version(1, 2, 2), false),
+        (MaruVersionReq::Caret(version(1, 2, 3)), version(1, 9, 0), true),
+        (MaruVersionReq::Caret(version(1, 2, 3)), version(1, 2, 2), false),
+        (MaruVersionReq::Caret(version(1, 2, 3)), version(2, 0, 0), false),
+        (MaruVersionReq::Caret(version(0, 2, 3)), version(0, 2, 9), true),
+        (MaruVersionReq::Caret(version(0, 2, 3)), version(0, 3, 0), false),
+        (MaruVersionReq::Caret(version(0, 0, 3)), version(0, 0, 4), false),
+        (MaruVersionReq::Tilde(version(1, 2, 3)), version(1, 2, 9), true),
+        (MaruVersionReq::Tilde(version(1, 2, 3)), version(1, 3, 0), false),
+    ];
+    for (requirement, version, matches) in cases {
+        assert_eq!(requirement.matches(&version), matches, "{} against {}", requirement, version);
+    }
 }
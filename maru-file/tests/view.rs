@@ -0,0 +1,87 @@
+use maru_file::*;
+
+fn sample(minor_version: u8) -> Vec<u8> {
+    let mut file = MaruFile::new();
+    file.minor_version = minor_version;
+    file.module_name = file.add_string("std".into());
+    let name = file.add_string("len".into());
+    let option = file.add_string("Option".into());
+    file.add_object(MaruObject {
+        name: option,
+        type_name: option,
+        variants: vec![MaruVariant { name: option, type_name: option, members: vec![(name, MaruTypeTag::U8)] }],
+        internal: 0,
+    });
+    let code = file.add_bytecode(vec![7, 8, 9].into_boxed_slice());
+    file.add_location(MaruLocation::new(name, vec![(1, 2)]));
+    file.add_function(MaruFunction {
+        name,
+        type_name: name,
+        parameters: vec![MaruTypeTag::String],
+        return_type: MaruTypeTag::U64,
+        bytecode_index: code,
+        variables: 1,
+    });
+    file.add_global(MaruGlobal { name: option, type_tag: MaruTypeTag::Bool, init_index: -1 });
+    file.into_binary()
+}
+
+#[test]
+fn test_view_borrows_strings_and_bytecode() {
+    for minor_version in [0, SECTIONED_MINOR_VERSION] {
+        let binary = sample(minor_version);
+        let view = MaruFileView::new(&binary).expect("view");
+        let bounds = binary.as_ptr_range();
+
+        assert_eq!(view.get_string(view.module_name), Some("std"));
+        assert_eq!(view.strings().collect::<Vec<_>>(), ["std", "len", "Option"]);
+        assert!(view.strings().all(|string| bounds.contains(&string.as_ptr())));
+        let code = view.get_bytecode(0).expect("bytecode");
+        assert_eq!(code, [7, 8, 9]);
+        assert!(bounds.contains(&code.as_ptr()));
+        assert_eq!(view.get_bytecode(1), None);
+        assert_eq!(view.get_bytecode(-1), None);
+
+        let function = view.get_function(1).expect("function");
+        assert_eq!(function.bytecode_index, 0);
+        assert_eq!(function.variables, 1);
+        assert_eq!(view.object(0).map(|object| object.variants.len()), Some(1));
+        assert_eq!(view.global(0).map(|global| global.name), Some(2));
+        assert_eq!(view.get_location(0).map(|location| location.locations), Some(vec![(1, 2)]));
+        assert!(view.function(1).is_none());
+        assert_eq!(view.get_object(2).map(|object| object.name), Some(2));
+        assert_eq!(view.get_global(2).map(|global| global.init_index), Some(-1));
+        assert!(view.get_object(1).is_none());
+
+        assert_eq!(view.to_file().into_binary(), binary);
+    }
+}
+
+#[test]
+fn test_view_rejects_what_from_binary_rejects() {
+    for minor_version in [0, SECTIONED_MINOR_VERSION] {
+        let binary = sample(minor_version);
+        for len in 0..binary.len() {
+            assert_eq!(
+                MaruFileView::new(&binary[..len]).err(),
+                MaruFile::from_binary(&binary[..len]).err(),
+                "prefix of {} bytes",
+                len,
+            );
+        }
+        // the view skips over entries instead of decoding them, and must fail the same way
+        for offset in 0..binary.len() {
+            for byte in [0x00, 0x0d, 0x0e, 0x7f, 0xff] {
+                let mut mutated = binary.clone();
+                mutated[offset] = byte;
+                assert_eq!(
+                    MaruFileView::new(&mutated).err(),
+                    MaruFile::from_binary(&mutated).err(),
+                    "byte {} set to {:#x}",
+                    offset,
+                    byte,
+                );
+            }
+        }
+    }
+}
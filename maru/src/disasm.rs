@@ -0,0 +1,181 @@
+//! A human readable listing of a Maru file.
+
+use std::fmt::{self, Write};
+
+use bytecode::{DecodedInstruction, Instructions};
+use maru_file::{BytecodeIndex, MaruFile, MaruSignature, MaruSymbolKind, MaruTypeTag, MaruVersion, StringIndex};
+
+/// The column source spans are aligned to.
+const SPAN_COLUMN: usize = 40;
+
+/// Lists the contents of a Maru file.
+///
+/// Bytecode is printed as assembly. When the file has a `LocationsMap`
+/// entry for a bytecode entry, the n-th location is shown next to the
+/// n-th instruction.
+pub fn disassemble(file: &MaruFile) -> String {
+    let mut output = String::new();
+    Disassembler { file, output: &mut output }.file().expect("writing to a String cannot fail");
+    output
+}
+
+struct Disassembler<'a> {
+    file: &'a MaruFile,
+    output: &'a mut String,
+}
+
+impl Disassembler<'_> {
+    fn file(&mut self) -> fmt::Result {
+        let file = self.file;
+        writeln!(
+            self.output,
+            "module {} (version {}.{}.{})",
+            self.string(file.module_name),
+            file.major_version,
+            file.minor_version,
+            file.patch_version,
+        )?;
+
+        if file.module_version != MaruVersion::default() || !file.dependencies.is_empty() {
+            writeln!(self.output, "version {}", file.module_version)?;
+        }
+        for dependency in &file.dependencies {
+            writeln!(self.output, "requires {} {}", self.string(dependency.module), dependency.requirement)?;
+        }
+
+        if !file.imports.is_empty() {
+            writeln!(self.output)?;
+        }
+        // Imported functions are called by the ids following the file's own functions
+        let mut function_id = file.functions.len();
+        for import in &file.imports {
+            let name = format!("{}.{}", self.string(import.module), self.string(import.name));
+            match &import.signature {
+                MaruSignature::Function { parameters, return_type } => {
+                    write!(self.output, "import function {} {}", function_id, name)?;
+                    self.signature(parameters, return_type)?;
+                    writeln!(self.output)?;
+                    function_id += 1;
+                }
+                MaruSignature::Object => writeln!(self.output, "import object {}", name)?,
+                MaruSignature::Global(type_tag) => {
+                    writeln!(self.output, "import global {}: {}", name, self.type_tag(type_tag))?
+                }
+            }
+        }
+
+        if !file.exports.is_empty() {
+            writeln!(self.output)?;
+        }
+        for export in &file.exports {
+            let kind = match export.kind {
+                MaruSymbolKind::Function => "function",
+                MaruSymbolKind::Object => "object",
+                MaruSymbolKind::Global => "global",
+            };
+            writeln!(self.output, "export {} {} = {}", kind, self.string(export.name), export.index)?;
+        }
+
+        for object in &file.objects {
+            writeln!(self.output)?;
+            write!(self.output, "object {}", self.string(object.type_name))?;
+            if object.name != object.type_name {
+                write!(self.output, " ({})", self.string(object.name))?;
+            }
+            if object.internal != 0 {
+                write!(self.output, " internal {}", object.internal)?;
+            }
+            writeln!(self.output)?;
+            for (id, variant) in object.variants.iter().enumerate() {
+                writeln!(self.output, "    variant {} {}", id, self.string(variant.type_name))?;
+                for (name, type_tag) in &variant.members {
+                    writeln!(self.output, "        {}: {}", self.string(*name), self.type_tag(type_tag))?;
+                }
+            }
+        }
+
+        for (id, global) in file.globals.iter().enumerate() {
+            writeln!(self.output)?;
+            writeln!(self.output, "global g{} {}: {}", id, self.string(global.name), self.type_tag(&global.type_tag))?;
+            self.bytecode(global.init_index)?;
+        }
+
+        for (id, function) in file.functions.iter().enumerate() {
+            writeln!(self.output)?;
+            write!(self.output, "function {} {}", id, self.string(function.type_name))?;
+            self.signature(&function.parameters, &function.return_type)?;
+            writeln!(self.output, " ; {} registers", function.variables)?;
+            self.bytecode(function.bytecode_index)?;
+        }
+        Ok(())
+    }
+
+    fn bytecode(&mut self, index: BytecodeIndex) -> fmt::Result {
+        if index < 0 {
+            return writeln!(self.output, "    ; internal");
+        }
+        let Some(code) = self.file.bytecode_table.entries.get(index as usize) else {
+            return writeln!(self.output, "    ; missing bytecode #{}", index);
+        };
+        let location = self.file.locations_map.entries.get(index as usize);
+        for (i, instruction) in Instructions::with_encoding(code, crate::encoding(self.file)).enumerate() {
+            let (offset, instruction) = match instruction {
+                Ok(instruction) => instruction,
+                Err(error) => return writeln!(self.output, "    ; {}", error),
+            };
+            let line = match instruction {
+                DecodedInstruction::StartBlock { .. } => format!("{:04x} {}", offset, instruction),
+                _ => format!("{:04x}     {}", offset, instruction),
+            };
+            match location.and_then(|location| location.locations.get(i).map(|span| (location.file, span))) {
+                Some((source, (start, end))) => writeln!(
+                    self.output,
+                    "{:<width$} ; {}:{}..{}",
+                    line,
+                    self.string(source),
+                    start,
+                    end,
+                    width = SPAN_COLUMN,
+                )?,
+                None => writeln!(self.output, "{}", line)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn signature(&mut self, parameters: &[MaruTypeTag], return_type: &MaruTypeTag) -> fmt::Result {
+        write!(self.output, "(")?;
+        for (i, parameter) in parameters.iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            write!(self.output, "{}{}", separator, self.type_tag(parameter))?;
+        }
+        write!(self.output, ") -> {}", self.type_tag(return_type))
+    }
+
+    fn string(&self, index: StringIndex) -> String {
+        match self.file.string_table.entries.get(index as usize) {
+            Some(string) => string.clone(),
+            None => format!("<string #{}>", index),
+        }
+    }
+
+    fn type_tag(&self, type_tag: &MaruTypeTag) -> String {
+        match type_tag {
+            MaruTypeTag::Unit => "unit".to_string(),
+            MaruTypeTag::Bool => "bool".to_string(),
+            MaruTypeTag::U8 => "u8".to_string(),
+            MaruTypeTag::I8 => "i8".to_string(),
+            MaruTypeTag::U16 => "u16".to_string(),
+            MaruTypeTag::I16 => "i16".to_string(),
+            MaruTypeTag::U32 => "u32".to_string(),
+            MaruTypeTag::I32 => "i32".to_string(),
+            MaruTypeTag::U64 => "u64".to_string(),
+            MaruTypeTag::I64 => "i64".to_string(),
+            MaruTypeTag::F32 => "f32".to_string(),
+            MaruTypeTag::F64 => "f64".to_string(),
+            MaruTypeTag::Object(name) => self.string(*name),
+            MaruTypeTag::Array(element) => format!("[{}]", self.type_tag(element)),
+            MaruTypeTag::String => "string".to_string(),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+pub mod disasm;
+pub mod verifier;
+pub mod vm;
+
+use bytecode::Encoding;
+use maru_file::MaruFile;
+
+/// The operand encoding of the function bodies in `file`.
+pub fn encoding(file: &MaruFile) -> Encoding {
+    if file.compact_bytecode() { Encoding::Compact } else { Encoding::Fixed }
+}
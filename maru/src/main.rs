@@ -1,5 +1,65 @@
-mod vm;
+use std::{env, fs, process::ExitCode};
 
-fn main() {
-    println!("Hello, world!");
+use maru::vm::linker::Linker;
+use maru_file::MaruFile;
+
+const USAGE: &str = "usage: maru disasm <file>\n       maru link <file>...";
+
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["disasm", path] => disasm(path),
+        ["link", paths @ ..] if !paths.is_empty() => link(paths),
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn disasm(path: &str) -> ExitCode {
+    match load(path) {
+        Some(file) => {
+            print!("{}", maru::disasm::disassemble(&file));
+            ExitCode::SUCCESS
+        }
+        None => ExitCode::FAILURE,
+    }
+}
+
+/// Links the given modules, reporting every unmet dependency and unresolved symbol.
+fn link(paths: &[&str]) -> ExitCode {
+    let Some(files) = paths.iter().map(|path| load(path)).collect::<Option<Vec<_>>>() else {
+        return ExitCode::FAILURE;
+    };
+    let mut linker = Linker::new();
+    for file in &files {
+        linker.add_module(file);
+    }
+    match linker.link() {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("error: {}", error);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn load(path: &str) -> Option<MaruFile> {
+    let binary = match fs::read(path) {
+        Ok(binary) => binary,
+        Err(error) => {
+            eprintln!("error: could not read `{}`: {}", path, error);
+            return None;
+        }
+    };
+    match MaruFile::from_binary(&binary) {
+        Ok(file) => Some(file),
+        Err(error) => {
+            eprintln!("error: could not load `{}`: {}", path, error);
+            None
+        }
+    }
 }
@@ -0,0 +1,807 @@
+//! A static verifier for the function bodies of a Maru file.
+//!
+//! The verifier infers the type of every register at every instruction and
+//! checks each instruction against the types, the signatures of the
+//! functions it calls and the objects and globals of the file. Registers
+//! whose type depends on the path taken are not checked, so code it accepts
+//! can still trap at runtime.
+//!
+//! Object types are numbered like the `ObjectDescTable` numbers them: the
+//! objects of the file follow the builtin types, starting at
+//! `FIRST_USER_TYPE`.
+
+use std::{collections::HashMap, fmt};
+
+use bytecode::{
+    CallArgument, DecodeError, DecodedInstruction, Id, Instruction, Instructions, JumpBranch, Register,
+};
+use maru_file::{BytecodeIndex, MaruFile, MaruFunction, MaruObject, MaruSignature, MaruTypeTag};
+
+use crate::vm::{
+    TypeSymbol, VariantId, VmType,
+    interpreter::{converts, converts_from, integer_width, signed, unsigned},
+    tables::{CLOSURE_TYPE, FIRST_USER_TYPE, STACK_FRAME_TYPE},
+};
+
+/// A problem found in a function body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The index of the function in the file.
+    pub function: usize,
+    /// The offset of the offending instruction in the function body.
+    pub offset: usize,
+    pub error: VerifyError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    MissingBytecode(BytecodeIndex),
+    Decode(DecodeError),
+    RegisterOutOfRange { register: Register, len: usize },
+    /// `expected` is `None` when any type of the right kind is accepted.
+    TypeMismatch { instruction: Instruction, register: Register, expected: Option<VmType>, found: VmType },
+    ReturnMismatch { expected: VmType, found: VmType },
+    UnknownFunction(Id),
+    ArgumentCount { function: Id, expected: usize, found: usize },
+    UnknownGlobal(Id),
+    UnknownType(TypeSymbol),
+    UnknownVariant { type_id: TypeSymbol, variant: Id },
+    UnknownField { type_id: TypeSymbol, field: Id },
+    UnknownBlock(Id),
+    /// The branch does not land on an instruction.
+    BranchOutOfRange { block_id: Id, offset: i32 },
+    /// The conversion can't produce `to` from a value of type `from`.
+    InvalidConversion { instruction: Instruction, from: VmType, to: VmType },
+    UnknownString(Id),
+    /// A `Match` case names a variant the scrutinee's type doesn't have.
+    UnknownTag { type_id: TypeSymbol, tag: Id },
+    /// Execution can reach the end of the function without returning.
+    FallsOffEnd,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::MissingBytecode(index) => write!(f, "bytecode entry {} does not exist", index),
+            VerifyError::Decode(error) => write!(f, "{}", error),
+            VerifyError::RegisterOutOfRange { register, len } => {
+                write!(f, "register {} is out of range for a frame of {} registers", register, len)
+            }
+            VerifyError::TypeMismatch { instruction, register, expected: Some(expected), found } => write!(
+                f,
+                "{:?} expects r{} to be {:?} but it is {:?}",
+                instruction, register, expected, found,
+            ),
+            VerifyError::TypeMismatch { instruction, register, expected: None, found } => {
+                write!(f, "{:?} cannot operate on r{} of type {:?}", instruction, register, found)
+            }
+            VerifyError::ReturnMismatch { expected, found } => {
+                write!(f, "returns {:?} from a function returning {:?}", found, expected)
+            }
+            VerifyError::UnknownFunction(function) => write!(f, "unknown function {}", function),
+            VerifyError::ArgumentCount { function, expected, found } => {
+                write!(f, "function {} expects {} arguments but got {}", function, expected, found)
+            }
+            VerifyError::UnknownGlobal(global) => write!(f, "unknown global {}", global),
+            VerifyError::UnknownType(type_id) => write!(f, "unknown type {}", type_id),
+            VerifyError::UnknownVariant { type_id, variant } => {
+                write!(f, "type {} has no variant {}", type_id, variant)
+            }
+            VerifyError::UnknownField { type_id, field } => write!(f, "type {} has no field {}", type_id, field),
+            VerifyError::UnknownBlock(block_id) => write!(f, "unknown block {}", block_id),
+            VerifyError::BranchOutOfRange { block_id, offset } => {
+                write!(f, "branch to block {} at offset {} does not land on an instruction", block_id, offset)
+            }
+            VerifyError::InvalidConversion { instruction, from, to } => {
+                write!(f, "{:?} cannot convert {:?} to {:?}", instruction, from, to)
+            }
+            VerifyError::UnknownString(string) => write!(f, "unknown string {}", string),
+            VerifyError::UnknownTag { type_id, tag } => write!(f, "match tag {} is not a variant of type {}", tag, type_id),
+            VerifyError::FallsOffEnd => write!(f, "execution can fall off the end of the function"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "function {} at offset {:#06x}: {}", self.function, self.offset, self.error)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Verifies every function with a bytecode body.
+pub fn verify(file: &MaruFile) -> Result<(), Vec<Diagnostic>> {
+    let diagnostics = (0..file.functions.len())
+        .filter_map(|function| verify_function(file, function).err())
+        .flatten()
+        .collect::<Vec<_>>();
+    if diagnostics.is_empty() { Ok(()) } else { Err(diagnostics) }
+}
+
+/// Verifies the body of `file.functions[function]` against its signature.
+///
+/// Internal functions have no body and always pass.
+pub fn verify_function(file: &MaruFile, function: usize) -> Result<(), Vec<Diagnostic>> {
+    let signature = &file.functions[function];
+    if signature.bytecode_index < 0 {
+        return Ok(());
+    }
+    let mut verifier = Verifier {
+        file,
+        function,
+        signature,
+        blocks: HashMap::new(),
+        indices: HashMap::new(),
+        offset: 0,
+        report: false,
+        diagnostics: Vec::new(),
+    };
+    match file.bytecode_table.entries.get(signature.bytecode_index as usize) {
+        Some(code) => verifier.run(code),
+        None => verifier.error(VerifyError::MissingBytecode(signature.bytecode_index)),
+    }
+    if verifier.diagnostics.is_empty() { Ok(()) } else { Err(verifier.diagnostics) }
+}
+
+/// What is statically known about a register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Type(VmType),
+    /// An object whose variant is also known.
+    Variant(TypeSymbol, VariantId),
+    /// The type depends on the path taken or can't be known statically.
+    Any,
+}
+
+impl Slot {
+    fn ty(self) -> Option<VmType> {
+        match self {
+            Slot::Type(ty) => Some(ty),
+            Slot::Variant(type_id, _) => Some(VmType::Object(type_id)),
+            Slot::Any => None,
+        }
+    }
+
+    fn merge(self, other: Slot) -> Slot {
+        match (self.ty(), other.ty()) {
+            _ if self == other => self,
+            (Some(a), Some(b)) if a == b => Slot::Type(a),
+            _ => Slot::Any,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    registers: Vec<Slot>,
+    /// The value `LoadReturn` would load.
+    returned: Slot,
+}
+
+impl State {
+    fn merge(&mut self, other: &State) -> bool {
+        let old = self.clone();
+        for (slot, other) in self.registers.iter_mut().zip(&other.registers) {
+            *slot = slot.merge(*other);
+        }
+        self.returned = self.returned.merge(other.returned);
+        *self != old
+    }
+}
+
+enum Flow {
+    Next,
+    Branches(Vec<JumpBranch>),
+    Stop,
+}
+
+/// The signature of a function that is called.
+struct Callee<'a> {
+    parameters: &'a [MaruTypeTag],
+    return_type: &'a MaruTypeTag,
+}
+
+struct Verifier<'a> {
+    file: &'a MaruFile,
+    function: usize,
+    signature: &'a MaruFunction,
+    /// The offset following each `StartBlock`.
+    blocks: HashMap<Id, usize>,
+    /// The index of the instruction at each offset.
+    indices: HashMap<usize, usize>,
+    /// The offset of the instruction being checked.
+    offset: usize,
+    /// Whether errors are reported. Off while the register types settle.
+    report: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Verifier<'a> {
+    fn run(&mut self, code: &[u8]) {
+        let mut decoded = Vec::new();
+        let mut instructions = Instructions::with_encoding(code, crate::encoding(self.file));
+        while let Some(instruction) = instructions.next() {
+            match instruction {
+                Ok((offset, instruction)) => {
+                    if let DecodedInstruction::StartBlock { id } = instruction {
+                        self.blocks.insert(id, instructions.offset());
+                    }
+                    self.indices.insert(offset, decoded.len());
+                    decoded.push((offset, instruction));
+                }
+                Err(error) => {
+                    self.offset = error.offset();
+                    self.report = true;
+                    return self.error(VerifyError::Decode(error));
+                }
+            }
+        }
+        if decoded.is_empty() {
+            self.report = true;
+            return self.error(VerifyError::FallsOffEnd);
+        }
+
+        let len = (self.signature.variables as usize).max(self.signature.parameters.len());
+        let mut registers = vec![Slot::Type(VmType::Unit); len];
+        for (slot, parameter) in registers.iter_mut().zip(&self.signature.parameters) {
+            *slot = self.slot(parameter);
+        }
+        let mut states = vec![None; decoded.len()];
+        states[0] = Some(State { registers, returned: Slot::Type(VmType::Unit) });
+
+        let mut worklist = vec![0];
+        while let Some(index) = worklist.pop() {
+            let mut state = states[index].clone().expect("queued instructions have a state");
+            for successor in self.step(&decoded, index, &mut state) {
+                if successor == decoded.len() {
+                    continue;
+                }
+                match &mut states[successor] {
+                    Some(old) => {
+                        if old.merge(&state) {
+                            worklist.push(successor);
+                        }
+                    }
+                    slot @ None => {
+                        *slot = Some(state.clone());
+                        worklist.push(successor);
+                    }
+                }
+            }
+        }
+
+        // Unreachable instructions are checked with nothing known about
+        // their registers, so only errors that don't depend on types show up.
+        self.report = true;
+        let unknown = State { registers: vec![Slot::Any; len], returned: Slot::Any };
+        for (index, state) in states.into_iter().enumerate() {
+            let reachable = state.is_some();
+            let mut state = state.unwrap_or_else(|| unknown.clone());
+            let falls_through = self.step(&decoded, index, &mut state).contains(&decoded.len());
+            if reachable && falls_through {
+                self.error(VerifyError::FallsOffEnd);
+            }
+        }
+    }
+
+    /// Checks one instruction, returning the indices of its successors.
+    ///
+    /// The index one past the last instruction stands for falling off the end.
+    fn step(&mut self, decoded: &[(usize, DecodedInstruction)], index: usize, state: &mut State) -> Vec<usize> {
+        let (offset, instruction) = &decoded[index];
+        self.offset = *offset;
+        match self.transfer(instruction, state) {
+            Flow::Next => vec![index + 1],
+            Flow::Stop => vec![],
+            Flow::Branches(branches) => branches.into_iter().filter_map(|branch| self.target(branch)).collect(),
+        }
+    }
+
+    fn target(&mut self, branch: JumpBranch) -> Option<usize> {
+        let Some(start) = self.blocks.get(&branch.block_id) else {
+            self.error(VerifyError::UnknownBlock(branch.block_id));
+            return None;
+        };
+        let target = start.checked_add_signed(branch.offset as isize)
+            .and_then(|target| self.indices.get(&target).copied());
+        if target.is_none() {
+            self.error(VerifyError::BranchOutOfRange { block_id: branch.block_id, offset: branch.offset });
+        }
+        target
+    }
+
+    fn transfer(&mut self, decoded: &DecodedInstruction, state: &mut State) -> Flow {
+        use DecodedInstruction as D;
+        let instruction = decoded.instruction();
+        match decoded {
+            D::Load8 { dst, .. } => self.write(state, *dst, Slot::Type(VmType::U8)),
+            D::Load16 { dst, .. } => self.write(state, *dst, Slot::Type(VmType::U16)),
+            D::Load32 { dst, .. } => self.write(state, *dst, Slot::Type(VmType::U32)),
+            D::Load64 { dst, .. } => self.write(state, *dst, Slot::Type(VmType::U64)),
+            D::Loadf32 { dst, .. } => self.write(state, *dst, Slot::Type(VmType::F32)),
+            D::Loadf64 { dst, .. } => self.write(state, *dst, Slot::Type(VmType::F64)),
+            D::Copy { dst, src } | D::Clone { dst, src } => {
+                let value = self.read(state, *src);
+                self.write(state, *dst, value);
+            }
+            D::Move { dst, src } => {
+                let value = self.read(state, *src);
+                self.write(state, *src, Slot::Type(VmType::Unit));
+                self.write(state, *dst, value);
+            }
+            D::Clear { register } | D::Destroy { register } => {
+                self.write(state, *register, Slot::Type(VmType::Unit))
+            }
+            D::Forget { register } => {
+                self.expect(state, instruction, *register, |ty| ty.is_object());
+                self.write(state, *register, Slot::Type(VmType::Unit));
+            }
+            D::LoadReturn { dst } => {
+                let value = std::mem::replace(&mut state.returned, Slot::Type(VmType::Unit));
+                self.write(state, *dst, value);
+            }
+            D::FetchRef { dst, src } => {
+                self.expect(state, instruction, *src, |ty| ty.is_object());
+                self.write(state, *dst, Slot::Type(VmType::I64));
+            }
+            D::MakeShared { register } => {
+                self.expect(state, instruction, *register, |ty| ty.is_object());
+            }
+            D::SetGlobal { global, src } => {
+                if let Some(ty) = self.global(*global).ty() {
+                    self.expect_type(state, instruction, *src, ty);
+                }
+                self.write(state, *src, Slot::Type(VmType::Unit));
+            }
+            D::CopyGlobal { dst, global } | D::CloneGlobal { dst, global } => {
+                let value = self.global(*global);
+                self.write(state, *dst, value);
+            }
+            D::Binary { instruction, dst, lhs, rhs } => {
+                let value = self.binary(state, *instruction, *lhs, *rhs);
+                self.write(state, *dst, value);
+            }
+            D::Unary { instruction, dst, src } => {
+                let value = self.unary(state, *instruction, *src);
+                self.write(state, *dst, value);
+            }
+            D::Convert { instruction, dst, src, ty } => {
+                let value = self.expect(state, *instruction, *src, |from| converts_from(*instruction, from));
+                if let Some(from) = value.ty()
+                    && !converts(*instruction, from, *ty)
+                {
+                    let to = VmType::from(*ty);
+                    self.error(VerifyError::InvalidConversion { instruction: *instruction, from, to });
+                }
+                self.write(state, *dst, Slot::Type(VmType::from(*ty)));
+            }
+            D::FmaF { dst, lhs, rhs, addend } => {
+                let value = self.expect(state, instruction, *lhs, |ty| matches!(ty, VmType::F32 | VmType::F64));
+                let value = match value.ty() {
+                    Some(ty) => {
+                        self.expect_type(state, instruction, *rhs, ty);
+                        self.expect_type(state, instruction, *addend, ty);
+                        value
+                    }
+                    None => {
+                        let rhs = self.expect(state, instruction, *rhs, |ty| matches!(ty, VmType::F32 | VmType::F64));
+                        self.expect(state, instruction, *addend, |ty| matches!(ty, VmType::F32 | VmType::F64));
+                        rhs
+                    }
+                };
+                self.write(state, *dst, value.ty().map_or(Slot::Any, Slot::Type));
+            }
+            D::CreateObject { dst, type_id, variant } => {
+                let value = match self.object(*type_id) {
+                    None => {
+                        self.error(VerifyError::UnknownType(*type_id));
+                        Slot::Any
+                    }
+                    Some(object) if *variant as usize >= object.variants.len() => {
+                        self.error(VerifyError::UnknownVariant { type_id: *type_id, variant: *variant });
+                        Slot::Type(VmType::Object(*type_id))
+                    }
+                    Some(_) => Slot::Variant(*type_id, *variant),
+                };
+                self.write(state, *dst, value);
+            }
+            D::GetField { dst, object, field }
+            | D::CopyField { dst, object, field }
+            | D::TakeField { dst, object, field } => {
+                let value = self.field(state, instruction, *object, *field);
+                self.write(state, *dst, value);
+            }
+            D::SetField { object, field, src } => {
+                if let Some(ty) = self.field(state, instruction, *object, *field).ty() {
+                    self.expect_type(state, instruction, *src, ty);
+                }
+            }
+            D::MoveField { object, field, src } | D::PlaceField { object, field, src } => {
+                if let Some(ty) = self.field(state, instruction, *object, *field).ty() {
+                    self.expect_type(state, instruction, *src, ty);
+                }
+                self.write(state, *src, Slot::Type(VmType::Unit));
+            }
+            D::Call { function, arguments } | D::CallTail { function, arguments } => {
+                let Some(callee) = self.callee(*function) else {
+                    self.arguments(state, arguments);
+                    state.returned = Slot::Any;
+                    return if instruction == Instruction::Call { Flow::Next } else { Flow::Stop };
+                };
+                if arguments.len() != callee.parameters.len() {
+                    self.error(VerifyError::ArgumentCount {
+                        function: *function,
+                        expected: callee.parameters.len(),
+                        found: arguments.len(),
+                    });
+                }
+                for (argument, parameter) in arguments.iter().zip(callee.parameters) {
+                    if let Some(ty) = self.slot(parameter).ty() {
+                        self.expect_type(state, instruction, argument.register, ty);
+                    }
+                }
+                self.arguments(state, arguments);
+                state.returned = self.slot(callee.return_type);
+                if instruction == Instruction::CallTail {
+                    return Flow::Stop;
+                }
+            }
+            D::Invoke { closure, arguments } | D::InvokeTail { closure, arguments } => {
+                self.expect_type(state, instruction, *closure, VmType::Object(CLOSURE_TYPE));
+                self.arguments(state, arguments);
+                state.returned = Slot::Any;
+                if instruction == Instruction::InvokeTail {
+                    return Flow::Stop;
+                }
+            }
+            D::Return { src } => {
+                let expected = self.slot(&self.signature.return_type);
+                if let (Some(expected), Some(found)) = (expected.ty(), self.read(state, *src).ty())
+                    && expected != found
+                {
+                    self.error(VerifyError::ReturnMismatch { expected, found });
+                }
+                return Flow::Stop;
+            }
+            D::ReturnUnit => {
+                if let Some(expected) = self.slot(&self.signature.return_type).ty()
+                    && expected != VmType::Unit
+                {
+                    self.error(VerifyError::ReturnMismatch { expected, found: VmType::Unit });
+                }
+                return Flow::Stop;
+            }
+            D::ReturnTail | D::ReturnTailUnit => return Flow::Stop,
+            D::CreateClosure { dst, function, captures } => {
+                if let Some(callee) = self.callee(*function) {
+                    if captures.len() > callee.parameters.len() {
+                        self.error(VerifyError::ArgumentCount {
+                            function: *function,
+                            expected: callee.parameters.len(),
+                            found: captures.len(),
+                        });
+                    }
+                    for (capture, parameter) in captures.iter().zip(callee.parameters) {
+                        if let Some(ty) = self.slot(parameter).ty() {
+                            self.expect_type(state, instruction, capture.register, ty);
+                        }
+                    }
+                }
+                self.arguments(state, captures);
+                self.write(state, *dst, Slot::Type(VmType::Object(CLOSURE_TYPE)));
+            }
+            D::CreateFnObject { dst, function } => {
+                self.callee(*function);
+                self.write(state, *dst, Slot::Type(VmType::Object(CLOSURE_TYPE)));
+            }
+            D::Jump { branch } => return Flow::Branches(vec![*branch]),
+            D::If { condition, then_branch, else_branch } => {
+                self.expect(state, instruction, *condition, |ty| integer_width(ty).is_some());
+                return Flow::Branches(vec![*then_branch, *else_branch]);
+            }
+            D::Switch { src, default, cases } => {
+                self.expect(state, instruction, *src, |ty| integer_width(ty).is_some());
+                let mut branches = vec![*default];
+                branches.extend(cases.iter().map(|case| case.branch));
+                return Flow::Branches(branches);
+            }
+            D::Match { src, cases } => {
+                let value = self.expect(state, instruction, *src, |ty| ty.is_object());
+                if let Some(VmType::Object(type_id)) = value.ty() {
+                    match self.object(type_id) {
+                        Some(object) => {
+                            for case in cases.iter().filter(|case| case.tag as usize >= object.variants.len()) {
+                                self.error(VerifyError::UnknownTag { type_id, tag: case.tag });
+                            }
+                        }
+                        None => self.error(VerifyError::TypeMismatch {
+                            instruction,
+                            register: *src,
+                            expected: None,
+                            found: VmType::Object(type_id),
+                        }),
+                    }
+                }
+                return Flow::Branches(cases.iter().map(|case| case.branch).collect());
+            }
+            D::StartBlock { .. } => {}
+            D::Handle { handler, body, .. } => {
+                self.expect_type(state, instruction, *handler, VmType::Object(CLOSURE_TYPE));
+                self.expect_type(state, instruction, *body, VmType::Object(CLOSURE_TYPE));
+                state.returned = Slot::Any;
+            }
+            D::Perform { arguments, .. } => {
+                self.arguments(state, arguments);
+                state.returned = Slot::Any;
+            }
+            D::Resume { continuation, value } => {
+                self.expect_type(state, instruction, *continuation, VmType::Object(STACK_FRAME_TYPE));
+                self.read(state, *value);
+                self.write(state, *continuation, Slot::Type(VmType::Unit));
+                state.returned = Slot::Any;
+            }
+            D::DropContinuation { continuation } => {
+                self.expect_type(state, instruction, *continuation, VmType::Object(STACK_FRAME_TYPE));
+                self.write(state, *continuation, Slot::Type(VmType::Unit));
+            }
+            D::CreateArray { dst, len, fill } => {
+                self.expect(state, instruction, *len, |ty| integer_width(ty).is_some());
+                self.read(state, *fill);
+                self.write(state, *dst, Slot::Type(VmType::Array));
+            }
+            D::GetElement { dst, array, index } => {
+                self.expect_type(state, instruction, *array, VmType::Array);
+                self.expect(state, instruction, *index, |ty| integer_width(ty).is_some());
+                self.write(state, *dst, Slot::Any);
+            }
+            D::SetElement { array, index, src } => {
+                self.expect_type(state, instruction, *array, VmType::Array);
+                self.expect(state, instruction, *index, |ty| integer_width(ty).is_some());
+                self.read(state, *src);
+            }
+            D::ArrayLength { dst, array } => {
+                self.expect_type(state, instruction, *array, VmType::Array);
+                self.write(state, *dst, Slot::Type(VmType::U64));
+            }
+            D::SliceArray { dst, array, start, end } => {
+                self.expect_type(state, instruction, *array, VmType::Array);
+                self.expect(state, instruction, *start, |ty| integer_width(ty).is_some());
+                self.expect(state, instruction, *end, |ty| integer_width(ty).is_some());
+                self.write(state, *dst, Slot::Type(VmType::Array));
+            }
+            D::CopyElements { dst, dst_start, src, src_start, count } => {
+                self.expect_type(state, instruction, *dst, VmType::Array);
+                self.expect_type(state, instruction, *src, VmType::Array);
+                for register in [dst_start, src_start, count] {
+                    self.expect(state, instruction, *register, |ty| integer_width(ty).is_some());
+                }
+            }
+            D::LoadString { dst, string } => {
+                if *string as usize >= self.file.string_table.entries.len() {
+                    self.error(VerifyError::UnknownString(*string));
+                }
+                self.write(state, *dst, Slot::Type(VmType::String));
+            }
+            D::ConcatString { dst, lhs, rhs } | D::CompareString { dst, lhs, rhs } => {
+                self.expect_type(state, instruction, *lhs, VmType::String);
+                self.expect_type(state, instruction, *rhs, VmType::String);
+                let ty = if instruction == Instruction::ConcatString { VmType::String } else { VmType::I32 };
+                self.write(state, *dst, Slot::Type(ty));
+            }
+            D::StringLength { dst, src } => {
+                self.expect_type(state, instruction, *src, VmType::String);
+                self.write(state, *dst, Slot::Type(VmType::U64));
+            }
+            D::StringByte { dst, string, index } | D::StringChar { dst, string, index } => {
+                self.expect_type(state, instruction, *string, VmType::String);
+                self.expect(state, instruction, *index, |ty| integer_width(ty).is_some());
+                let ty = if instruction == Instruction::StringByte { VmType::U8 } else { VmType::U32 };
+                self.write(state, *dst, Slot::Type(ty));
+            }
+            D::SliceString { dst, string, start, end } => {
+                self.expect_type(state, instruction, *string, VmType::String);
+                self.expect(state, instruction, *start, |ty| integer_width(ty).is_some());
+                self.expect(state, instruction, *end, |ty| integer_width(ty).is_some());
+                self.write(state, *dst, Slot::Type(VmType::String));
+            }
+        }
+        Flow::Next
+    }
+
+    fn binary(&mut self, state: &State, instruction: Instruction, lhs: Register, rhs: Register) -> Slot {
+        use Instruction::*;
+        let compare = matches!(
+            instruction,
+            EqI | NeqI | EqF | NeqF | LtU | GtU | LteU | GteU | LtS | GtS | LteS | GteS | LtF | GtF | LteF | GteF
+        );
+        let result = |slot: Slot| if compare { Slot::Type(VmType::Bool) } else { slot };
+        if matches!(
+            instruction,
+            AddF | SubF | MulF | DivF | RemF | MinF | MaxF | CopySignF | EqF | NeqF | LtF | GtF | LteF | GteF
+        ) {
+            let value = self.expect(state, instruction, lhs, |ty| matches!(ty, VmType::F32 | VmType::F64));
+            match value.ty() {
+                Some(ty) => self.expect_type(state, instruction, rhs, ty),
+                None => self.expect(state, instruction, rhs, |ty| matches!(ty, VmType::F32 | VmType::F64)),
+            };
+            return result(value.ty().map_or(Slot::Any, Slot::Type));
+        }
+
+        let value = self.expect(state, instruction, lhs, |ty| integer_width(ty).is_some());
+        let width = value.ty().and_then(integer_width);
+        let shift = matches!(instruction, ShiftLeft | LogicalShiftRight | ArithmeticShiftRight | RotateLeft | RotateRight);
+        match width {
+            Some(width) if !shift => self.expect(state, instruction, rhs, |ty| integer_width(ty) == Some(width)),
+            _ => self.expect(state, instruction, rhs, |ty| integer_width(ty).is_some()),
+        };
+        let Some(width) = width else {
+            return result(Slot::Any);
+        };
+        result(match instruction {
+            AddU | SubU | MulU | DivU | RemU | CheckedAddU | CheckedSubU | CheckedMulU | SaturatingAddU
+            | SaturatingSubU | SaturatingMulU => Slot::Type(unsigned(width)),
+            AddS | SubS | MulS | DivS | RemS | CheckedAddS | CheckedSubS | CheckedMulS | CheckedDivS
+            | SaturatingAddS | SaturatingSubS | SaturatingMulS | SaturatingDivS => Slot::Type(signed(width)),
+            _ => value,
+        })
+    }
+
+    fn unary(&mut self, state: &State, instruction: Instruction, src: Register) -> Slot {
+        use Instruction::*;
+        let float = |ty| matches!(ty, VmType::F32 | VmType::F64);
+        match instruction {
+            Not => self.expect(state, instruction, src, |ty| integer_width(ty).is_some()),
+            ByteSwap | PopCount | CountLeadingZeros | CountTrailingZeros | BitReverse => {
+                self.expect(state, instruction, src, |ty| integer_width(ty).is_some_and(|width| width >= 8))
+            }
+            NegF | AbsF | SqrtF | FloorF | CeilF | TruncF | RoundF => self.expect(state, instruction, src, float),
+            IsNull => {
+                self.expect(state, instruction, src, |ty| ty.is_object());
+                Slot::Type(VmType::Bool)
+            }
+            _ => {
+                self.expect(state, instruction, src, float);
+                Slot::Type(VmType::Bool)
+            }
+        }
+    }
+
+    /// The type of a field, or `Any` when it depends on the variant.
+    fn field(&mut self, state: &State, instruction: Instruction, register: Register, field: Id) -> Slot {
+        let value = self.expect(state, instruction, register, |ty| ty.is_object());
+        let (type_id, variant) = match value {
+            Slot::Variant(type_id, variant) => (type_id, Some(variant)),
+            Slot::Type(VmType::Object(type_id)) => (type_id, None),
+            _ => return Slot::Any,
+        };
+        let Some(object) = self.object(type_id) else {
+            self.error(VerifyError::UnknownField { type_id, field });
+            return Slot::Any;
+        };
+        let mut members = object.variants.iter()
+            .enumerate()
+            .filter(|(id, _)| variant.is_none_or(|variant| variant as usize == *id))
+            .filter_map(|(_, variant)| variant.members.get(field as usize))
+            .map(|(_, type_tag)| self.slot(type_tag));
+        let Some(first) = members.next() else {
+            self.error(VerifyError::UnknownField { type_id, field });
+            return Slot::Any;
+        };
+        members.fold(first, Slot::merge)
+    }
+
+    fn arguments(&mut self, state: &State, arguments: &[CallArgument]) {
+        for argument in arguments {
+            self.read(state, argument.register);
+        }
+    }
+
+    fn read(&mut self, state: &State, register: Register) -> Slot {
+        match state.registers.get(register as usize) {
+            Some(slot) => *slot,
+            None => {
+                self.error(VerifyError::RegisterOutOfRange { register, len: state.registers.len() });
+                Slot::Any
+            }
+        }
+    }
+
+    fn write(&mut self, state: &mut State, register: Register, value: Slot) {
+        let len = state.registers.len();
+        match state.registers.get_mut(register as usize) {
+            Some(slot) => *slot = value,
+            None => self.error(VerifyError::RegisterOutOfRange { register, len }),
+        }
+    }
+
+    /// Reads a register, checking its type when it is known.
+    fn expect(&mut self, state: &State, instruction: Instruction, register: Register, accepts: impl Fn(VmType) -> bool) -> Slot {
+        let value = self.read(state, register);
+        match value.ty() {
+            Some(found) if !accepts(found) => {
+                self.error(VerifyError::TypeMismatch { instruction, register, expected: None, found });
+                Slot::Any
+            }
+            _ => value,
+        }
+    }
+
+    fn expect_type(&mut self, state: &State, instruction: Instruction, register: Register, expected: VmType) -> Slot {
+        let value = self.read(state, register);
+        match value.ty() {
+            Some(found) if found != expected => {
+                self.error(VerifyError::TypeMismatch { instruction, register, expected: Some(expected), found });
+                Slot::Any
+            }
+            _ => value,
+        }
+    }
+
+    fn global(&mut self, global: Id) -> Slot {
+        match self.file.globals.get(global as usize) {
+            Some(global) => self.slot(&global.type_tag),
+            None => {
+                self.error(VerifyError::UnknownGlobal(global));
+                Slot::Any
+            }
+        }
+    }
+
+    /// The parameters and return type of the function `function` calls,
+    /// which is either one of the file's functions or a function import.
+    fn callee(&mut self, function: Id) -> Option<Callee<'a>> {
+        let file = self.file;
+        let callee = match file.functions.get(function as usize) {
+            Some(callee) => Some(Callee { parameters: &callee.parameters, return_type: &callee.return_type }),
+            None => file.get_function_import(function).and_then(|import| match &import.signature {
+                MaruSignature::Function { parameters, return_type } => Some(Callee { parameters, return_type }),
+                _ => None,
+            }),
+        };
+        if callee.is_none() {
+            self.error(VerifyError::UnknownFunction(function));
+        }
+        callee
+    }
+
+    fn object(&self, type_id: TypeSymbol) -> Option<&'a MaruObject> {
+        let file = self.file;
+        let index = type_id.checked_sub(FIRST_USER_TYPE)?;
+        file.objects.get(index as usize)
+    }
+
+    fn slot(&self, type_tag: &MaruTypeTag) -> Slot {
+        let ty = match type_tag {
+            MaruTypeTag::Unit => VmType::Unit,
+            MaruTypeTag::Bool => VmType::Bool,
+            MaruTypeTag::U8 => VmType::U8,
+            MaruTypeTag::I8 => VmType::I8,
+            MaruTypeTag::U16 => VmType::U16,
+            MaruTypeTag::I16 => VmType::I16,
+            MaruTypeTag::U32 => VmType::U32,
+            MaruTypeTag::I32 => VmType::I32,
+            MaruTypeTag::U64 => VmType::U64,
+            MaruTypeTag::I64 => VmType::I64,
+            MaruTypeTag::F32 => VmType::F32,
+            MaruTypeTag::F64 => VmType::F64,
+            MaruTypeTag::Object(name) => {
+                let index = self.file.objects.iter().position(|object| object.type_name == *name);
+                match index {
+                    Some(index) => VmType::Object(FIRST_USER_TYPE + index as TypeSymbol),
+                    None => return Slot::Any,
+                }
+            }
+            MaruTypeTag::Array(_) => VmType::Array,
+            MaruTypeTag::String => VmType::String,
+        };
+        Slot::Type(ty)
+    }
+
+    fn error(&mut self, error: VerifyError) {
+        let diagnostic = Diagnostic { function: self.function, offset: self.offset, error };
+        if self.report && !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+}
@@ -1,18 +1,22 @@
 use std::ptr::NonNull;
 
+use bytecode::NumericType;
 use refcounter::RefCounter;
 
-mod tables;
-mod linker;
-mod allocator;
+pub mod tables;
+pub mod linker;
+pub mod allocator;
+pub mod interpreter;
 
 pub type StringSymbol = u32;
 pub type TypeSymbol = u32;
 pub type VariantId = u32;
+pub type EffectId = u32;
 pub type FunctionSymbol = u32;
 pub type FunctionPtr = extern "C" fn ();
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VmType {
     Unit,
     Bool,
@@ -26,7 +30,45 @@ pub enum VmType {
     I64,
     F32,
     F64,
-    Object(TypeSymbol)
+    Object(TypeSymbol),
+    /// An array of any element type; the element type is kept by the array.
+    Array,
+    String,
+}
+
+impl VmType {
+    /// The number of bytes a value of this type occupies inside of an object.
+    pub fn size(&self) -> usize {
+        match self {
+            VmType::Unit => 0,
+            VmType::Bool | VmType::U8 | VmType::I8 => 1,
+            VmType::U16 | VmType::I16 => 2,
+            VmType::U32 | VmType::I32 | VmType::F32 => 4,
+            VmType::U64 | VmType::I64 | VmType::F64 => 8,
+            VmType::Object(_) | VmType::Array | VmType::String => size_of::<usize>(),
+        }
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self, VmType::Object(_) | VmType::Array | VmType::String)
+    }
+}
+
+impl From<NumericType> for VmType {
+    fn from(ty: NumericType) -> Self {
+        match ty {
+            NumericType::U8 => VmType::U8,
+            NumericType::I8 => VmType::I8,
+            NumericType::U16 => VmType::U16,
+            NumericType::I16 => VmType::I16,
+            NumericType::U32 => VmType::U32,
+            NumericType::I32 => VmType::I32,
+            NumericType::U64 => VmType::U64,
+            NumericType::I64 => VmType::I64,
+            NumericType::F32 => VmType::F32,
+            NumericType::F64 => VmType::F64,
+        }
+    }
 }
 
 #[repr(C)]
@@ -42,34 +84,63 @@ pub struct StackFrameCore {
     pub prev: Option<NonNull<StackFrame>>,
     pub next: Option<NonNull<StackFrame>>,
     pub return_slot: u64,
+    pub return_type: VmType,
     pub closure_slot: u64,
+    /// The function this frame is executing.
+    pub function: FunctionSymbol,
+    /// The offset of the next instruction to execute in `function`.
+    pub pc: usize,
     pub variables_len: usize,
     pub variables: *mut u64,
     pub variables_type: *mut VmType,
+    /// The handler closure installed on this frame by `Handle`, or 0.
+    pub handler_slot: u64,
+    /// The effect `handler_slot` handles.
+    pub effect: EffectId,
+    /// Whether this frame is the top of a captured continuation that has
+    /// not been resumed or dropped yet.
+    pub suspended: bool,
 }
 
 impl StackFrameCore {
     pub fn new(variables_len: usize) -> StackFrameCore {
         use std::alloc::*;
-        let layout = Layout::array::<u64>(variables_len).unwrap();
-        let variables = unsafe { alloc(layout) as *mut u64 };
-        if variables.is_null() {
-            handle_alloc_error(layout);
-        }
-        let layout = Layout::array::<VmType>(variables_len).unwrap();
-        let variables_type = unsafe { alloc(layout)  as *mut VmType };
-        if variables_type.is_null() {
-            handle_alloc_error(layout);
+        let (variables, variables_type) = if variables_len == 0 {
+            (NonNull::dangling().as_ptr(), NonNull::dangling().as_ptr())
+        } else {
+            let layout = Layout::array::<u64>(variables_len).unwrap();
+            let variables = unsafe { alloc(layout) as *mut u64 };
+            if variables.is_null() {
+                handle_alloc_error(layout);
+            }
+            let layout = Layout::array::<VmType>(variables_len).unwrap();
+            let variables_type = unsafe { alloc(layout)  as *mut VmType };
+            if variables_type.is_null() {
+                handle_alloc_error(layout);
+            }
+            (variables, variables_type)
+        };
+        for i in 0..variables_len {
+            unsafe {
+                variables.add(i).write(0);
+                variables_type.add(i).write(VmType::Unit);
+            }
         }
 
-        StackFrameCore { 
-            prev: None, 
-            next: None, 
-            return_slot: 0, 
-            closure_slot: 0, 
-            variables_len, 
-            variables, 
-            variables_type
+        StackFrameCore {
+            prev: None,
+            next: None,
+            return_slot: 0,
+            return_type: VmType::Unit,
+            closure_slot: 0,
+            function: 0,
+            pc: 0,
+            variables_len,
+            variables,
+            variables_type,
+            handler_slot: 0,
+            effect: 0,
+            suspended: false,
         }
     }
 
@@ -77,12 +148,15 @@ impl StackFrameCore {
         use std::alloc::{Layout, dealloc};
         let variables_len = self.variables_len;
 
-        let layout = Layout::array::<u64>(variables_len).unwrap();
-        unsafe { dealloc( self.variables as *mut u8, layout) };
-        let layout = Layout::array::<VmType>(variables_len).unwrap();
-        unsafe { dealloc(self.variables_type as *mut u8, layout) };
-        self.variables = std::ptr::null_mut();
+        if variables_len != 0 {
+            let layout = Layout::array::<u64>(variables_len).unwrap();
+            unsafe { dealloc( self.variables as *mut u8, layout) };
+            let layout = Layout::array::<VmType>(variables_len).unwrap();
+            unsafe { dealloc(self.variables_type as *mut u8, layout) };
+        }
+        self.variables_len = 0;
         self.variables = std::ptr::null_mut();
+        self.variables_type = std::ptr::null_mut();
     }
 }
 
@@ -90,4 +164,41 @@ impl StackFrameCore {
 pub struct StackFrame {
     metadata: Metadata,
     core: StackFrameCore,
-}
\ No newline at end of file
+}
+
+/// A function value.
+///
+/// Closures are created by `CreateClosure` and `CreateFnObject` and hold
+/// the values they captured, which are passed to the function ahead of
+/// its arguments when the closure is invoked.
+#[repr(C)]
+pub struct Closure {
+    metadata: Metadata,
+    pub function: FunctionSymbol,
+    pub captures_len: usize,
+    pub captures: *mut u64,
+    pub captures_type: *mut VmType,
+}
+
+/// A fixed-length array.
+///
+/// Arrays are created by `CreateArray` and `SliceArray`. Every element
+/// has the type `element`.
+#[repr(C)]
+pub struct Array {
+    metadata: Metadata,
+    pub element: VmType,
+    pub len: usize,
+    pub elements: *mut u64,
+}
+
+/// An immutable UTF-8 string.
+///
+/// The length in bytes is stored ahead of a pointer to the bytes, which
+/// are not NUL-terminated.
+#[repr(C)]
+pub struct VmString {
+    metadata: Metadata,
+    pub len: usize,
+    pub bytes: *mut u8,
+}
@@ -1,8 +1,8 @@
-use std::{collections::VecDeque, sync::{Mutex, OnceLock}, time::SystemTime};
+use std::{collections::VecDeque, sync::{Mutex, OnceLock}};
 
 use refcounter::RefCounter;
 
-use crate::vm::{Metadata, StackFrame, StackFrameCore, TypeSymbol, VariantId, allocator, tables::ObjectDescTable};
+use crate::vm::{Metadata, StackFrame, StackFrameCore, TypeSymbol, VariantId, tables::{ObjectDescTable, STACK_FRAME_TYPE}};
 
 #[derive(Debug)]
 struct AllocationGroup {
@@ -40,6 +40,9 @@ pub struct Allocator {
 }
 
 
+// The allocator hands out raw object pointers and takes them back,
+// so the pointers it is given are trusted to come from it.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 impl Allocator {
     pub fn new(max_type_symbol: TypeSymbol) -> Self {
         Allocator { memory_pool: vec![AllocationGroup::new(); max_type_symbol as usize] }
@@ -68,7 +71,7 @@ impl Allocator {
             }
         }
 
-        output as *mut T
+        output
     }
 
     pub fn reuse_memory<T>(&mut self, memory: *mut T) {
@@ -91,10 +94,10 @@ impl Allocator {
         desc_table: &ObjectDescTable, 
         variable_size: usize
     ) -> *mut StackFrame {
-        let frame = self.allocate(0, 0, desc_table) as *mut StackFrame;
+        let frame = self.allocate::<StackFrame>(STACK_FRAME_TYPE, 0, desc_table);
         {
             let frame = unsafe { &mut *frame };
-            frame.metadata = Metadata { refcount: RefCounter::new(), type_id: 0, variant_id: 0 };
+            frame.metadata = Metadata { refcount: RefCounter::new(), type_id: STACK_FRAME_TYPE, variant_id: 0 };
             frame.core = StackFrameCore::new(variable_size);
         }
 
@@ -0,0 +1,1464 @@
+use std::{collections::{HashMap, hash_map::Entry}, fmt, ptr::NonNull};
+
+use bytecode::{
+    CallArgument, DecodeError, DecodedInstruction, Encoding, Id, Instruction, Instructions, JumpBranch, NumericType,
+    Register,
+};
+
+use crate::vm::{
+    Array, Closure, FunctionSymbol, Metadata, StackFrame, TypeSymbol, VmString, VmType,
+    allocator::Allocator,
+    tables::{
+        ARRAY_TYPE, CLOSURE_TYPE, FIRST_USER_TYPE, FunctionTable, GetFunctionResult, ObjectDescTable, STACK_FRAME_TYPE,
+        STRING_TYPE, StringTable,
+    },
+};
+
+/// A value held in a register, a global or a return slot.
+///
+/// Integers are stored zero-extended from their width, floats as their bits
+/// and objects as a pointer to their `Metadata`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Value {
+    pub bits: u64,
+    pub ty: VmType,
+}
+
+impl Value {
+    pub const UNIT: Value = Value { bits: 0, ty: VmType::Unit };
+
+    pub fn new(bits: u64, ty: VmType) -> Self {
+        Value { bits: truncate(bits, ty), ty }
+    }
+
+    pub fn bool(value: bool) -> Self {
+        Value { bits: value as u64, ty: VmType::Bool }
+    }
+
+    pub fn f32(value: f32) -> Self {
+        Value { bits: value.to_bits() as u64, ty: VmType::F32 }
+    }
+
+    pub fn f64(value: f64) -> Self {
+        Value { bits: value.to_bits(), ty: VmType::F64 }
+    }
+
+    /// Reads the value as a signed integer, sign-extending from its width.
+    pub fn as_i64(&self) -> i64 {
+        sign_extend(self.bits, self.ty)
+    }
+
+    pub fn as_f32(&self) -> f32 {
+        f32::from_bits(self.bits as u32)
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        f64::from_bits(self.bits)
+    }
+
+    fn object(&self) -> Option<NonNull<Metadata>> {
+        match self.ty {
+            VmType::Object(_) | VmType::Array | VmType::String => NonNull::new(self.bits as *mut Metadata),
+            _ => None,
+        }
+    }
+}
+
+/// An error raised while executing bytecode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trap {
+    UnknownFunction(FunctionSymbol),
+    /// Native functions can't be called from the interpreter yet.
+    NativeFunction(FunctionSymbol),
+    ArgumentCount { function: FunctionSymbol, expected: usize, found: usize },
+    RegisterOutOfRange { register: Register, len: usize },
+    UnknownGlobal(Id),
+    UnknownType(TypeSymbol),
+    UnknownVariant { type_id: TypeSymbol, variant: Id },
+    UnknownField { type_id: TypeSymbol, field: Id },
+    UnknownBlock(Id),
+    BranchOutOfRange { block_id: Id, offset: i32 },
+    /// The function body is not valid bytecode.
+    Decode(DecodeError),
+    TypeMismatch { instruction: Instruction, found: VmType },
+    NullObject { instruction: Instruction },
+    UnmatchedVariant(Id),
+    DivisionByZero,
+    /// A checked integer instruction produced a result outside of its type.
+    IntegerOverflow { instruction: Instruction },
+    /// The source type can't be converted to `to` by the instruction, such as
+    /// a `SignExtend` to a narrower type.
+    InvalidConversion { instruction: Instruction, from: VmType, to: VmType },
+    /// A trapping float to integer conversion was given NaN or a value
+    /// outside of the range of `to`.
+    ConversionOutOfRange { to: VmType },
+    /// Execution reached the end of a function without returning.
+    MissingReturn(FunctionSymbol),
+    /// No frame on the stack handles the performed effect.
+    UnhandledEffect(Id),
+    /// The continuation was already resumed or dropped.
+    ContinuationResumed,
+    /// An array index, or the end of a range of elements, is past `len`.
+    /// A range that starts past its end reports its end as `len`.
+    IndexOutOfBounds { index: u64, len: usize },
+    /// The memory for an array of `len` elements couldn't be allocated.
+    ArrayTooLarge { len: u64 },
+    UnknownString(Id),
+    /// A string index falls inside of a multi-byte char.
+    NotCharBoundary { index: u64 },
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trap::UnknownFunction(function) => write!(f, "unknown function {}", function),
+            Trap::NativeFunction(function) => write!(f, "cannot interpret native function {}", function),
+            Trap::ArgumentCount { function, expected, found } => {
+                write!(f, "function {} expects {} arguments but got {}", function, expected, found)
+            }
+            Trap::RegisterOutOfRange { register, len } => {
+                write!(f, "register {} is out of range for a frame of {} registers", register, len)
+            }
+            Trap::UnknownGlobal(global) => write!(f, "unknown global {}", global),
+            Trap::UnknownType(type_id) => write!(f, "unknown type {}", type_id),
+            Trap::UnknownVariant { type_id, variant } => {
+                write!(f, "type {} has no variant {}", type_id, variant)
+            }
+            Trap::UnknownField { type_id, field } => write!(f, "type {} has no field {}", type_id, field),
+            Trap::UnknownBlock(block_id) => write!(f, "unknown block {}", block_id),
+            Trap::BranchOutOfRange { block_id, offset } => {
+                write!(f, "branch to block {} offset {} is outside of the function", block_id, offset)
+            }
+            Trap::Decode(error) => write!(f, "{}", error),
+            Trap::TypeMismatch { instruction, found } => {
+                write!(f, "{:?} cannot operate on a value of type {:?}", instruction, found)
+            }
+            Trap::NullObject { instruction } => write!(f, "{:?} was given a null object", instruction),
+            Trap::UnmatchedVariant(variant) => write!(f, "no match case for variant {}", variant),
+            Trap::DivisionByZero => write!(f, "division by zero"),
+            Trap::IntegerOverflow { instruction } => write!(f, "{:?} overflowed", instruction),
+            Trap::InvalidConversion { instruction, from, to } => {
+                write!(f, "{:?} cannot convert {:?} to {:?}", instruction, from, to)
+            }
+            Trap::ConversionOutOfRange { to } => write!(f, "float is NaN or out of range for {:?}", to),
+            Trap::MissingReturn(function) => write!(f, "function {} ended without returning", function),
+            Trap::UnhandledEffect(effect) => write!(f, "effect {} was performed without a handler", effect),
+            Trap::ContinuationResumed => write!(f, "continuation was already resumed or dropped"),
+            Trap::IndexOutOfBounds { index, len } => {
+                write!(f, "index {} is out of bounds for an array of {} elements", index, len)
+            }
+            Trap::ArrayTooLarge { len } => write!(f, "cannot allocate an array of {} elements", len),
+            Trap::UnknownString(string) => write!(f, "unknown string {}", string),
+            Trap::NotCharBoundary { index } => write!(f, "string index {} is not on a char boundary", index),
+        }
+    }
+}
+
+impl std::error::Error for Trap {}
+
+impl From<DecodeError> for Trap {
+    fn from(error: DecodeError) -> Self {
+        Trap::Decode(error)
+    }
+}
+
+/// Executes bytecode functions.
+///
+/// Each call gets its own `StackFrame` from the allocator; frames are linked
+/// through `prev`/`next` and remember where execution resumes in `pc`.
+pub struct Interpreter<'a> {
+    functions: &'a FunctionTable,
+    objects: &'a ObjectDescTable,
+    strings: &'a StringTable,
+    allocator: Allocator,
+    globals: Vec<Value>,
+    /// The offset just past each `StartBlock`, per function and block id.
+    blocks: HashMap<FunctionSymbol, HashMap<Id, usize>>,
+    frame: Option<NonNull<StackFrame>>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(
+        functions: &'a FunctionTable,
+        objects: &'a ObjectDescTable,
+        strings: &'a StringTable,
+        global_count: usize,
+    ) -> Self {
+        Interpreter {
+            functions,
+            objects,
+            strings,
+            allocator: Allocator::new(objects.len() as TypeSymbol),
+            globals: vec![Value::UNIT; global_count],
+            blocks: HashMap::new(),
+            frame: None,
+        }
+    }
+
+    pub fn global(&self, global: Id) -> Option<Value> {
+        self.globals.get(global as usize).copied()
+    }
+
+    /// Runs `function` to completion and returns its result.
+    pub fn run(&mut self, function: FunctionSymbol, arguments: &[Value]) -> Result<Value, Trap> {
+        let caller = self.frame.take();
+        let result = self.enter(function, None, arguments.to_vec(), false)
+            .and_then(|_| self.execute());
+        if result.is_err() {
+            self.unwind();
+        }
+        self.frame = caller;
+        result
+    }
+
+    /// Reads an object field without touching reference counts.
+    ///
+    /// This is meant for hosts inspecting the objects returned by `run`.
+    pub fn field(&self, object: Value, field: Id) -> Result<Value, Trap> {
+        let (ptr, ty) = self.field_ptr(Instruction::CopyField, object, field)?;
+        Ok(unsafe { read_field(ptr, ty) })
+    }
+
+    /// Reads the elements of an array without touching reference counts.
+    pub fn elements(&self, array: Value) -> Result<Vec<Value>, Trap> {
+        let array = self.array(Instruction::GetElement, array)?;
+        Ok(unsafe { (0..(*array).len).map(|i| element(array, i)).collect() })
+    }
+
+    /// Reads the contents of a string.
+    pub fn str(&self, string: Value) -> Result<&str, Trap> {
+        let bytes = self.bytes(Instruction::StringLength, string)?;
+        Ok(unsafe { std::str::from_utf8_unchecked(bytes) })
+    }
+
+    fn execute(&mut self) -> Result<Value, Trap> {
+        loop {
+            let frame = self.current();
+            let function = unsafe { (*frame).core.function };
+            let (code, encoding) = self.code(function)?;
+            let pc = unsafe { (*frame).core.pc };
+            if pc >= code.len() {
+                return Err(Trap::MissingReturn(function));
+            }
+            let (decoded, len) = DecodedInstruction::decode_with(code, pc, encoding)?;
+            match self.step(decoded)? {
+                Flow::Next | Flow::Called => unsafe { (*frame).core.pc = pc + len },
+                Flow::Branch(branch) => {
+                    let target = self.branch_target(function, code, encoding, branch)?;
+                    unsafe { (*frame).core.pc = target };
+                }
+                Flow::TailCalled => {}
+                Flow::Returned(None) => {}
+                Flow::Returned(Some(value)) => return Ok(value),
+            }
+        }
+    }
+
+    fn step(&mut self, decoded: DecodedInstruction) -> Result<Flow, Trap> {
+        use DecodedInstruction as D;
+        let instruction = decoded.instruction();
+        match decoded {
+            D::Load8 { dst, value } => self.set(dst, Value::new(value as u64, VmType::U8))?,
+            D::Load16 { dst, value } => self.set(dst, Value::new(value as u64, VmType::U16))?,
+            D::Load32 { dst, value } => self.set(dst, Value::new(value as u64, VmType::U32))?,
+            D::Load64 { dst, value } => self.set(dst, Value::new(value, VmType::U64))?,
+            D::Loadf32 { dst, value } => self.set(dst, Value::f32(value))?,
+            D::Loadf64 { dst, value } => self.set(dst, Value::f64(value))?,
+            D::Copy { dst, src } => {
+                let value = self.get(src)?;
+                self.set(dst, value)?;
+            }
+            D::Clone { dst, src } => {
+                let value = self.get(src)?;
+                retain(value);
+                self.set(dst, value)?;
+            }
+            D::Move { dst, src } => {
+                let value = self.get(src)?;
+                self.set(src, Value::UNIT)?;
+                self.set(dst, value)?;
+            }
+            D::Clear { register } => self.set(register, Value::UNIT)?,
+            D::Destroy { register } => {
+                let value = self.get(register)?;
+                self.set(register, Value::UNIT)?;
+                self.release(value);
+            }
+            D::Forget { register } => {
+                let value = self.get(register)?;
+                let object = self.non_null(instruction, value)?;
+                self.set(register, Value::UNIT)?;
+                self.free_memory(object);
+            }
+            D::LoadReturn { dst } => {
+                let frame = self.current();
+                let value = unsafe {
+                    let core = &mut (*frame).core;
+                    let value = Value { bits: core.return_slot, ty: core.return_type };
+                    core.return_slot = 0;
+                    core.return_type = VmType::Unit;
+                    value
+                };
+                self.set(dst, value)?;
+            }
+            D::FetchRef { dst, src } => {
+                let value = self.get(src)?;
+                let object = self.non_null(instruction, value)?;
+                let count = unsafe { (*object.as_ptr()).refcount.fetch_value() };
+                self.set(dst, Value::new(count as u64, VmType::I64))?;
+            }
+            D::MakeShared { register } => {
+                let value = self.get(register)?;
+                let object = self.non_null(instruction, value)?;
+                unsafe { (*object.as_ptr()).refcount.make_shared() };
+            }
+            D::SetGlobal { global, src } => {
+                let value = self.get(src)?;
+                let slot = self.globals.get_mut(global as usize).ok_or(Trap::UnknownGlobal(global))?;
+                let old = std::mem::replace(slot, value);
+                self.set(src, Value::UNIT)?;
+                self.release(old);
+            }
+            D::CopyGlobal { dst, global } | D::CloneGlobal { dst, global } => {
+                let value = self.global(global).ok_or(Trap::UnknownGlobal(global))?;
+                if instruction == Instruction::CloneGlobal {
+                    retain(value);
+                }
+                self.set(dst, value)?;
+            }
+            D::Binary { instruction, dst, lhs, rhs } => {
+                let lhs = self.get(lhs)?;
+                let rhs = self.get(rhs)?;
+                self.set(dst, binary(instruction, lhs, rhs)?)?;
+            }
+            D::Unary { instruction: Instruction::IsNull, dst, src } => {
+                let src = self.get(src)?;
+                if !src.ty.is_object() {
+                    return Err(Trap::TypeMismatch { instruction, found: src.ty });
+                }
+                self.set(dst, Value::bool(src.bits == 0))?;
+            }
+            D::Unary { instruction, dst, src } => {
+                let src = self.get(src)?;
+                self.set(dst, unary(instruction, src)?)?;
+            }
+            D::Convert { instruction, dst, src, ty } => {
+                let src = self.get(src)?;
+                self.set(dst, convert(instruction, src, ty)?)?;
+            }
+            D::FmaF { dst, lhs, rhs, addend } => {
+                let (lhs, rhs, addend) = (self.get(lhs)?, self.get(rhs)?, self.get(addend)?);
+                if let Some(value) = [rhs, addend].into_iter().find(|value| value.ty != lhs.ty) {
+                    return Err(Trap::TypeMismatch { instruction, found: value.ty });
+                }
+                let value = match lhs.ty {
+                    VmType::F32 => Value::f32(lhs.as_f32().mul_add(rhs.as_f32(), addend.as_f32())),
+                    VmType::F64 => Value::f64(lhs.as_f64().mul_add(rhs.as_f64(), addend.as_f64())),
+                    found => return Err(Trap::TypeMismatch { instruction, found }),
+                };
+                self.set(dst, value)?;
+            }
+            D::CreateObject { dst, type_id, variant } => {
+                let object = self.create_object(type_id, variant)?;
+                self.set(dst, object)?;
+            }
+            D::GetField { dst, object, field }
+            | D::CopyField { dst, object, field }
+            | D::TakeField { dst, object, field } => {
+                let object = self.get(object)?;
+                let (ptr, ty) = self.field_ptr(instruction, object, field)?;
+                let value = unsafe { read_field(ptr, ty) };
+                match instruction {
+                    Instruction::GetField => retain(value),
+                    Instruction::TakeField => unsafe { write_field(ptr, Value { bits: 0, ty }) },
+                    _ => {}
+                }
+                self.set(dst, value)?;
+            }
+            D::SetField { object, field, src }
+            | D::MoveField { object, field, src }
+            | D::PlaceField { object, field, src } => {
+                let object = self.get(object)?;
+                let value = self.get(src)?;
+                let (ptr, ty) = self.field_ptr(instruction, object, field)?;
+                if value.ty != ty {
+                    return Err(Trap::TypeMismatch { instruction, found: value.ty });
+                }
+                let old = unsafe { read_field(ptr, ty) };
+                unsafe { write_field(ptr, value) };
+                match instruction {
+                    Instruction::SetField => {
+                        retain(value);
+                        self.release(old);
+                    }
+                    Instruction::MoveField => {
+                        self.set(src, Value::UNIT)?;
+                        self.release(old);
+                    }
+                    _ => self.set(src, Value::UNIT)?,
+                }
+            }
+            D::Call { function, arguments } => {
+                let arguments = self.arguments(arguments)?;
+                self.enter(function, None, arguments, false)?;
+                return Ok(Flow::Called);
+            }
+            D::CallTail { function, arguments } => {
+                let arguments = self.arguments(arguments)?;
+                self.enter(function, None, arguments, true)?;
+                return Ok(Flow::TailCalled);
+            }
+            D::Invoke { closure, arguments } => {
+                let closure = self.get(closure)?;
+                let arguments = self.arguments(arguments)?;
+                self.invoke(instruction, closure, arguments, false)?;
+                return Ok(Flow::Called);
+            }
+            D::InvokeTail { closure, arguments } => {
+                let closure = self.get(closure)?;
+                let arguments = self.arguments(arguments)?;
+                self.invoke(instruction, closure, arguments, true)?;
+                return Ok(Flow::TailCalled);
+            }
+            D::Return { src } => {
+                let value = self.get(src)?;
+                return Ok(Flow::Returned(self.leave(value)));
+            }
+            D::ReturnTail => {
+                let frame = self.current();
+                let value = unsafe { Value { bits: (*frame).core.return_slot, ty: (*frame).core.return_type } };
+                return Ok(Flow::Returned(self.leave(value)));
+            }
+            D::ReturnUnit | D::ReturnTailUnit => {
+                return Ok(Flow::Returned(self.leave(Value::UNIT)));
+            }
+            D::CreateClosure { dst, function, captures } => {
+                let captures = self.arguments(captures)?;
+                let closure = self.create_closure(function, captures)?;
+                self.set(dst, closure)?;
+            }
+            D::CreateFnObject { dst, function } => {
+                let closure = self.create_closure(function, Vec::new())?;
+                self.set(dst, closure)?;
+            }
+            D::Jump { branch } => return Ok(Flow::Branch(branch)),
+            D::If { condition, then_branch, else_branch } => {
+                let condition = self.get(condition)?;
+                if integer_width(condition.ty).is_none() {
+                    return Err(Trap::TypeMismatch { instruction, found: condition.ty });
+                }
+                return Ok(Flow::Branch(if condition.bits != 0 { then_branch } else { else_branch }));
+            }
+            D::Switch { src, default, cases } => {
+                let src = self.get(src)?;
+                if integer_width(src.ty).is_none() {
+                    return Err(Trap::TypeMismatch { instruction, found: src.ty });
+                }
+                let branch = cases.iter()
+                    .find(|case| truncate(case.value, src.ty) == src.bits)
+                    .map(|case| case.branch)
+                    .unwrap_or(default);
+                return Ok(Flow::Branch(branch));
+            }
+            D::Match { src, cases } => {
+                let src = self.get(src)?;
+                let object = self.non_null(instruction, src)?;
+                let variant = unsafe { (*object.as_ptr()).variant_id };
+                let case = cases.iter()
+                    .find(|case| case.tag == variant)
+                    .ok_or(Trap::UnmatchedVariant(variant))?;
+                return Ok(Flow::Branch(case.branch));
+            }
+            D::StartBlock { .. } => {}
+            D::Handle { effect, handler, body } => {
+                let handler = self.get(handler)?;
+                if handler.ty != VmType::Object(CLOSURE_TYPE) {
+                    return Err(Trap::TypeMismatch { instruction, found: handler.ty });
+                }
+                self.non_null(instruction, handler)?;
+                let body = self.get(body)?;
+                self.invoke(instruction, body, Vec::new(), false)?;
+                retain(handler);
+                let core = unsafe { &mut (*self.current()).core };
+                core.handler_slot = handler.bits;
+                core.effect = effect;
+                return Ok(Flow::Called);
+            }
+            D::Perform { effect, arguments } => {
+                let arguments = self.arguments(arguments)?;
+                let top = self.current();
+                let mut prompt = top;
+                while unsafe { (*prompt).core.handler_slot == 0 || (*prompt).core.effect != effect } {
+                    prompt = match unsafe { (*prompt).core.prev } {
+                        Some(prev) => prev.as_ptr(),
+                        None => return Err(Trap::UnhandledEffect(effect)),
+                    };
+                }
+                // Cut the frames from `top` down to the prompt off the stack;
+                // the continuation owns them until it is resumed or dropped.
+                let (handler, caller) = unsafe {
+                    let caller = (*prompt).core.prev.take();
+                    if let Some(caller) = caller {
+                        (*caller.as_ptr()).core.next = None;
+                    }
+                    (*top).core.suspended = true;
+                    (Value { bits: (*prompt).core.handler_slot, ty: VmType::Object(CLOSURE_TYPE) }, caller)
+                };
+                let mut values = vec![Value { bits: top as u64, ty: VmType::Object(STACK_FRAME_TYPE) }];
+                values.extend(arguments);
+                self.frame = caller;
+                if let Err(trap) = self.invoke(instruction, handler, values, false) {
+                    // Put the frames back so that unwinding frees them.
+                    unsafe {
+                        (*prompt).core.prev = caller;
+                        if let Some(caller) = caller {
+                            (*caller.as_ptr()).core.next = NonNull::new(prompt);
+                        }
+                        (*top).core.suspended = false;
+                    }
+                    self.frame = NonNull::new(top);
+                    return Err(trap);
+                }
+                return Ok(Flow::Called);
+            }
+            D::Resume { continuation, value } => {
+                let top = self.continuation(instruction, continuation)?;
+                if unsafe { !(*top).core.suspended } {
+                    return Err(Trap::ContinuationResumed);
+                }
+                let value = self.get(value)?;
+                self.set(continuation, Value::UNIT)?;
+                let current = self.current();
+                unsafe {
+                    let mut bottom = top;
+                    while let Some(prev) = (*bottom).core.prev {
+                        bottom = prev.as_ptr();
+                    }
+                    (*bottom).core.prev = NonNull::new(current);
+                    (*current).core.next = NonNull::new(bottom);
+                    let core = &mut (*top).core;
+                    core.suspended = false;
+                    core.return_slot = value.bits;
+                    core.return_type = value.ty;
+                }
+                self.frame = NonNull::new(top);
+                return Ok(Flow::Called);
+            }
+            D::DropContinuation { continuation } => {
+                let top = self.continuation(instruction, continuation)?;
+                self.set(continuation, Value::UNIT)?;
+                if unsafe { (*top).core.suspended } {
+                    self.discard(top);
+                }
+                self.release(Value { bits: top as u64, ty: VmType::Object(STACK_FRAME_TYPE) });
+            }
+            D::CreateArray { dst, len, fill } => {
+                let len = self.index(instruction, len)?;
+                let fill = self.get(fill)?;
+                let mut elements = Vec::new();
+                usize::try_from(len).ok()
+                    .and_then(|len| elements.try_reserve_exact(len).ok())
+                    .ok_or(Trap::ArrayTooLarge { len })?;
+                elements.extend((0..len).map(|_| {
+                    retain(fill);
+                    fill.bits
+                }));
+                let array = self.create_array(fill.ty, elements);
+                self.set(dst, array)?;
+            }
+            D::GetElement { dst, array, index } => {
+                let array = self.array(instruction, self.get(array)?)?;
+                let index = self.index(instruction, index)?;
+                let value = unsafe { element(array, bounds(index, (*array).len)?) };
+                retain(value);
+                self.set(dst, value)?;
+            }
+            D::SetElement { array, index, src } => {
+                let array = self.array(instruction, self.get(array)?)?;
+                let index = self.index(instruction, index)?;
+                let value = self.get(src)?;
+                let index = unsafe { bounds(index, (*array).len)? };
+                if value.ty != unsafe { (*array).element } {
+                    return Err(Trap::TypeMismatch { instruction, found: value.ty });
+                }
+                let old = unsafe { element(array, index) };
+                unsafe { *(*array).elements.add(index) = value.bits };
+                retain(value);
+                self.release(old);
+            }
+            D::ArrayLength { dst, array } => {
+                let array = self.array(instruction, self.get(array)?)?;
+                self.set(dst, Value::new(unsafe { (*array).len } as u64, VmType::U64))?;
+            }
+            D::SliceArray { dst, array, start, end } => {
+                let array = self.array(instruction, self.get(array)?)?;
+                let (start, end) = (self.index(instruction, start)?, self.index(instruction, end)?);
+                let (start, end) = unsafe { range(start, end, (*array).len)? };
+                let elements = (start..end)
+                    .map(|i| {
+                        let value = unsafe { element(array, i) };
+                        retain(value);
+                        value.bits
+                    })
+                    .collect();
+                let slice = self.create_array(unsafe { (*array).element }, elements);
+                self.set(dst, slice)?;
+            }
+            D::CopyElements { dst, dst_start, src, src_start, count } => {
+                let dst = self.array(instruction, self.get(dst)?)?;
+                let src = self.array(instruction, self.get(src)?)?;
+                let count = self.index(instruction, count)?;
+                let (dst_start, src_start) = (self.index(instruction, dst_start)?, self.index(instruction, src_start)?);
+                let (element_type, found) = unsafe { ((*dst).element, (*src).element) };
+                if element_type != found && count != 0 {
+                    return Err(Trap::TypeMismatch { instruction, found });
+                }
+                let end = |start: u64, len| start.checked_add(count).ok_or(Trap::IndexOutOfBounds { index: start, len });
+                let (dst_start, _) = unsafe { range(dst_start, end(dst_start, (*dst).len)?, (*dst).len)? };
+                let (src_start, src_end) = unsafe { range(src_start, end(src_start, (*src).len)?, (*src).len)? };
+                let values = (src_start..src_end)
+                    .map(|i| unsafe { element(src, i) })
+                    .collect::<Vec<_>>();
+                for (i, value) in values.into_iter().enumerate() {
+                    retain(value);
+                    let old = unsafe { element(dst, dst_start + i) };
+                    unsafe { *(*dst).elements.add(dst_start + i) = value.bits };
+                    self.release(old);
+                }
+            }
+            D::LoadString { dst, string } => {
+                let entry = self.strings.get(string).ok_or(Trap::UnknownString(string))?;
+                let string = self.create_string(entry.as_str().as_bytes().to_vec());
+                self.set(dst, string)?;
+            }
+            D::ConcatString { dst, lhs, rhs } => {
+                let bytes = [self.bytes(instruction, self.get(lhs)?)?, self.bytes(instruction, self.get(rhs)?)?].concat();
+                let string = self.create_string(bytes);
+                self.set(dst, string)?;
+            }
+            D::StringLength { dst, src } => {
+                let len = self.bytes(instruction, self.get(src)?)?.len();
+                self.set(dst, Value::new(len as u64, VmType::U64))?;
+            }
+            D::StringByte { dst, string, index } => {
+                let bytes = self.bytes(instruction, self.get(string)?)?;
+                let byte = bytes[bounds(self.index(instruction, index)?, bytes.len())?];
+                self.set(dst, Value::new(byte as u64, VmType::U8))?;
+            }
+            D::StringChar { dst, string, index } => {
+                let string = unsafe { std::str::from_utf8_unchecked(self.bytes(instruction, self.get(string)?)?) };
+                let index = self.index(instruction, index)?;
+                let start = char_boundary(string, bounds(index, string.len())?)?;
+                let char = string[start..].chars().next().expect("a char starts at every boundary before the end");
+                self.set(dst, Value::new(char as u64, VmType::U32))?;
+            }
+            D::SliceString { dst, string, start, end } => {
+                let string = unsafe { std::str::from_utf8_unchecked(self.bytes(instruction, self.get(string)?)?) };
+                let (start, end) = (self.index(instruction, start)?, self.index(instruction, end)?);
+                let (start, end) = range(start, end, string.len())?;
+                let bytes = string.as_bytes()[char_boundary(string, start)?..char_boundary(string, end)?].to_vec();
+                let slice = self.create_string(bytes);
+                self.set(dst, slice)?;
+            }
+            D::CompareString { dst, lhs, rhs } => {
+                let ordering = self.bytes(instruction, self.get(lhs)?)?.cmp(self.bytes(instruction, self.get(rhs)?)?);
+                self.set(dst, Value::new(ordering as i64 as u64, VmType::I32))?;
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn current(&self) -> *mut StackFrame {
+        self.frame.expect("no frame is executing").as_ptr()
+    }
+
+    fn code(&self, function: FunctionSymbol) -> Result<(&'static [u8], Encoding), Trap> {
+        let entry = self.functions.get(function).ok_or(Trap::UnknownFunction(function))?;
+        match entry.get_function() {
+            GetFunctionResult::Bytecode(code) => Ok((code, entry.encoding)),
+            GetFunctionResult::Ptr(_) => Err(Trap::NativeFunction(function)),
+        }
+    }
+
+    fn get(&self, register: Register) -> Result<Value, Trap> {
+        let core = unsafe { &(*self.current()).core };
+        let index = register as usize;
+        if index >= core.variables_len {
+            return Err(Trap::RegisterOutOfRange { register, len: core.variables_len });
+        }
+        unsafe {
+            Ok(Value {
+                bits: *core.variables.add(index),
+                ty: *core.variables_type.add(index),
+            })
+        }
+    }
+
+    fn set(&mut self, register: Register, value: Value) -> Result<(), Trap> {
+        let core = unsafe { &mut (*self.current()).core };
+        let index = register as usize;
+        if index >= core.variables_len {
+            return Err(Trap::RegisterOutOfRange { register, len: core.variables_len });
+        }
+        unsafe {
+            *core.variables.add(index) = value.bits;
+            *core.variables_type.add(index) = value.ty;
+        }
+        Ok(())
+    }
+
+    fn non_null(&self, instruction: Instruction, value: Value) -> Result<NonNull<Metadata>, Trap> {
+        if !value.ty.is_object() {
+            return Err(Trap::TypeMismatch { instruction, found: value.ty });
+        }
+        value.object().ok_or(Trap::NullObject { instruction })
+    }
+
+    /// Checks that a value is a non-null array.
+    fn array(&self, instruction: Instruction, value: Value) -> Result<*mut Array, Trap> {
+        if value.ty != VmType::Array {
+            return Err(Trap::TypeMismatch { instruction, found: value.ty });
+        }
+        Ok(self.non_null(instruction, value)?.as_ptr() as *mut Array)
+    }
+
+    /// Checks that a value is a non-null string and borrows its bytes.
+    fn bytes(&self, instruction: Instruction, value: Value) -> Result<&[u8], Trap> {
+        if value.ty != VmType::String {
+            return Err(Trap::TypeMismatch { instruction, found: value.ty });
+        }
+        let string = self.non_null(instruction, value)?.as_ptr() as *mut VmString;
+        Ok(unsafe { std::slice::from_raw_parts((*string).bytes, (*string).len) })
+    }
+
+    /// Reads a register holding an array index, length or count.
+    fn index(&self, instruction: Instruction, register: Register) -> Result<u64, Trap> {
+        let value = self.get(register)?;
+        if integer_width(value.ty).is_none() {
+            return Err(Trap::TypeMismatch { instruction, found: value.ty });
+        }
+        Ok(value.bits)
+    }
+
+    /// Reads a register holding a continuation.
+    fn continuation(&self, instruction: Instruction, register: Register) -> Result<*mut StackFrame, Trap> {
+        let value = self.get(register)?;
+        if value.ty != VmType::Object(STACK_FRAME_TYPE) {
+            return Err(Trap::TypeMismatch { instruction, found: value.ty });
+        }
+        Ok(self.non_null(instruction, value)?.as_ptr() as *mut StackFrame)
+    }
+
+    fn arguments(&self, arguments: Vec<CallArgument>) -> Result<Vec<Value>, Trap> {
+        arguments.into_iter()
+            .map(|argument| {
+                let value = self.get(argument.register)?;
+                if argument.increment_ref {
+                    retain(value);
+                }
+                Ok(value)
+            })
+            .collect()
+    }
+
+    fn branch_target(
+        &mut self,
+        function: FunctionSymbol,
+        code: &[u8],
+        encoding: Encoding,
+        branch: JumpBranch,
+    ) -> Result<usize, Trap> {
+        let blocks = match self.blocks.entry(function) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(scan_blocks(code, encoding)?),
+        };
+        let start = *blocks.get(&branch.block_id).ok_or(Trap::UnknownBlock(branch.block_id))?;
+        start.checked_add_signed(branch.offset as isize)
+            .filter(|target| *target <= code.len())
+            .ok_or(Trap::BranchOutOfRange { block_id: branch.block_id, offset: branch.offset })
+    }
+
+    /// Pushes a frame for `function`, or replaces the current one when `tail` is set.
+    fn enter(
+        &mut self,
+        function: FunctionSymbol,
+        closure: Option<NonNull<Metadata>>,
+        arguments: Vec<Value>,
+        tail: bool,
+    ) -> Result<(), Trap> {
+        self.code(function)?;
+        let entry = &self.functions[function];
+        if arguments.len() != entry.parameters.len() {
+            return Err(Trap::ArgumentCount { function, expected: entry.parameters.len(), found: arguments.len() });
+        }
+        entry.count_call();
+        let variables = (entry.variable_count as usize).max(arguments.len());
+        let frame = self.allocator.allocate_stack_frame(self.objects, variables);
+        unsafe {
+            let core = &mut (*frame).core;
+            core.function = function;
+            core.closure_slot = closure.map_or(0, |closure| closure.as_ptr() as u64);
+            for (i, argument) in arguments.into_iter().enumerate() {
+                *core.variables.add(i) = argument.bits;
+                *core.variables_type.add(i) = argument.ty;
+            }
+        }
+
+        let caller = if tail {
+            let current = self.current();
+            // A tail call from a handled body stays under the handler.
+            unsafe {
+                (*frame).core.handler_slot = std::mem::take(&mut (*current).core.handler_slot);
+                (*frame).core.effect = (*current).core.effect;
+            }
+            let prev = unsafe { (*current).core.prev };
+            self.free_frame(current);
+            prev
+        } else {
+            self.frame
+        };
+        unsafe {
+            (*frame).core.prev = caller;
+            if let Some(caller) = caller {
+                (*caller.as_ptr()).core.next = NonNull::new(frame);
+            }
+        }
+        self.frame = NonNull::new(frame);
+        Ok(())
+    }
+
+    fn invoke(&mut self, instruction: Instruction, closure: Value, arguments: Vec<Value>, tail: bool) -> Result<(), Trap> {
+        if closure.ty != VmType::Object(CLOSURE_TYPE) {
+            return Err(Trap::TypeMismatch { instruction, found: closure.ty });
+        }
+        let object = self.non_null(instruction, closure)?;
+        let closure = object.as_ptr() as *mut Closure;
+        let (function, mut values) = unsafe {
+            let captures = (0..(*closure).captures_len)
+                .map(|i| Value { bits: *(*closure).captures.add(i), ty: *(*closure).captures_type.add(i) })
+                .collect::<Vec<_>>();
+            ((*closure).function, captures)
+        };
+        for value in &values {
+            retain(*value);
+        }
+        values.extend(arguments);
+        retain(Value { bits: object.as_ptr() as u64, ty: VmType::Object(CLOSURE_TYPE) });
+        self.enter(function, Some(object), values, tail)
+    }
+
+    /// Pops the current frame, handing `value` to the caller.
+    ///
+    /// Returns `Some` once the outermost frame of this run has returned.
+    fn leave(&mut self, value: Value) -> Option<Value> {
+        let frame = self.current();
+        let caller = unsafe { (*frame).core.prev };
+        self.free_frame(frame);
+        self.frame = caller;
+        match caller {
+            Some(caller) => {
+                unsafe {
+                    let core = &mut (*caller.as_ptr()).core;
+                    core.next = None;
+                    core.return_slot = value.bits;
+                    core.return_type = value.ty;
+                }
+                None
+            }
+            None => Some(value),
+        }
+    }
+
+    /// Pops a frame off the stack.
+    ///
+    /// A frame captured by a continuation value that is still alive keeps its
+    /// memory until that value is released.
+    fn free_frame(&mut self, frame: *mut StackFrame) {
+        self.release_slots(frame);
+        if unsafe { (*frame).metadata.refcount.decrement() } == 0 {
+            self.allocator.reuse_stack_frame_memory(frame);
+        } else {
+            unsafe { (*frame).core.free_memory() };
+        }
+    }
+
+    fn release_slots(&mut self, frame: *mut StackFrame) {
+        let (closure, handler) = unsafe {
+            let core = &mut (*frame).core;
+            (std::mem::take(&mut core.closure_slot), std::mem::take(&mut core.handler_slot))
+        };
+        self.release(Value { bits: closure, ty: VmType::Object(CLOSURE_TYPE) });
+        self.release(Value { bits: handler, ty: VmType::Object(CLOSURE_TYPE) });
+    }
+
+    /// Frees the frames of a suspended continuation.
+    ///
+    /// The top frame's memory stays with the continuation value. Like
+    /// unwinding after a trap, this doesn't release the objects in the
+    /// frames' registers.
+    fn discard(&mut self, top: *mut StackFrame) {
+        let mut frame = unsafe {
+            (*top).core.suspended = false;
+            (*top).core.prev.take()
+        };
+        self.release_slots(top);
+        unsafe { (*top).core.free_memory() };
+        while let Some(current) = frame {
+            frame = unsafe { (*current.as_ptr()).core.prev };
+            self.free_frame(current.as_ptr());
+        }
+    }
+
+    /// Frees every frame left behind by a trap.
+    fn unwind(&mut self) {
+        while let Some(frame) = self.frame {
+            self.frame = unsafe { (*frame.as_ptr()).core.prev };
+            self.free_frame(frame.as_ptr());
+        }
+    }
+
+    fn create_object(&mut self, type_id: TypeSymbol, variant: Id) -> Result<Value, Trap> {
+        if type_id < FIRST_USER_TYPE || type_id as usize >= self.objects.len() {
+            return Err(Trap::UnknownType(type_id));
+        }
+        let description = &self.objects[type_id];
+        if variant as usize >= description.variants.len() {
+            return Err(Trap::UnknownVariant { type_id, variant });
+        }
+        let data_size = description.layout.size() - size_of::<Metadata>();
+        let object = self.allocator.allocate::<u8>(type_id, variant, self.objects);
+        unsafe { object.add(size_of::<Metadata>()).write_bytes(0, data_size) };
+        Ok(Value { bits: object as u64, ty: VmType::Object(type_id) })
+    }
+
+    fn create_closure(&mut self, function: FunctionSymbol, captures: Vec<Value>) -> Result<Value, Trap> {
+        if self.functions.get(function).is_none() {
+            return Err(Trap::UnknownFunction(function));
+        }
+        let closure = self.allocator.allocate::<Closure>(CLOSURE_TYPE, 0, self.objects);
+        let captures_len = captures.len();
+        let (bits, types): (Vec<u64>, Vec<VmType>) = captures.into_iter()
+            .map(|value| (value.bits, value.ty))
+            .unzip();
+        unsafe {
+            (*closure).function = function;
+            (*closure).captures_len = captures_len;
+            (*closure).captures = Box::into_raw(bits.into_boxed_slice()) as *mut u64;
+            (*closure).captures_type = Box::into_raw(types.into_boxed_slice()) as *mut VmType;
+        }
+        Ok(Value { bits: closure as u64, ty: VmType::Object(CLOSURE_TYPE) })
+    }
+
+    fn create_array(&mut self, element: VmType, elements: Vec<u64>) -> Value {
+        let array = self.allocator.allocate::<Array>(ARRAY_TYPE, 0, self.objects);
+        unsafe {
+            (*array).element = element;
+            (*array).len = elements.len();
+            (*array).elements = Box::into_raw(elements.into_boxed_slice()) as *mut u64;
+        }
+        Value { bits: array as u64, ty: VmType::Array }
+    }
+
+    /// Creates a string from bytes that are known to be UTF-8.
+    fn create_string(&mut self, bytes: Vec<u8>) -> Value {
+        let string = self.allocator.allocate::<VmString>(STRING_TYPE, 0, self.objects);
+        unsafe {
+            (*string).len = bytes.len();
+            (*string).bytes = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        }
+        Value { bits: string as u64, ty: VmType::String }
+    }
+
+    fn field_ptr(&self, instruction: Instruction, object: Value, field: Id) -> Result<(*mut u8, VmType), Trap> {
+        let ptr = self.non_null(instruction, object)?.as_ptr();
+        let (type_id, variant) = unsafe { ((*ptr).type_id, (*ptr).variant_id) };
+        let variant = self.objects[type_id].variants.get(variant as usize)
+            .ok_or(Trap::UnknownField { type_id, field })?;
+        let (offset, ty) = variant.packing_offsets.get(field as usize)
+            .zip(variant.member_types.get(field as usize))
+            .ok_or(Trap::UnknownField { type_id, field })?;
+        let ptr = unsafe { (ptr as *mut u8).add(size_of::<Metadata>() + offset) };
+        Ok((ptr, *ty))
+    }
+
+    /// Decrements the reference count of an object, freeing it at zero.
+    fn release(&mut self, value: Value) {
+        let Some(object) = value.object() else {
+            return;
+        };
+        if unsafe { (*object.as_ptr()).refcount.decrement() } != 0 {
+            return;
+        }
+        let (type_id, variant) = unsafe { ((*object.as_ptr()).type_id, (*object.as_ptr()).variant_id) };
+        if type_id == STACK_FRAME_TYPE {
+            let frame = object.as_ptr() as *mut StackFrame;
+            if unsafe { (*frame).core.suspended } {
+                self.discard(frame);
+            }
+        } else if type_id == CLOSURE_TYPE {
+            let closure = object.as_ptr() as *mut Closure;
+            for i in 0..unsafe { (*closure).captures_len } {
+                let value = unsafe { Value { bits: *(*closure).captures.add(i), ty: *(*closure).captures_type.add(i) } };
+                self.release(value);
+            }
+        } else if type_id == ARRAY_TYPE {
+            let array = object.as_ptr() as *mut Array;
+            for i in 0..unsafe { (*array).len } {
+                self.release(unsafe { element(array, i) });
+            }
+        } else {
+            let objects = self.objects;
+            let variant = &objects[type_id].variants[variant as usize];
+            for (offset, ty) in variant.packing_offsets.iter().zip(variant.member_types.iter()) {
+                if ty.is_object() {
+                    let field = unsafe { read_field((object.as_ptr() as *mut u8).add(size_of::<Metadata>() + offset), *ty) };
+                    self.release(field);
+                }
+            }
+        }
+        self.free_memory(object);
+    }
+
+    /// Hands an object's memory back to the allocator without releasing its fields.
+    fn free_memory(&mut self, object: NonNull<Metadata>) {
+        let type_id = unsafe { (*object.as_ptr()).type_id };
+        if type_id == STACK_FRAME_TYPE {
+            self.allocator.reuse_stack_frame_memory(object.as_ptr() as *mut StackFrame);
+            return;
+        }
+        if type_id == CLOSURE_TYPE {
+            let closure = object.as_ptr() as *mut Closure;
+            unsafe {
+                let len = (*closure).captures_len;
+                drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut((*closure).captures, len)));
+                drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut((*closure).captures_type, len)));
+            }
+        }
+        if type_id == ARRAY_TYPE {
+            let array = object.as_ptr() as *mut Array;
+            unsafe { drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut((*array).elements, (*array).len))) };
+        }
+        if type_id == STRING_TYPE {
+            let string = object.as_ptr() as *mut VmString;
+            unsafe { drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut((*string).bytes, (*string).len))) };
+        }
+        self.allocator.reuse_memory(object.as_ptr());
+    }
+}
+
+enum Flow {
+    Next,
+    Branch(JumpBranch),
+    Called,
+    TailCalled,
+    Returned(Option<Value>),
+}
+
+/// Finds where each block of a function starts.
+fn scan_blocks(code: &[u8], encoding: Encoding) -> Result<HashMap<Id, usize>, Trap> {
+    let mut blocks = HashMap::new();
+    let mut instructions = Instructions::with_encoding(code, encoding);
+    while let Some(instruction) = instructions.next() {
+        let (_, instruction) = instruction?;
+        if let DecodedInstruction::StartBlock { id } = instruction {
+            blocks.insert(id, instructions.offset());
+        }
+    }
+    Ok(blocks)
+}
+
+fn retain(value: Value) {
+    if let Some(object) = value.object() {
+        unsafe { (*object.as_ptr()).refcount.increment() };
+    }
+}
+
+/// Reads the element at `index` of an array.
+unsafe fn element(array: *mut Array, index: usize) -> Value {
+    unsafe { Value { bits: *(*array).elements.add(index), ty: (*array).element } }
+}
+
+/// Checks that `index` is below `len`.
+fn bounds(index: u64, len: usize) -> Result<usize, Trap> {
+    usize::try_from(index).ok()
+        .filter(|index| *index < len)
+        .ok_or(Trap::IndexOutOfBounds { index, len })
+}
+
+fn char_boundary(string: &str, index: usize) -> Result<usize, Trap> {
+    if !string.is_char_boundary(index) {
+        return Err(Trap::NotCharBoundary { index: index as u64 });
+    }
+    Ok(index)
+}
+
+/// Checks that `start..end` is a range of elements of an array of `len`.
+fn range(start: u64, end: u64, len: usize) -> Result<(usize, usize), Trap> {
+    if end > len as u64 {
+        return Err(Trap::IndexOutOfBounds { index: end, len });
+    }
+    if start > end {
+        return Err(Trap::IndexOutOfBounds { index: start, len: end as usize });
+    }
+    Ok((start as usize, end as usize))
+}
+
+unsafe fn read_field(ptr: *mut u8, ty: VmType) -> Value {
+    let bits = unsafe {
+        match ty.size() {
+            0 => 0,
+            1 => ptr.read() as u64,
+            2 => (ptr as *mut u16).read_unaligned() as u64,
+            4 => (ptr as *mut u32).read_unaligned() as u64,
+            _ => (ptr as *mut u64).read_unaligned(),
+        }
+    };
+    Value { bits, ty }
+}
+
+unsafe fn write_field(ptr: *mut u8, value: Value) {
+    unsafe {
+        match value.ty.size() {
+            0 => {}
+            1 => ptr.write(value.bits as u8),
+            2 => (ptr as *mut u16).write_unaligned(value.bits as u16),
+            4 => (ptr as *mut u32).write_unaligned(value.bits as u32),
+            _ => (ptr as *mut u64).write_unaligned(value.bits),
+        }
+    }
+}
+
+/// The width in bits of an integer type, treating `Bool` as a 1-bit integer.
+pub(crate) fn integer_width(ty: VmType) -> Option<u32> {
+    match ty {
+        VmType::Bool => Some(1),
+        VmType::U8 | VmType::I8 => Some(8),
+        VmType::U16 | VmType::I16 => Some(16),
+        VmType::U32 | VmType::I32 => Some(32),
+        VmType::U64 | VmType::I64 => Some(64),
+        _ => None,
+    }
+}
+
+fn truncate(bits: u64, ty: VmType) -> u64 {
+    match (ty, integer_width(ty)) {
+        (VmType::F32, _) => bits & u32::MAX as u64,
+        (_, Some(width)) if width < 64 => bits & ((1u64 << width) - 1),
+        _ => bits,
+    }
+}
+
+fn sign_extend(bits: u64, ty: VmType) -> i64 {
+    match integer_width(ty) {
+        Some(width) if width < 64 => {
+            let shift = 64 - width;
+            ((bits << shift) as i64) >> shift
+        }
+        _ => bits as i64,
+    }
+}
+
+pub(crate) fn unsigned(width: u32) -> VmType {
+    match width {
+        1 => VmType::Bool,
+        8 => VmType::U8,
+        16 => VmType::U16,
+        32 => VmType::U32,
+        _ => VmType::U64,
+    }
+}
+
+pub(crate) fn signed(width: u32) -> VmType {
+    match width {
+        1 => VmType::Bool,
+        8 => VmType::I8,
+        16 => VmType::I16,
+        32 => VmType::I32,
+        _ => VmType::I64,
+    }
+}
+
+fn binary(instruction: Instruction, lhs: Value, rhs: Value) -> Result<Value, Trap> {
+    use Instruction::*;
+    if matches!(
+        instruction,
+        AddF | SubF | MulF | DivF | RemF | MinF | MaxF | CopySignF | EqF | NeqF | LtF | GtF | LteF | GteF
+    ) {
+        return float_binary(instruction, lhs, rhs);
+    }
+    let width = integer_width(lhs.ty).ok_or(Trap::TypeMismatch { instruction, found: lhs.ty })?;
+    let shift = matches!(instruction, ShiftLeft | LogicalShiftRight | ArithmeticShiftRight | RotateLeft | RotateRight);
+    match integer_width(rhs.ty) {
+        Some(rhs_width) if shift || rhs_width == width => {}
+        _ => return Err(Trap::TypeMismatch { instruction, found: rhs.ty }),
+    }
+    if let Some(result) = exact(instruction, lhs, rhs, width) {
+        return result;
+    }
+    let (a, b) = (lhs.bits, rhs.bits);
+    let (sa, sb) = (lhs.as_i64(), rhs.as_i64());
+    let (bits, ty) = match instruction {
+        AddU => (a.wrapping_add(b), unsigned(width)),
+        SubU => (a.wrapping_sub(b), unsigned(width)),
+        MulU => (a.wrapping_mul(b), unsigned(width)),
+        DivU => (a.checked_div(b).ok_or(Trap::DivisionByZero)?, unsigned(width)),
+        RemU => (a.checked_rem(b).ok_or(Trap::DivisionByZero)?, unsigned(width)),
+        AddS => (sa.wrapping_add(sb) as u64, signed(width)),
+        SubS => (sa.wrapping_sub(sb) as u64, signed(width)),
+        MulS => (sa.wrapping_mul(sb) as u64, signed(width)),
+        DivS | RemS if sb == 0 => return Err(Trap::DivisionByZero),
+        DivS => (sa.wrapping_div(sb) as u64, signed(width)),
+        RemS => (sa.wrapping_rem(sb) as u64, signed(width)),
+        And => (a & b, lhs.ty),
+        Or => (a | b, lhs.ty),
+        Xor => (a ^ b, lhs.ty),
+        ShiftLeft => (a << (b % width as u64), lhs.ty),
+        LogicalShiftRight => (a >> (b % width as u64), lhs.ty),
+        ArithmeticShiftRight => ((sa >> (b % width as u64)) as u64, lhs.ty),
+        RotateLeft => (rotate_left(a, b % width as u64, width), lhs.ty),
+        RotateRight => (rotate_left(a, (width as u64 - b % width as u64) % width as u64, width), lhs.ty),
+        EqI => ((a == b) as u64, VmType::Bool),
+        NeqI => ((a != b) as u64, VmType::Bool),
+        LtU => ((a < b) as u64, VmType::Bool),
+        GtU => ((a > b) as u64, VmType::Bool),
+        LteU => ((a <= b) as u64, VmType::Bool),
+        GteU => ((a >= b) as u64, VmType::Bool),
+        LtS => ((sa < sb) as u64, VmType::Bool),
+        GtS => ((sa > sb) as u64, VmType::Bool),
+        LteS => ((sa <= sb) as u64, VmType::Bool),
+        GteS => ((sa >= sb) as u64, VmType::Bool),
+        _ => unreachable!("{:?} is not a binary integer instruction", instruction),
+    };
+    Ok(Value::new(bits, ty))
+}
+
+/// Runs the checked and saturating instructions, which compute the exact
+/// result before fitting it into the type.
+fn exact(instruction: Instruction, lhs: Value, rhs: Value, width: u32) -> Option<Result<Value, Trap>> {
+    use Instruction::*;
+    let (signed_op, saturating) = match instruction {
+        CheckedAddU | CheckedSubU | CheckedMulU => (false, false),
+        CheckedAddS | CheckedSubS | CheckedMulS | CheckedDivS => (true, false),
+        SaturatingAddU | SaturatingSubU | SaturatingMulU => (false, true),
+        SaturatingAddS | SaturatingSubS | SaturatingMulS | SaturatingDivS => (true, true),
+        _ => return None,
+    };
+    let (a, b, min, max, ty) = if signed_op {
+        let max = (1i128 << (width - 1)) - 1;
+        (lhs.as_i64() as i128, rhs.as_i64() as i128, -max - 1, max, signed(width))
+    } else {
+        (lhs.bits as i128, rhs.bits as i128, 0, (1i128 << width) - 1, unsigned(width))
+    };
+    // Both operands fit in 64 bits, so sums, differences, quotients and
+    // signed products fit in an `i128`. An unsigned product can overflow it,
+    // but is then past `max` anyway, so it is pinned to `i128::MAX`.
+    let result = match instruction {
+        CheckedAddU | CheckedAddS | SaturatingAddU | SaturatingAddS => a + b,
+        CheckedSubU | CheckedSubS | SaturatingSubU | SaturatingSubS => a - b,
+        CheckedMulU | CheckedMulS | SaturatingMulU | SaturatingMulS => a.checked_mul(b).unwrap_or(i128::MAX),
+        _ if b == 0 => return Some(Err(Trap::DivisionByZero)),
+        _ => a / b,
+    };
+    let result = match (result < min || result > max, saturating) {
+        (false, _) => result,
+        (true, true) => result.clamp(min, max),
+        (true, false) => return Some(Err(Trap::IntegerOverflow { instruction })),
+    };
+    Some(Ok(Value::new(result as u64, ty)))
+}
+
+/// Rotates the low `width` bits of `bits` left by `amount`, which is less than `width`.
+fn rotate_left(bits: u64, amount: u64, width: u32) -> u64 {
+    if amount == 0 {
+        return bits;
+    }
+    (bits << amount) | (bits >> (width as u64 - amount))
+}
+
+fn float_binary(instruction: Instruction, lhs: Value, rhs: Value) -> Result<Value, Trap> {
+    use Instruction::*;
+    if lhs.ty != rhs.ty {
+        return Err(Trap::TypeMismatch { instruction, found: rhs.ty });
+    }
+    macro_rules! apply {
+        ($a:expr, $b:expr, $make:path) => {{
+            let (a, b) = ($a, $b);
+            match instruction {
+                AddF => $make(a + b),
+                SubF => $make(a - b),
+                MulF => $make(a * b),
+                DivF => $make(a / b),
+                RemF => $make(a % b),
+                MinF => $make(minimum(a as f64, b as f64) as _),
+                MaxF => $make(-minimum(-a as f64, -b as f64) as _),
+                CopySignF => $make(a.copysign(b)),
+                EqF => Value::bool(a == b),
+                NeqF => Value::bool(a != b),
+                LtF => Value::bool(a < b),
+                GtF => Value::bool(a > b),
+                LteF => Value::bool(a <= b),
+                GteF => Value::bool(a >= b),
+                _ => unreachable!("{:?} is not a binary float instruction", instruction),
+            }
+        }};
+    }
+    match lhs.ty {
+        VmType::F32 => Ok(apply!(lhs.as_f32(), rhs.as_f32(), Value::f32)),
+        VmType::F64 => Ok(apply!(lhs.as_f64(), rhs.as_f64(), Value::f64)),
+        found => Err(Trap::TypeMismatch { instruction, found }),
+    }
+}
+
+/// The IEEE 754 `minimum` of two floats, which is NaN when either is NaN
+/// and orders -0.0 below 0.0.
+///
+/// `F32`s go through `f64` exactly, since the result is one of the operands.
+fn minimum(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        a + b
+    } else if a == b {
+        if a.is_sign_negative() { a } else { b }
+    } else {
+        a.min(b)
+    }
+}
+
+macro_rules! float_unary {
+    ($instruction:expr, $value:expr) => {{
+        let value = $value;
+        match $instruction {
+            Instruction::NegF => -value,
+            Instruction::AbsF => value.abs(),
+            Instruction::SqrtF => value.sqrt(),
+            Instruction::FloorF => value.floor(),
+            Instruction::CeilF => value.ceil(),
+            Instruction::TruncF => value.trunc(),
+            Instruction::RoundF => value.round_ties_even(),
+            instruction => unreachable!("{:?} is not a unary float instruction", instruction),
+        }
+    }};
+}
+
+fn unary(instruction: Instruction, src: Value) -> Result<Value, Trap> {
+    use Instruction::*;
+    let mismatch = Trap::TypeMismatch { instruction, found: src.ty };
+    match (instruction, src.ty) {
+        (Not, VmType::Bool) => Ok(Value::bool(src.bits == 0)),
+        (Not, ty) if integer_width(ty).is_some() => Ok(Value::new(!src.bits, ty)),
+        (ByteSwap, ty) => {
+            let bits = match integer_width(ty) {
+                Some(8) => src.bits,
+                Some(16) => (src.bits as u16).swap_bytes() as u64,
+                Some(32) => (src.bits as u32).swap_bytes() as u64,
+                Some(64) => src.bits.swap_bytes(),
+                _ => return Err(mismatch),
+            };
+            Ok(Value::new(bits, ty))
+        }
+        (PopCount | CountLeadingZeros | CountTrailingZeros | BitReverse, ty) => {
+            let width = match integer_width(ty) {
+                Some(width) if width >= 8 => width,
+                _ => return Err(mismatch),
+            };
+            // The bits above the width are zero, so only leading zeros and
+            // bit reversal need to account for them.
+            let bits = match instruction {
+                PopCount => src.bits.count_ones() as u64,
+                CountLeadingZeros => (src.bits.leading_zeros() - (64 - width)) as u64,
+                CountTrailingZeros => src.bits.trailing_zeros().min(width) as u64,
+                _ => src.bits.reverse_bits() >> (64 - width),
+            };
+            Ok(Value::new(bits, ty))
+        }
+        (NegF | AbsF | SqrtF | FloorF | CeilF | TruncF | RoundF, VmType::F32) => {
+            Ok(Value::f32(float_unary!(instruction, src.as_f32())))
+        }
+        (NegF | AbsF | SqrtF | FloorF | CeilF | TruncF | RoundF, VmType::F64) => {
+            Ok(Value::f64(float_unary!(instruction, src.as_f64())))
+        }
+        (IsNaN, VmType::F32) => Ok(Value::bool(src.as_f32().is_nan())),
+        (IsNaN, VmType::F64) => Ok(Value::bool(src.as_f64().is_nan())),
+        (IsInfinity, VmType::F32) => Ok(Value::bool(src.as_f32().is_infinite())),
+        (IsInfinity, VmType::F64) => Ok(Value::bool(src.as_f64().is_infinite())),
+        _ => Err(mismatch),
+    }
+}
+
+/// Whether `instruction` takes a source of type `from`, whatever the target.
+pub(crate) fn converts_from(instruction: Instruction, from: VmType) -> bool {
+    use Instruction::*;
+    match instruction {
+        SignExtend | ZeroExtend | Truncate | IntToFloatS | IntToFloatU => integer_width(from).is_some(),
+        _ => matches!(from, VmType::F32 | VmType::F64),
+    }
+}
+
+/// Whether `instruction` can convert a value of type `from` to `to`.
+pub(crate) fn converts(instruction: Instruction, from: VmType, to: NumericType) -> bool {
+    use Instruction::*;
+    let Some(width) = integer_width(from) else {
+        return converts_from(instruction, from) && (to.is_float() == (instruction == FloatToFloat));
+    };
+    match instruction {
+        SignExtend | ZeroExtend => !to.is_float() && to.width() >= width,
+        Truncate => !to.is_float() && to.width() <= width,
+        IntToFloatS | IntToFloatU => to.is_float(),
+        _ => false,
+    }
+}
+
+fn convert(instruction: Instruction, src: Value, to: NumericType) -> Result<Value, Trap> {
+    use Instruction::*;
+    let ty = VmType::from(to);
+    if !converts_from(instruction, src.ty) {
+        return Err(Trap::TypeMismatch { instruction, found: src.ty });
+    }
+    if !converts(instruction, src.ty, to) {
+        return Err(Trap::InvalidConversion { instruction, from: src.ty, to: ty });
+    }
+    let float = |value: f64| match to {
+        NumericType::F32 => Value::f32(value as f32),
+        _ => Value::f64(value),
+    };
+    let value = if src.ty == VmType::F32 { src.as_f32() as f64 } else { src.as_f64() };
+    Ok(match instruction {
+        SignExtend => Value::new(src.as_i64() as u64, ty),
+        ZeroExtend | Truncate => Value::new(src.bits, ty),
+        // Converting straight from the integer rounds once, where going
+        // through `f64` first could round twice.
+        IntToFloatS if to == NumericType::F32 => Value::f32(src.as_i64() as f32),
+        IntToFloatS => Value::f64(src.as_i64() as f64),
+        IntToFloatU if to == NumericType::F32 => Value::f32(src.bits as f32),
+        IntToFloatU => Value::f64(src.bits as f64),
+        FloatToIntSat => Value::new(saturate(value, to), ty),
+        FloatToInt => {
+            // The bounds are powers of two, so they are exact as `f64`s.
+            let (min, end) = match (to.is_signed(), to.width()) {
+                (true, width) => (-(2f64.powi(width as i32 - 1)), 2f64.powi(width as i32 - 1)),
+                (false, width) => (0.0, 2f64.powi(width as i32)),
+            };
+            let value = value.trunc();
+            if value.is_nan() || value < min || value >= end {
+                return Err(Trap::ConversionOutOfRange { to: ty });
+            }
+            Value::new(saturate(value, to), ty)
+        }
+        _ => float(value),
+    })
+}
+
+/// Converts a float to an integer type the way Rust's `as` does: rounding
+/// toward zero, saturating at the bounds and turning NaN into 0.
+fn saturate(value: f64, to: NumericType) -> u64 {
+    match to {
+        NumericType::U8 => value as u8 as u64,
+        NumericType::I8 => value as i8 as u64,
+        NumericType::U16 => value as u16 as u64,
+        NumericType::I16 => value as i16 as u64,
+        NumericType::U32 => value as u32 as u64,
+        NumericType::I32 => value as i32 as u64,
+        NumericType::U64 => value as u64,
+        NumericType::I64 => value as i64 as u64,
+        NumericType::F32 | NumericType::F64 => unreachable!("{:?} is not an integer type", to),
+    }
+}
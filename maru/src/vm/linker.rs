@@ -1,7 +1,279 @@
+//! Resolves the imports of a set of modules against each other's exports.
+//!
+//! Modules are found by their module name. Each dependency a module declares
+//! must be among the modules, at a version its requirement accepts. An
+//! import resolves when the module it names exports a symbol of that name
+//! whose kind and signature match the import. Object types in signatures are
+//! compared by name, since each module numbers its strings on its own.
 
+use std::fmt;
 
+use maru_file::{MaruFile, MaruImport, MaruSignature, MaruSymbolKind, MaruTypeTag, MaruVersion, MaruVersionReq, StringIndex};
 
 pub enum LinkerEntry<T, S> {
     Entry(T),
     Hole(S),
-}
\ No newline at end of file
+}
+
+/// The symbol an import resolved to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolved {
+    /// The index of the exporting module in the linker.
+    pub module: usize,
+    pub kind: MaruSymbolKind,
+    /// The index of the symbol in the exporting module's functions, objects or globals.
+    pub index: u32,
+}
+
+/// A dependency or an import that could not be resolved.
+///
+/// `module` is the dependent module and `dependency` the module it depends
+/// on or imports from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// No module is named `dependency`, which `module` depends on
+    MissingDependency { module: String, dependency: String, requirement: MaruVersionReq },
+    /// `dependency` is at a version `requirement` doesn't accept
+    IncompatibleDependency { module: String, dependency: String, requirement: MaruVersionReq, version: MaruVersion },
+    /// No module is named `dependency`
+    UnknownModule { module: String, dependency: String, symbol: String },
+    /// `dependency` doesn't export `symbol`
+    UnresolvedSymbol { module: String, dependency: String, symbol: String },
+    /// `symbol` is exported as a different kind of item than it is imported as
+    KindMismatch { module: String, dependency: String, symbol: String, expected: MaruSymbolKind, found: MaruSymbolKind },
+    /// `symbol` has a different signature than the import expects
+    SignatureMismatch { module: String, dependency: String, symbol: String, expected: String, found: String },
+    /// The export of `symbol` refers to an item `module` doesn't have
+    InvalidExport { module: String, symbol: String, kind: MaruSymbolKind, index: u32 },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::MissingDependency { module, dependency, requirement } => {
+                write!(f, "`{}` requires `{}` {}, but there is no module `{}`", module, dependency, requirement, dependency)
+            }
+            LinkError::IncompatibleDependency { module, dependency, requirement, version } => write!(
+                f,
+                "`{}` requires `{}` {}, but `{}` is version {}",
+                module, dependency, requirement, dependency, version,
+            ),
+            LinkError::UnknownModule { module, dependency, symbol } => {
+                write!(f, "`{}` imports `{}` from `{}`, but there is no module `{}`", module, symbol, dependency, dependency)
+            }
+            LinkError::UnresolvedSymbol { module, dependency, symbol } => {
+                write!(f, "`{}` imports `{}` from `{}`, which doesn't export it", module, symbol, dependency)
+            }
+            LinkError::KindMismatch { module, dependency, symbol, expected, found } => write!(
+                f,
+                "`{}` imports `{}` from `{}` as {}, but it is exported as {}",
+                module,
+                symbol,
+                dependency,
+                kind_name(*expected),
+                kind_name(*found),
+            ),
+            LinkError::SignatureMismatch { module, dependency, symbol, expected, found } => write!(
+                f,
+                "`{}` imports `{}` from `{}` as `{}`, but it is exported as `{}`",
+                module, symbol, dependency, expected, found,
+            ),
+            LinkError::InvalidExport { module, symbol, kind, index } => {
+                write!(f, "`{}` exports `{}` as {} {}, which it doesn't have", module, symbol, kind_name(*kind), index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// The modules being linked.
+#[derive(Default)]
+pub struct Linker<'a> {
+    modules: Vec<&'a MaruFile>,
+}
+
+impl<'a> Linker<'a> {
+    pub fn new() -> Self {
+        Linker { modules: Vec::new() }
+    }
+
+    /// Adds a module and returns its index.
+    pub fn add_module(&mut self, file: &'a MaruFile) -> usize {
+        self.modules.push(file);
+        self.modules.len() - 1
+    }
+
+    /// The index of the first module named `name`.
+    pub fn module(&self, name: &str) -> Option<usize> {
+        self.modules.iter().position(|file| string(file, file.module_name) == name)
+    }
+
+    /// Resolves the imports of each module, in the order they were added.
+    ///
+    /// Each module gets an entry per import, which is a hole if the import
+    /// can't be resolved.
+    pub fn resolve(&self) -> Vec<Vec<LinkerEntry<Resolved, LinkError>>> {
+        self.modules
+            .iter()
+            .map(|file| {
+                file.imports
+                    .iter()
+                    .map(|import| match self.resolve_import(file, import) {
+                        Ok(resolved) => LinkerEntry::Entry(resolved),
+                        Err(error) => LinkerEntry::Hole(error),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Checks the dependencies of each module against the versions of the
+    /// modules they name.
+    pub fn check_dependencies(&self) -> Vec<LinkError> {
+        let mut errors = Vec::new();
+        for file in &self.modules {
+            for dependency in &file.dependencies {
+                let module = string(file, file.module_name).to_string();
+                let name = string(file, dependency.module).to_string();
+                let requirement = dependency.requirement;
+                match self.module(&name) {
+                    None => errors.push(LinkError::MissingDependency { module, dependency: name, requirement }),
+                    Some(index) => {
+                        let version = self.modules[index].module_version;
+                        if !requirement.matches(&version) {
+                            errors.push(LinkError::IncompatibleDependency { module, dependency: name, requirement, version });
+                        }
+                    }
+                }
+            }
+        }
+        errors
+    }
+
+    /// Resolves every import, or returns every unmet dependency and every
+    /// import that can't be resolved.
+    pub fn link(&self) -> Result<Vec<Vec<Resolved>>, Vec<LinkError>> {
+        let mut errors = self.check_dependencies();
+        let mut modules = Vec::new();
+        for entries in self.resolve() {
+            let mut imports = Vec::new();
+            for entry in entries {
+                match entry {
+                    LinkerEntry::Entry(resolved) => imports.push(resolved),
+                    LinkerEntry::Hole(error) => errors.push(error),
+                }
+            }
+            modules.push(imports);
+        }
+        if errors.is_empty() { Ok(modules) } else { Err(errors) }
+    }
+
+    fn resolve_import(&self, file: &MaruFile, import: &MaruImport) -> Result<Resolved, LinkError> {
+        let module = string(file, file.module_name).to_string();
+        let dependency = string(file, import.module).to_string();
+        let symbol = string(file, import.name).to_string();
+        let Some(index) = self.module(&dependency) else {
+            return Err(LinkError::UnknownModule { module, dependency, symbol });
+        };
+        let exporter = self.modules[index];
+        let Some(export) = exporter.exports.iter().find(|export| string(exporter, export.name) == symbol) else {
+            return Err(LinkError::UnresolvedSymbol { module, dependency, symbol });
+        };
+        let expected = import.signature.kind();
+        if export.kind != expected {
+            return Err(LinkError::KindMismatch { module, dependency, symbol, expected, found: export.kind });
+        }
+
+        let invalid = || LinkError::InvalidExport {
+            module: dependency.clone(),
+            symbol: symbol.clone(),
+            kind: export.kind,
+            index: export.index,
+        };
+        let at = export.index as usize;
+        let matches = match &import.signature {
+            MaruSignature::Function { parameters, return_type } => {
+                let function = exporter.functions.get(at).ok_or_else(invalid)?;
+                parameters.len() == function.parameters.len()
+                    && parameters.iter().zip(&function.parameters).all(|(a, b)| same_type(file, a, exporter, b))
+                    && same_type(file, return_type, exporter, &function.return_type)
+            }
+            MaruSignature::Object => {
+                exporter.objects.get(at).ok_or_else(invalid)?;
+                true
+            }
+            MaruSignature::Global(type_tag) => {
+                let global = exporter.globals.get(at).ok_or_else(invalid)?;
+                same_type(file, type_tag, exporter, &global.type_tag)
+            }
+        };
+        if !matches {
+            let expected = signature_name(file, &import.signature);
+            let found = match export.kind {
+                MaruSymbolKind::Function => {
+                    let function = &exporter.functions[at];
+                    function_name(exporter, &function.parameters, &function.return_type)
+                }
+                MaruSymbolKind::Object => "object".to_string(),
+                MaruSymbolKind::Global => type_name(exporter, &exporter.globals[at].type_tag),
+            };
+            return Err(LinkError::SignatureMismatch { module, dependency, symbol, expected, found });
+        }
+        Ok(Resolved { module: index, kind: export.kind, index: export.index })
+    }
+}
+
+fn string(file: &MaruFile, index: StringIndex) -> &str {
+    file.string_table.entries.get(index as usize).map_or("<invalid string>", String::as_str)
+}
+
+fn kind_name(kind: MaruSymbolKind) -> &'static str {
+    match kind {
+        MaruSymbolKind::Function => "a function",
+        MaruSymbolKind::Object => "an object",
+        MaruSymbolKind::Global => "a global",
+    }
+}
+
+/// Whether `a` in `a_file` is the same type as `b` in `b_file`.
+fn same_type(a_file: &MaruFile, a: &MaruTypeTag, b_file: &MaruFile, b: &MaruTypeTag) -> bool {
+    match (a, b) {
+        (MaruTypeTag::Object(a), MaruTypeTag::Object(b)) => string(a_file, *a) == string(b_file, *b),
+        (MaruTypeTag::Array(a), MaruTypeTag::Array(b)) => same_type(a_file, a, b_file, b),
+        _ => std::mem::discriminant(a) == std::mem::discriminant(b),
+    }
+}
+
+fn signature_name(file: &MaruFile, signature: &MaruSignature) -> String {
+    match signature {
+        MaruSignature::Function { parameters, return_type } => function_name(file, parameters, return_type),
+        MaruSignature::Object => "object".to_string(),
+        MaruSignature::Global(type_tag) => type_name(file, type_tag),
+    }
+}
+
+fn function_name(file: &MaruFile, parameters: &[MaruTypeTag], return_type: &MaruTypeTag) -> String {
+    let parameters = parameters.iter().map(|parameter| type_name(file, parameter)).collect::<Vec<_>>();
+    format!("fn({}) -> {}", parameters.join(", "), type_name(file, return_type))
+}
+
+fn type_name(file: &MaruFile, type_tag: &MaruTypeTag) -> String {
+    match type_tag {
+        MaruTypeTag::Unit => "unit".to_string(),
+        MaruTypeTag::Bool => "bool".to_string(),
+        MaruTypeTag::U8 => "u8".to_string(),
+        MaruTypeTag::I8 => "i8".to_string(),
+        MaruTypeTag::U16 => "u16".to_string(),
+        MaruTypeTag::I16 => "i16".to_string(),
+        MaruTypeTag::U32 => "u32".to_string(),
+        MaruTypeTag::I32 => "i32".to_string(),
+        MaruTypeTag::U64 => "u64".to_string(),
+        MaruTypeTag::I64 => "i64".to_string(),
+        MaruTypeTag::F32 => "f32".to_string(),
+        MaruTypeTag::F64 => "f64".to_string(),
+        MaruTypeTag::Object(name) => string(file, *name).to_string(),
+        MaruTypeTag::Array(element) => format!("[{}]", type_name(file, element)),
+        MaruTypeTag::String => "string".to_string(),
+    }
+}
@@ -2,4 +2,6 @@ mod function_table;
 mod object_table;
 mod string_table;
 
-pub use object_table::*;
\ No newline at end of file
+pub use function_table::*;
+pub use object_table::*;
+pub use string_table::*;
\ No newline at end of file
@@ -1,17 +1,39 @@
-use std::{cell::UnsafeCell, sync::{Arc, atomic::AtomicUsize}};
+use std::{cell::UnsafeCell, ops::Range, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
 
-use crate::vm::{FunctionPtr, StringSymbol, VmType};
+use bytecode::Encoding;
+
+use crate::vm::{FunctionPtr, FunctionSymbol, StringSymbol, VmType};
 
 pub enum GetFunctionResult {
     Ptr(FunctionPtr),
     Bytecode(&'static [u8])
 }
 
+/// The owner of a buffer of bytecode shared between functions, such as a
+/// memory-mapped file or the `Vec` it was read into.
+pub type SharedBytes = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
 pub enum FunctionData {
     Bytecode(Box<[u8]>),
+    /// Bytecode at `range` in a buffer shared with other functions, such as
+    /// the file a `MaruFileView` reads from.
+    Shared(SharedBytes, Range<usize>),
     Native,
 }
 
+impl FunctionData {
+    /// Shares `code`, which must lie inside of `binary`.
+    ///
+    /// This is how bytecode borrowed from a `MaruFileView` over `binary` is
+    /// kept without copying it.
+    pub fn shared(binary: &SharedBytes, code: &[u8]) -> Option<Self> {
+        let bytes = (**binary).as_ref();
+        let start = (code.as_ptr() as usize).checked_sub(bytes.as_ptr() as usize)?;
+        let end = start.checked_add(code.len()).filter(|end| *end <= bytes.len())?;
+        Some(FunctionData::Shared(binary.clone(), start..end))
+    }
+}
+
 pub struct Function {
     pub name: StringSymbol,
     pub type_name: StringSymbol,
@@ -19,12 +41,38 @@ pub struct Function {
     pub return_type: VmType,
     pub function: FunctionData,
     pub variable_count: u32,
+    /// How the operands of the bytecode are encoded. `Fixed` unless set.
+    pub encoding: Encoding,
     call_counter: Arc<AtomicUsize>,
     function_ptr: UnsafeCell<Option<FunctionPtr>>,
 }
 
 impl Function {
+    pub fn new(
+        name: StringSymbol,
+        type_name: StringSymbol,
+        parameters: Box<[VmType]>,
+        return_type: VmType,
+        function: FunctionData,
+        variable_count: u32,
+    ) -> Self {
+        Function {
+            name,
+            type_name,
+            parameters,
+            return_type,
+            function,
+            variable_count,
+            encoding: Encoding::Fixed,
+            call_counter: Arc::new(AtomicUsize::new(0)),
+            function_ptr: UnsafeCell::new(None),
+        }
+    }
 
+    /// Records a call to this function and returns the number of previous calls.
+    pub fn count_call(&self) -> usize {
+        self.call_counter.fetch_add(1, Ordering::Relaxed)
+    }
 
     pub fn set_function_ptr(&self, ptr: FunctionPtr) {
         unsafe {
@@ -46,6 +94,10 @@ impl Function {
                 _ => {
                     match &self.function {
                         FunctionData::Native => todo!("load a builtin function"),
+                        FunctionData::Shared(binary, range) => {
+                            let code = &(**binary).as_ref()[range.clone()];
+                            GetFunctionResult::Bytecode(std::slice::from_raw_parts(code.as_ptr(), code.len()))
+                        }
                         FunctionData::Bytecode(code) => {
                             let len = code.len();
                             let code = code.as_ptr().as_ref().unwrap();
@@ -57,4 +109,42 @@ impl Function {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Default)]
+pub struct FunctionTable {
+    table: Vec<Function>,
+}
+
+impl FunctionTable {
+    pub fn new() -> Self {
+        Self {
+            table: Vec::new(),
+        }
+    }
+
+    pub fn push_function(&mut self, function: Function) -> FunctionSymbol {
+        let symbol = self.table.len() as FunctionSymbol;
+        self.table.push(function);
+        symbol
+    }
+
+    pub fn get(&self, symbol: FunctionSymbol) -> Option<&Function> {
+        self.table.get(symbol as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+impl std::ops::Index<FunctionSymbol> for FunctionTable {
+    type Output = Function;
+    fn index(&self, index: FunctionSymbol) -> &Self::Output {
+        &self.table[index as usize]
+    }
+}
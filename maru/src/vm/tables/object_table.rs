@@ -1,6 +1,17 @@
 use std::{alloc::Layout, sync::OnceLock};
 
-use crate::vm::{Metadata, StringSymbol, TypeSymbol};
+use crate::vm::{Array, Closure, Metadata, StackFrame, StringSymbol, TypeSymbol, VmString, VmType};
+
+/// The type symbol the allocator uses for stack frames.
+pub const STACK_FRAME_TYPE: TypeSymbol = 0;
+/// The type symbol used for closures and function objects.
+pub const CLOSURE_TYPE: TypeSymbol = 1;
+/// The type symbol used for arrays.
+pub const ARRAY_TYPE: TypeSymbol = 2;
+/// The type symbol used for strings.
+pub const STRING_TYPE: TypeSymbol = 3;
+/// The type symbol of the first type declared by a Maru file.
+pub const FIRST_USER_TYPE: TypeSymbol = STRING_TYPE + 1;
 
 
 #[derive(Debug)]
@@ -20,6 +31,8 @@ pub struct VariantDescription {
     /// Due to tight packing, members are out of order but this
     /// field gives you the correct offset into the pointer for each member.
     pub packing_offsets: Box<[usize]>,
+    /// The type of each member, in declaration order.
+    pub member_types: Box<[VmType]>,
 }
 
 #[derive(Debug)]
@@ -27,13 +40,41 @@ pub struct ObjectDescTable {
     table: Vec<ObjectDescription>
 }
 
+impl ObjectDescription {
+    fn builtin<T>() -> Self {
+        Self {
+            name: 0,
+            type_name: 0,
+            size: size_of::<T>() - size_of::<Metadata>(),
+            variants: Box::new([]),
+            layout: Layout::new::<T>(),
+        }
+    }
+}
+
 impl ObjectDescTable  {
+    /// Creates a table holding the descriptions of the builtin types.
+    ///
+    /// User types are pushed after them, starting at `FIRST_USER_TYPE`.
     pub fn new(max_type_symbol: TypeSymbol) -> Self {
+        let mut table = Vec::with_capacity(max_type_symbol as usize);
+        table.push(ObjectDescription::builtin::<StackFrame>());
+        table.push(ObjectDescription::builtin::<Closure>());
+        table.push(ObjectDescription::builtin::<Array>());
+        table.push(ObjectDescription::builtin::<VmString>());
         Self {
-            table: Vec::with_capacity(max_type_symbol as usize)
+            table
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
     pub fn push_desc(&mut self, desc: ObjectDescription) {
         self.table.push(desc);
     }
@@ -1,5 +1,7 @@
 use std::ptr::copy_nonoverlapping;
 
+use crate::vm::StringSymbol;
+
 
 
 pub struct StringEntry {
@@ -31,4 +33,43 @@ impl StringEntry {
             std::str::from_utf8_unchecked(slice)
         }
     }
-}
\ No newline at end of file
+}
+
+/// The string literals of the loaded modules, read by `LoadString`.
+#[derive(Default)]
+pub struct StringTable {
+    table: Vec<StringEntry>,
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self {
+            table: Vec::new(),
+        }
+    }
+
+    pub fn push_string(&mut self, string: &str) -> StringSymbol {
+        let symbol = self.table.len() as StringSymbol;
+        self.table.push(StringEntry::new(string));
+        symbol
+    }
+
+    pub fn get(&self, symbol: StringSymbol) -> Option<&StringEntry> {
+        self.table.get(symbol as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+impl std::ops::Index<StringSymbol> for StringTable {
+    type Output = StringEntry;
+    fn index(&self, index: StringSymbol) -> &Self::Output {
+        &self.table[index as usize]
+    }
+}
@@ -0,0 +1,93 @@
+use bytecode::{Encoding, assemble_with};
+use maru::disasm::disassemble;
+use maru_file::*;
+
+#[test]
+fn test_disassemble_file() {
+    let mut file = MaruFile::new();
+    file.major_version = 1;
+    file.minor_version = 2;
+    let module = file.add_string("example".to_string());
+    let option = file.add_string("Option<T>".to_string());
+    let option_u32 = file.add_string("Option<u32>".to_string());
+    let none = file.add_string("None".to_string());
+    let some = file.add_string("Some".to_string());
+    let value = file.add_string("value".to_string());
+    let unwrap = file.add_string("unwrap".to_string());
+    let counter = file.add_string("counter".to_string());
+    let source = file.add_string("main.maru".to_string());
+    file.module_name = module;
+    file.add_object(MaruObject {
+        name: option,
+        type_name: option_u32,
+        variants: vec![
+            MaruVariant { name: none, type_name: none, members: vec![] },
+            MaruVariant { name: some, type_name: some, members: vec![(value, MaruTypeTag::U32)] },
+        ],
+        internal: 0,
+    });
+    let code = assemble_with("match r0 { 1 -> @block0 }\nblock0:\ncopyfield r1, r0, 0\nreturn r1", Encoding::Compact).unwrap();
+    let bytecode_index = file.add_bytecode(code);
+    file.add_location(MaruLocation::new(source, vec![(4, 30), (4, 30), (10, 17)]));
+    file.add_function(MaruFunction {
+        name: unwrap,
+        type_name: unwrap,
+        parameters: vec![MaruTypeTag::Object(option_u32)],
+        return_type: MaruTypeTag::U32,
+        bytecode_index,
+        variables: 2,
+    });
+    file.add_global(MaruGlobal { name: counter, type_tag: MaruTypeTag::I64, init_index: -1 });
+
+    let file = MaruFile::from_binary(&file.into_binary()).expect("from_binary");
+    assert_eq!(disassemble(&file), "\
+module example (version 1.2.0)
+
+object Option<u32> (Option<T>)
+    variant 0 None
+    variant 1 Some
+        value: u32
+
+global g0 counter: i64
+    ; internal
+
+function 0 unwrap(Option<u32>) -> u32 ; 2 registers
+0000     match r0 { 1 -> @block0 }       ; main.maru:4..30
+0006 block0:                             ; main.maru:4..30
+0008     copyfield r1, r0, 0             ; main.maru:10..17
+000c     return r1
+");
+}
+
+#[test]
+fn test_disassemble_module_interface() {
+    let mut file = MaruFile::new();
+    file.minor_version = SECTIONED_MINOR_VERSION;
+    file.module_name = file.add_string("app".to_string());
+    let std = file.add_string("std".to_string());
+    let print = file.add_string("print".to_string());
+    let string = file.add_string("String".to_string());
+    let count = file.add_string("count".to_string());
+    let signature = MaruSignature::Function { parameters: vec![MaruTypeTag::Object(string)], return_type: MaruTypeTag::Unit };
+    file.add_import(MaruImport { module: std, name: string, signature: MaruSignature::Object });
+    file.add_import(MaruImport { module: std, name: print, signature });
+    file.add_global(MaruGlobal { name: count, type_tag: MaruTypeTag::U64, init_index: -1 });
+    file.add_export(MaruExport { name: count, kind: MaruSymbolKind::Global, index: 0 });
+    file.module_version = MaruVersion::new(1, 4, 0);
+    file.add_dependency(MaruDependency { module: std, requirement: MaruVersionReq::Tilde(MaruVersion::new(1, 2, 0)) });
+
+    let file = MaruFile::from_binary(&file.into_binary()).expect("from_binary");
+    assert_eq!(disassemble(&file), "\
+module app (version 0.2.0)
+version 1.4.0
+requires std ~1.2.0
+
+import object std.String
+import function 0 std.print(String) -> unit
+
+export global count = 0
+
+global g0 count: u64
+    ; internal
+");
+}
@@ -0,0 +1,577 @@
+use std::{alloc::Layout, sync::Arc};
+
+use bytecode::{CallArgument, DecodedInstruction as D, Encoding, FunctionBuilder, Instruction, NumericType};
+use maru_file::{CHECKSUM_MINOR_VERSION, MaruFile, MaruFileView, MaruFunction, MaruTypeTag};
+use maru::vm::{
+    Metadata, VmType,
+    interpreter::{Interpreter, Trap, Value},
+    tables::{
+        Function, FunctionData, FunctionTable, ObjectDescTable, ObjectDescription, STACK_FRAME_TYPE,
+        SharedBytes, StringTable, VariantDescription,
+    },
+};
+
+fn function(builder: FunctionBuilder, parameters: &[VmType], variables: u32) -> Function {
+    let code = builder.finish().expect("finish");
+    Function::new(0, 0, parameters.into(), VmType::Unit, FunctionData::Bytecode(code), variables)
+}
+
+fn code(instructions: Vec<D>) -> FunctionBuilder {
+    let mut builder = FunctionBuilder::new();
+    for instruction in instructions {
+        builder.emit(instruction);
+    }
+    builder
+}
+
+fn binary(instruction: Instruction, dst: u32, lhs: u32, rhs: u32) -> D {
+    D::Binary { instruction, dst, lhs, rhs }
+}
+
+fn arg(register: u32) -> CallArgument {
+    CallArgument { increment_ref: false, register }
+}
+
+fn run(functions: FunctionTable, arguments: &[Value]) -> Result<Value, Trap> {
+    let objects = ObjectDescTable::new(2);
+    let strings = StringTable::new();
+    let mut interpreter = Interpreter::new(&functions, &objects, &strings, 0);
+    interpreter.run(0, arguments)
+}
+
+#[test]
+fn test_arithmetic_wraps_at_register_width() {
+    let builder = code(vec![
+        D::Load8 { dst: 0, value: 200 },
+        D::Load8 { dst: 1, value: 100 },
+        binary(Instruction::AddU, 2, 0, 1),
+        D::Return { src: 2 },
+    ]);
+    let mut functions = FunctionTable::new();
+    functions.push_function(function(builder, &[], 3));
+    assert_eq!(run(functions, &[]), Ok(Value::new(44, VmType::U8)));
+}
+
+/// Owns the bytes of a file the way a memory map would, without being an `Arc<[u8]>`.
+struct Mapped(Vec<u8>);
+
+impl AsRef<[u8]> for Mapped {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[test]
+fn test_shared_bytecode_runs_from_a_view() {
+    let compact = |instructions: Vec<D>| {
+        let mut builder = FunctionBuilder::with_encoding(Encoding::Compact);
+        for instruction in instructions {
+            builder.emit(instruction);
+        }
+        builder.finish().expect("finish")
+    };
+    let main = compact(vec![D::Call { function: 1, arguments: vec![] }, D::LoadReturn { dst: 0 }, D::Return { src: 0 }]);
+    let seven = compact(vec![D::Load8 { dst: 0, value: 7 }, D::Return { src: 0 }]);
+    let mut file = MaruFile::new();
+    file.minor_version = CHECKSUM_MINOR_VERSION;
+    for (bytecode_index, code) in [main, seven].into_iter().enumerate() {
+        file.add_bytecode(code);
+        let return_type = MaruTypeTag::U8;
+        let bytecode_index = bytecode_index as i32;
+        file.add_function(MaruFunction { name: 0, type_name: 0, parameters: vec![], return_type, bytecode_index, variables: 1 });
+    }
+    let binary: SharedBytes = Arc::new(Mapped(file.into_binary()));
+
+    let view = MaruFileView::new((*binary).as_ref()).expect("view");
+    let encoding = if view.compact_bytecode() { Encoding::Compact } else { Encoding::Fixed };
+    let mut functions = FunctionTable::new();
+    for entry in view.functions() {
+        let code = view.get_bytecode(entry.bytecode_index).expect("bytecode");
+        let data = FunctionData::shared(&binary, code).expect("shared");
+        let mut function = Function::new(entry.name, entry.type_name, Box::new([]), VmType::U8, data, entry.variables);
+        function.encoding = encoding;
+        functions.push_function(function);
+    }
+    drop(view);
+    assert!(FunctionData::shared(&binary, &[7]).is_none());
+    assert_eq!(run(functions, &[]), Ok(Value::new(7, VmType::U8)));
+}
+
+#[test]
+fn test_loop_sums_with_blocks_and_branches() {
+    for encoding in [Encoding::Fixed, Encoding::Compact] {
+        // r0 = n, r1 = sum, r2 = 1, r3 = 0, r4 = cond
+        let mut builder = FunctionBuilder::with_encoding(encoding);
+        builder.emit(D::Load32 { dst: 1, value: 0 });
+        builder.emit(D::Load32 { dst: 2, value: 1 });
+        builder.emit(D::Load32 { dst: 3, value: 0 });
+        let (head, body, exit) = (builder.label(), builder.label(), builder.label());
+        builder.start_block(head);
+        builder.emit(binary(Instruction::GtU, 4, 0, 3));
+        builder.branch_if(4, body, exit);
+        builder.start_block(body);
+        builder.emit(binary(Instruction::AddU, 1, 1, 0));
+        builder.emit(binary(Instruction::SubU, 0, 0, 2));
+        builder.jump(head);
+        builder.start_block(exit);
+        builder.emit(D::Return { src: 1 });
+
+        let mut function = function(builder, &[VmType::U32], 5);
+        function.encoding = encoding;
+        let mut functions = FunctionTable::new();
+        functions.push_function(function);
+        let result = run(functions, &[Value::new(10, VmType::U32)]);
+        assert_eq!(result, Ok(Value::new(55, VmType::U32)), "{:?}", encoding);
+    }
+}
+
+#[test]
+fn test_call_and_load_return() {
+    let main = code(vec![
+        D::Load64 { dst: 0, value: 20 },
+        D::Call { function: 1, arguments: vec![arg(0)] },
+        D::LoadReturn { dst: 1 },
+        D::Return { src: 1 },
+    ]);
+    let double = code(vec![binary(Instruction::AddU, 0, 0, 0), D::Return { src: 0 }]);
+    let mut functions = FunctionTable::new();
+    functions.push_function(function(main, &[], 2));
+    functions.push_function(function(double, &[VmType::U64], 1));
+    assert_eq!(run(functions, &[]), Ok(Value::new(40, VmType::U64)));
+}
+
+#[test]
+fn test_closure_receives_captures_before_arguments() {
+    let main = code(vec![
+        D::Load32 { dst: 0, value: 7 },
+        D::CreateClosure { dst: 1, function: 1, captures: vec![arg(0)] },
+        D::Load32 { dst: 2, value: 3 },
+        D::Invoke { closure: 1, arguments: vec![arg(2)] },
+        D::Destroy { register: 1 },
+        D::LoadReturn { dst: 3 },
+        D::Return { src: 3 },
+    ]);
+    let subtract = code(vec![binary(Instruction::SubU, 0, 0, 1), D::Return { src: 0 }]);
+    let mut functions = FunctionTable::new();
+    functions.push_function(function(main, &[], 4));
+    functions.push_function(function(subtract, &[VmType::U32, VmType::U32], 2));
+    assert_eq!(run(functions, &[]), Ok(Value::new(4, VmType::U32)));
+}
+
+/// Runs `body` under `handler` for effect 7 and returns the result of the
+/// `Handle`. The body can call `perform`, which takes a `U32`, as function 3.
+fn handle(handler: Vec<D>, body: Vec<D>, perform: Vec<D>) -> Result<Value, Trap> {
+    let main = code(vec![
+        D::CreateFnObject { dst: 0, function: 1 },
+        D::CreateFnObject { dst: 1, function: 2 },
+        D::Handle { effect: 7, handler: 0, body: 1 },
+        D::Destroy { register: 0 },
+        D::Destroy { register: 1 },
+        D::LoadReturn { dst: 2 },
+        D::Return { src: 2 },
+    ]);
+    let mut functions = FunctionTable::new();
+    functions.push_function(function(main, &[], 3));
+    functions.push_function(function(code(handler), &[VmType::Object(STACK_FRAME_TYPE), VmType::U32], 6));
+    functions.push_function(function(code(body), &[], 4));
+    functions.push_function(function(code(perform), &[VmType::U32], 2));
+    run(functions, &[])
+}
+
+#[test]
+fn test_effect_handler_resumes_continuation() {
+    let body = vec![
+        D::Load32 { dst: 0, value: 10 },
+        D::Perform { effect: 7, arguments: vec![arg(0)] },
+        D::LoadReturn { dst: 1 },
+        D::Load32 { dst: 2, value: 1 },
+        binary(Instruction::AddU, 3, 1, 2),
+        D::Return { src: 3 },
+    ];
+    // Resumes with twice the performed value and adds 100 to the body's result.
+    let handler = vec![
+        binary(Instruction::AddU, 2, 1, 1),
+        D::Resume { continuation: 0, value: 2 },
+        D::LoadReturn { dst: 3 },
+        D::Load32 { dst: 4, value: 100 },
+        binary(Instruction::AddU, 5, 3, 4),
+        D::Return { src: 5 },
+    ];
+    assert_eq!(handle(handler, body, vec![D::ReturnUnit]), Ok(Value::new(121, VmType::U32)));
+}
+
+#[test]
+fn test_effect_handler_drops_or_misuses_continuation() {
+    // The effect is performed two frames above the handled body.
+    let body = vec![
+        D::Load32 { dst: 0, value: 10 },
+        D::Call { function: 3, arguments: vec![arg(0)] },
+        D::LoadReturn { dst: 1 },
+        D::Return { src: 1 },
+    ];
+    let perform = vec![D::Perform { effect: 7, arguments: vec![arg(0)] }, D::Return { src: 0 }];
+
+    // Dropping the continuation aborts the body, like an exception.
+    let handler = vec![D::DropContinuation { continuation: 0 }, D::Return { src: 1 }];
+    assert_eq!(handle(handler, body.clone(), perform.clone()), Ok(Value::new(10, VmType::U32)));
+
+    let handler = vec![
+        D::Clone { dst: 2, src: 0 },
+        D::Resume { continuation: 0, value: 1 },
+        D::Resume { continuation: 2, value: 1 },
+        D::ReturnUnit,
+    ];
+    assert_eq!(handle(handler, body.clone(), perform), Err(Trap::ContinuationResumed));
+
+    let perform = vec![D::Perform { effect: 8, arguments: vec![] }, D::ReturnUnit];
+    assert_eq!(handle(vec![D::ReturnUnit], body.clone(), perform), Err(Trap::UnhandledEffect(8)));
+
+    // The handler takes one argument, so it can't be invoked with two; the
+    // frames up to the handler are unwound with the rest of the stack.
+    let perform = vec![D::Perform { effect: 7, arguments: vec![arg(0), arg(0)] }, D::ReturnUnit];
+    let trap = Trap::ArgumentCount { function: 1, expected: 2, found: 3 };
+    assert_eq!(handle(vec![D::ReturnUnit], body, perform), Err(trap));
+}
+
+#[test]
+fn test_objects_fields_and_match() {
+    // type 4 has the variants `None` and `Some(u32)`
+    let mut objects = ObjectDescTable::new(5);
+    objects.push_desc(ObjectDescription {
+        name: 0,
+        type_name: 0,
+        size: 4,
+        variants: Box::new([
+            VariantDescription { variant_names: Box::new([]), packing_offsets: Box::new([]), member_types: Box::new([]) },
+            VariantDescription { variant_names: Box::new([0]), packing_offsets: Box::new([0]), member_types: Box::new([VmType::U32]) },
+        ]),
+        layout: Layout::from_size_align(size_of::<Metadata>() + 4, 8).unwrap(),
+    });
+
+    let mut builder = code(vec![
+        D::Load32 { dst: 0, value: 99 },
+        D::CreateObject { dst: 1, type_id: 4, variant: 1 },
+        D::PlaceField { object: 1, field: 0, src: 0 },
+    ]);
+    let (none, some) = (builder.label(), builder.label());
+    builder.match_variant(1, &[(0, none), (1, some)]);
+    builder.start_block(none);
+    builder.emit(D::Destroy { register: 1 });
+    builder.emit(D::ReturnUnit);
+    builder.start_block(some);
+    builder.emit(D::CopyField { dst: 2, object: 1, field: 0 });
+    builder.emit(D::Destroy { register: 1 });
+    builder.emit(D::Return { src: 2 });
+
+    let mut functions = FunctionTable::new();
+    functions.push_function(function(builder, &[], 3));
+    let strings = StringTable::new();
+    let mut interpreter = Interpreter::new(&functions, &objects, &strings, 0);
+    assert_eq!(interpreter.run(0, &[]), Ok(Value::new(99, VmType::U32)));
+}
+
+#[test]
+fn test_arrays_slice_copy_and_check_bounds() {
+    // r2 = [7, 7, 7], r6 = r2[1..3] after r2[1] = 9
+    let build = code(vec![
+        D::Load64 { dst: 0, value: 3 },
+        D::Load32 { dst: 1, value: 7 },
+        D::CreateArray { dst: 2, len: 0, fill: 1 },
+        D::Load64 { dst: 3, value: 1 },
+        D::Load32 { dst: 4, value: 9 },
+        D::SetElement { array: 2, index: 3, src: 4 },
+        D::SliceArray { dst: 6, array: 2, start: 3, end: 0 },
+        D::Load64 { dst: 7, value: 0 },
+        D::ArrayLength { dst: 8, array: 6 },
+        D::CopyElements { dst: 2, dst_start: 7, src: 6, src_start: 7, count: 8 },
+        D::Destroy { register: 6 },
+        D::Return { src: 2 },
+    ]);
+    let out_of_bounds = code(vec![
+        D::Load64 { dst: 0, value: 3 },
+        D::CreateArray { dst: 1, len: 0, fill: 0 },
+        D::GetElement { dst: 2, array: 1, index: 0 },
+        D::Return { src: 2 },
+    ]);
+    let mismatch = code(vec![
+        D::Load64 { dst: 0, value: 1 },
+        D::CreateArray { dst: 1, len: 0, fill: 0 },
+        D::Load8 { dst: 2, value: 0 },
+        D::SetElement { array: 1, index: 2, src: 2 },
+        D::ReturnUnit,
+    ]);
+    let too_large = code(vec![
+        D::Load64 { dst: 0, value: u64::MAX },
+        D::CreateArray { dst: 1, len: 0, fill: 0 },
+        D::Return { src: 1 },
+    ]);
+    let mut functions = FunctionTable::new();
+    functions.push_function(function(build, &[], 9));
+    functions.push_function(function(out_of_bounds, &[], 3));
+    functions.push_function(function(mismatch, &[], 3));
+    functions.push_function(function(too_large, &[], 2));
+    let objects = ObjectDescTable::new(0);
+    let strings = StringTable::new();
+    let mut interpreter = Interpreter::new(&functions, &objects, &strings, 0);
+
+    let array = interpreter.run(0, &[]).expect("run");
+    assert_eq!(array.ty, VmType::Array);
+    let u32 = |value| Value::new(value, VmType::U32);
+    assert_eq!(interpreter.elements(array), Ok(vec![u32(9), u32(7), u32(7)]));
+    assert_eq!(interpreter.run(1, &[]), Err(Trap::IndexOutOfBounds { index: 3, len: 3 }));
+    assert_eq!(
+        interpreter.run(2, &[]),
+        Err(Trap::TypeMismatch { instruction: Instruction::SetElement, found: VmType::U8 }),
+    );
+    assert_eq!(interpreter.run(3, &[]), Err(Trap::ArrayTooLarge { len: u64::MAX }));
+}
+
+#[test]
+fn test_string_literals_and_operations() {
+    let mut strings = StringTable::new();
+    strings.push_string("héllo");
+    strings.push_string(" world");
+    let literals = [D::LoadString { dst: 0, string: 0 }, D::LoadString { dst: 1, string: 1 }];
+    let program = |instructions: Vec<D>| code(literals.iter().cloned().chain(instructions).collect());
+    let mut functions = FunctionTable::new();
+    for (instructions, variables) in [
+        (vec![D::ConcatString { dst: 2, lhs: 0, rhs: 1 }, D::Return { src: 2 }], 3),
+        (vec![D::Load64 { dst: 2, value: 1 }, D::StringChar { dst: 3, string: 0, index: 2 }, D::Return { src: 3 }], 4),
+        (vec![D::Load64 { dst: 2, value: 2 }, D::StringChar { dst: 3, string: 0, index: 2 }, D::Return { src: 3 }], 4),
+        (vec![D::StringLength { dst: 2, src: 0 }, D::StringByte { dst: 3, string: 0, index: 2 }, D::Return { src: 3 }], 4),
+        (
+            vec![
+                D::Load64 { dst: 2, value: 0 },
+                D::Load64 { dst: 3, value: 1 },
+                D::SliceString { dst: 4, string: 0, start: 2, end: 3 },
+                D::CompareString { dst: 5, lhs: 4, rhs: 0 },
+                D::Return { src: 5 },
+            ],
+            6,
+        ),
+        (vec![D::LoadString { dst: 2, string: 2 }, D::ReturnUnit], 3),
+    ] {
+        functions.push_function(function(program(instructions), &[], variables));
+    }
+    let objects = ObjectDescTable::new(0);
+    let mut interpreter = Interpreter::new(&functions, &objects, &strings, 0);
+
+    let string = interpreter.run(0, &[]).expect("run");
+    assert_eq!(string.ty, VmType::String);
+    assert_eq!(interpreter.str(string), Ok("héllo world"));
+    assert_eq!(interpreter.run(1, &[]), Ok(Value::new('é' as u64, VmType::U32)));
+    assert_eq!(interpreter.run(2, &[]), Err(Trap::NotCharBoundary { index: 2 }));
+    assert_eq!(interpreter.run(3, &[]), Err(Trap::IndexOutOfBounds { index: 6, len: 6 }));
+    assert_eq!(interpreter.run(4, &[]), Ok(Value::new(-1i64 as u64, VmType::I32)));
+    assert_eq!(interpreter.run(5, &[]), Err(Trap::UnknownString(2)));
+}
+
+#[test]
+fn test_division_by_zero_traps() {
+    let builder = code(vec![
+        D::Load32 { dst: 0, value: 1 },
+        D::Load32 { dst: 1, value: 0 },
+        binary(Instruction::DivS, 2, 0, 1),
+        D::Return { src: 2 },
+    ]);
+    let mut functions = FunctionTable::new();
+    functions.push_function(function(builder, &[], 3));
+    assert_eq!(run(functions, &[]), Err(Trap::DivisionByZero));
+}
+
+#[test]
+fn test_checked_and_saturating_arithmetic() {
+    let arithmetic = |instruction, lhs: Value, rhs: Value| {
+        let builder = code(vec![binary(instruction, 2, 0, 1), D::Return { src: 2 }]);
+        let mut functions = FunctionTable::new();
+        functions.push_function(function(builder, &[lhs.ty, rhs.ty], 3));
+        run(functions, &[lhs, rhs])
+    };
+    use Instruction::*;
+    let u8 = |value| Value::new(value, VmType::U8);
+    let i8 = |value: i64| Value::new(value as u64, VmType::I8);
+    let i64 = |value: i64| Value::new(value as u64, VmType::I64);
+    let u64 = |value| Value::new(value, VmType::U64);
+    let cases = [
+        (AddU, u8(200), u8(100), Ok(u8(44))),
+        (CheckedAddU, u8(200), u8(55), Ok(u8(255))),
+        (CheckedAddU, u8(200), u8(100), Err(Trap::IntegerOverflow { instruction: CheckedAddU })),
+        (CheckedSubU, u8(1), u8(2), Err(Trap::IntegerOverflow { instruction: CheckedSubU })),
+        (CheckedMulS, i8(-64), i8(2), Ok(i8(-128))),
+        (CheckedMulS, i8(64), i8(2), Err(Trap::IntegerOverflow { instruction: CheckedMulS })),
+        (SaturatingAddU, u8(200), u8(100), Ok(u8(255))),
+        (SaturatingSubU, u8(1), u8(2), Ok(u8(0))),
+        (SaturatingSubS, i8(-100), i8(100), Ok(i8(-128))),
+        (SaturatingMulS, i64(i64::MAX), i64(-2), Ok(i64(i64::MIN))),
+        (CheckedMulU, u64(u64::MAX), u64(u64::MAX), Err(Trap::IntegerOverflow { instruction: CheckedMulU })),
+        (CheckedMulU, u64(u64::MAX), u64(1), Ok(u64(u64::MAX))),
+        (SaturatingMulU, u64(u64::MAX), u64(u64::MAX), Ok(u64(u64::MAX))),
+        (SaturatingMulU, u64(1 << 32), u64(1 << 32), Ok(u64(u64::MAX))),
+        (DivS, i64(i64::MIN), i64(-1), Ok(i64(i64::MIN))),
+        (RemS, i64(i64::MIN), i64(-1), Ok(i64(0))),
+        (CheckedDivS, i64(i64::MIN), i64(-1), Err(Trap::IntegerOverflow { instruction: CheckedDivS })),
+        (SaturatingDivS, i8(-128), i8(-1), Ok(i8(127))),
+        (SaturatingDivS, i8(-7), i8(2), Ok(i8(-3))),
+        (CheckedDivS, i8(1), i8(0), Err(Trap::DivisionByZero)),
+        (RemU, u8(1), u8(0), Err(Trap::DivisionByZero)),
+    ];
+    for (instruction, lhs, rhs, expected) in cases {
+        assert_eq!(arithmetic(instruction, lhs, rhs), expected, "{:?} {:?} {:?}", instruction, lhs, rhs);
+    }
+}
+
+#[test]
+fn test_bit_manipulation_respects_width() {
+    let unary = |instruction, src: Value| {
+        let builder = code(vec![D::Unary { instruction, dst: 1, src: 0 }, D::Return { src: 1 }]);
+        let mut functions = FunctionTable::new();
+        functions.push_function(function(builder, &[src.ty], 2));
+        run(functions, &[src])
+    };
+    let rotate = |instruction, lhs: Value, amount| {
+        let builder = code(vec![D::Load8 { dst: 1, value: amount }, binary(instruction, 2, 0, 1), D::Return { src: 2 }]);
+        let mut functions = FunctionTable::new();
+        functions.push_function(function(builder, &[lhs.ty], 3));
+        run(functions, &[lhs])
+    };
+    use Instruction::*;
+    let u8 = |value| Value::new(value, VmType::U8);
+    let i16 = |value| Value::new(value, VmType::I16);
+    let u64 = |value| Value::new(value, VmType::U64);
+    let cases = [
+        (PopCount, i16(0xFFFF), i16(16)),
+        (CountLeadingZeros, u8(0x10), u8(3)),
+        (CountLeadingZeros, i16(0), i16(16)),
+        (CountTrailingZeros, u8(0), u8(8)),
+        (CountTrailingZeros, u64(1 << 40), u64(40)),
+        (BitReverse, u8(0b0000_0110), u8(0b0110_0000)),
+        (BitReverse, u64(1), u64(1 << 63)),
+    ];
+    for (instruction, src, expected) in cases {
+        assert_eq!(unary(instruction, src), Ok(expected), "{:?} {:?}", instruction, src);
+    }
+    assert_eq!(rotate(RotateLeft, u8(0b1000_0001), 1), Ok(u8(0b0000_0011)));
+    assert_eq!(rotate(RotateRight, u8(0b1000_0001), 9), Ok(u8(0b1100_0000)));
+    assert_eq!(rotate(RotateLeft, u64(0x8000_0000_0000_0001), 68), Ok(u64(0x18)));
+    assert_eq!(rotate(RotateRight, i16(0x1234), 0), Ok(i16(0x1234)));
+    assert_eq!(unary(PopCount, Value::bool(true)), Err(Trap::TypeMismatch { instruction: PopCount, found: VmType::Bool }));
+}
+
+#[test]
+fn test_float_operations() {
+    let run_code = |instructions: Vec<D>, arguments: &[Value]| {
+        let types = arguments.iter().map(|argument| argument.ty).collect::<Vec<_>>();
+        let mut functions = FunctionTable::new();
+        functions.push_function(function(code(instructions), &types, 4));
+        run(functions, arguments)
+    };
+    let unary = |instruction, src: Value| run_code(vec![D::Unary { instruction, dst: 1, src: 0 }, D::Return { src: 1 }], &[src]);
+    let binary = |instruction, lhs: Value, rhs: Value| {
+        run_code(vec![binary(instruction, 2, 0, 1), D::Return { src: 2 }], &[lhs, rhs])
+    };
+    use Instruction::*;
+    let cases = [
+        (NegF, Value::f64(0.0), Value::f64(-0.0)),
+        (AbsF, Value::f32(-2.5), Value::f32(2.5)),
+        (SqrtF, Value::f64(2.25), Value::f64(1.5)),
+        (FloorF, Value::f32(-1.5), Value::f32(-2.0)),
+        (CeilF, Value::f64(1.1), Value::f64(2.0)),
+        (TruncF, Value::f64(-1.9), Value::f64(-1.0)),
+        (RoundF, Value::f64(2.5), Value::f64(2.0)),
+        (RoundF, Value::f32(3.5), Value::f32(4.0)),
+    ];
+    for (instruction, src, expected) in cases {
+        assert_eq!(unary(instruction, src), Ok(expected), "{:?} {:?}", instruction, src);
+    }
+    assert!(unary(SqrtF, Value::f32(-1.0)).unwrap().as_f32().is_nan());
+
+    let cases = [
+        (RemF, Value::f64(-7.0), Value::f64(2.0), Value::f64(-1.0)),
+        (MinF, Value::f64(0.0), Value::f64(-0.0), Value::f64(-0.0)),
+        (MinF, Value::f32(1.0), Value::f32(-3.0), Value::f32(-3.0)),
+        (MaxF, Value::f64(-0.0), Value::f64(0.0), Value::f64(0.0)),
+        (MaxF, Value::f32(1.0), Value::f32(-3.0), Value::f32(1.0)),
+        (CopySignF, Value::f64(3.0), Value::f64(-0.0), Value::f64(-3.0)),
+    ];
+    for (instruction, lhs, rhs, expected) in cases {
+        assert_eq!(binary(instruction, lhs, rhs), Ok(expected), "{:?} {:?} {:?}", instruction, lhs, rhs);
+    }
+    assert!(binary(MinF, Value::f64(f64::NAN), Value::f64(1.0)).unwrap().as_f64().is_nan());
+    assert!(binary(MaxF, Value::f32(1.0), Value::f32(f32::NAN)).unwrap().as_f32().is_nan());
+
+    // 0.1 * 10 - 1 is 0 with two roundings but not with one.
+    let fma = vec![D::FmaF { dst: 3, lhs: 0, rhs: 1, addend: 2 }, D::Return { src: 3 }];
+    let result = run_code(fma.clone(), &[Value::f64(0.1), Value::f64(10.0), Value::f64(-1.0)]);
+    assert_eq!(result, Ok(Value::f64(0.1f64.mul_add(10.0, -1.0))));
+    assert_ne!(result, Ok(Value::f64(0.0)));
+    assert_eq!(
+        run_code(fma, &[Value::f64(1.0), Value::f32(1.0), Value::f64(1.0)]),
+        Err(Trap::TypeMismatch { instruction: FmaF, found: VmType::F32 }),
+    );
+}
+
+#[test]
+fn test_conversions() {
+    let convert = |instruction, argument: Value, ty| {
+        let builder = code(vec![D::Convert { instruction, dst: 1, src: 0, ty }, D::Return { src: 1 }]);
+        let mut functions = FunctionTable::new();
+        functions.push_function(function(builder, &[argument.ty], 2));
+        run(functions, &[argument])
+    };
+    use Instruction::*;
+    let cases = [
+        (SignExtend, Value::new(0x80, VmType::I8), NumericType::I32, Value::new(0xFFFF_FF80, VmType::I32)),
+        (ZeroExtend, Value::new(0x80, VmType::I8), NumericType::U64, Value::new(0x80, VmType::U64)),
+        (Truncate, Value::new(0x1234, VmType::U16), NumericType::U8, Value::new(0x34, VmType::U8)),
+        (IntToFloatS, Value::new(-3i64 as u64, VmType::I64), NumericType::F32, Value::f32(-3.0)),
+        (IntToFloatU, Value::new(u64::MAX, VmType::U64), NumericType::F64, Value::f64(18446744073709551615.0)),
+        (FloatToIntSat, Value::f64(f64::NAN), NumericType::I32, Value::new(0, VmType::I32)),
+        (FloatToIntSat, Value::f64(-1e10), NumericType::I16, Value::new(0x8000, VmType::I16)),
+        (FloatToIntSat, Value::f32(300.7), NumericType::U8, Value::new(255, VmType::U8)),
+        (FloatToInt, Value::f64(-2.9), NumericType::I8, Value::new(0xFE, VmType::I8)),
+        (FloatToInt, Value::f64(255.9), NumericType::U8, Value::new(255, VmType::U8)),
+        (FloatToFloat, Value::f64(0.1), NumericType::F32, Value::f32(0.1)),
+    ];
+    for (instruction, argument, ty, expected) in cases {
+        assert_eq!(convert(instruction, argument, ty), Ok(expected), "{:?} {:?}", instruction, argument);
+    }
+
+    let out_of_range = Err(Trap::ConversionOutOfRange { to: VmType::U8 });
+    assert_eq!(convert(FloatToInt, Value::f64(256.0), NumericType::U8), out_of_range);
+    assert_eq!(convert(FloatToInt, Value::f64(-1.0), NumericType::U8), out_of_range);
+    assert_eq!(convert(FloatToInt, Value::f32(f32::NAN), NumericType::U8), out_of_range);
+    assert_eq!(
+        convert(FloatToInt, Value::f64(9.3e18), NumericType::I64),
+        Err(Trap::ConversionOutOfRange { to: VmType::I64 }),
+    );
+    assert_eq!(
+        convert(SignExtend, Value::new(1, VmType::I32), NumericType::I8),
+        Err(Trap::InvalidConversion { instruction: SignExtend, from: VmType::I32, to: VmType::I8 }),
+    );
+    assert_eq!(
+        convert(Truncate, Value::f32(1.0), NumericType::U8),
+        Err(Trap::TypeMismatch { instruction: Truncate, found: VmType::F32 }),
+    );
+}
+
+#[test]
+fn test_missing_return_traps() {
+    let builder = code(vec![D::Load8 { dst: 0, value: 1 }]);
+    let mut functions = FunctionTable::new();
+    functions.push_function(function(builder, &[], 1));
+    assert_eq!(run(functions, &[]), Err(Trap::MissingReturn(0)));
+}
+
+#[test]
+fn test_invalid_opcode_traps() {
+    let mut functions = FunctionTable::new();
+    let code = Box::new([Instruction::ReturnUnit.into(), 0xFF]);
+    functions.push_function(Function::new(0, 0, Box::new([]), VmType::Unit, FunctionData::Bytecode(Box::new([0xFF])), 0));
+    functions.push_function(Function::new(0, 0, Box::new([]), VmType::Unit, FunctionData::Bytecode(code), 0));
+    let objects = ObjectDescTable::new(2);
+    let strings = StringTable::new();
+    let mut interpreter = Interpreter::new(&functions, &objects, &strings, 0);
+    let expected = Trap::Decode(bytecode::DecodeError::InvalidOpcode { offset: 0, opcode: 0xFF });
+    assert_eq!(interpreter.run(0, &[]), Err(expected));
+    assert_eq!(interpreter.run(1, &[]), Ok(Value::UNIT));
+}
@@ -0,0 +1,137 @@
+use maru::vm::linker::{LinkError, LinkerEntry, Linker, Resolved};
+use maru_file::*;
+
+/// A `std` module exporting `String`, `print(String) -> unit` and the global `count: u64`.
+fn library() -> MaruFile {
+    let mut file = MaruFile::new();
+    file.minor_version = SECTIONED_MINOR_VERSION;
+    file.module_name = file.add_string("std".to_string());
+    // the strings are numbered differently than in `app`
+    let string = file.add_string("String".to_string());
+    let print = file.add_string("print".to_string());
+    let count = file.add_string("count".to_string());
+    file.add_object(MaruObject { name: string, type_name: string, variants: vec![], internal: 0 });
+    file.add_function(MaruFunction {
+        name: print,
+        type_name: print,
+        parameters: vec![MaruTypeTag::Object(string)],
+        return_type: MaruTypeTag::Unit,
+        bytecode_index: -1,
+        variables: 1,
+    });
+    file.add_global(MaruGlobal { name: count, type_tag: MaruTypeTag::U64, init_index: -1 });
+    file.add_export(MaruExport { name: string, kind: MaruSymbolKind::Object, index: 0 });
+    file.add_export(MaruExport { name: print, kind: MaruSymbolKind::Function, index: 0 });
+    file.add_export(MaruExport { name: count, kind: MaruSymbolKind::Global, index: 0 });
+    file
+}
+
+/// An `app` module importing `imports` from the modules they name.
+fn app(imports: Vec<(&str, &str, MaruSignature)>) -> MaruFile {
+    let mut file = MaruFile::new();
+    file.minor_version = SECTIONED_MINOR_VERSION;
+    file.module_name = file.add_string("app".to_string());
+    for (module, name, signature) in imports {
+        let module = file.add_string(module.to_string());
+        let name = file.add_string(name.to_string());
+        file.add_import(MaruImport { module, name, signature });
+    }
+    file
+}
+
+fn print(parameter: MaruTypeTag) -> MaruSignature {
+    MaruSignature::Function { parameters: vec![parameter], return_type: MaruTypeTag::Unit }
+}
+
+#[test]
+fn test_link_resolves_imports_by_name() {
+    let library = library();
+    let mut app = app(vec![
+        ("std", "count", MaruSignature::Global(MaruTypeTag::U64)),
+        ("std", "String", MaruSignature::Object),
+        ("std", "print", print(MaruTypeTag::U8)),
+    ]);
+    let string = app.add_string("String".to_string());
+    app.imports[2].signature = print(MaruTypeTag::Object(string));
+
+    let mut linker = Linker::new();
+    assert_eq!(linker.add_module(&app), 0);
+    assert_eq!(linker.add_module(&library), 1);
+    assert_eq!(linker.module("std"), Some(1));
+    let resolved = linker.link().expect("link");
+    assert_eq!(resolved[0], vec![
+        Resolved { module: 1, kind: MaruSymbolKind::Global, index: 0 },
+        Resolved { module: 1, kind: MaruSymbolKind::Object, index: 0 },
+        Resolved { module: 1, kind: MaruSymbolKind::Function, index: 0 },
+    ]);
+    assert!(resolved[1].is_empty());
+}
+
+#[test]
+fn test_link_reports_every_unresolved_import() {
+    let library = library();
+    let app = app(vec![
+        ("io", "read", print(MaruTypeTag::U8)),
+        ("std", "exit", print(MaruTypeTag::I32)),
+        ("std", "count", MaruSignature::Object),
+        ("std", "print", print(MaruTypeTag::String)),
+        ("std", "count", MaruSignature::Global(MaruTypeTag::U64)),
+    ]);
+    let mut linker = Linker::new();
+    linker.add_module(&app);
+    linker.add_module(&library);
+
+    let holes = linker.resolve().remove(0).into_iter().filter(|entry| matches!(entry, LinkerEntry::Hole(_))).count();
+    assert_eq!(holes, 4);
+
+    let errors = linker.link().expect_err("unresolved imports");
+    let messages = errors.iter().map(LinkError::to_string).collect::<Vec<_>>();
+    assert_eq!(messages, [
+        "`app` imports `read` from `io`, but there is no module `io`",
+        "`app` imports `exit` from `std`, which doesn't export it",
+        "`app` imports `count` from `std` as an object, but it is exported as a global",
+        "`app` imports `print` from `std` as `fn(string) -> unit`, but it is exported as `fn(String) -> unit`",
+    ]);
+}
+
+#[test]
+fn test_link_rejects_exports_of_missing_items() {
+    let mut library = library();
+    library.exports[1].index = 3;
+    let app = app(vec![("std", "print", print(MaruTypeTag::String))]);
+    let mut linker = Linker::new();
+    linker.add_module(&library);
+    linker.add_module(&app);
+    assert_eq!(linker.link().err(), Some(vec![LinkError::InvalidExport {
+        module: "std".to_string(),
+        symbol: "print".to_string(),
+        kind: MaruSymbolKind::Function,
+        index: 3,
+    }]));
+}
+
+#[test]
+fn test_link_refuses_incompatible_dependencies() {
+    let mut library = library();
+    library.module_version = MaruVersion::new(2, 0, 0);
+    let mut app = app(vec![("std", "count", MaruSignature::Global(MaruTypeTag::U64))]);
+    let (std, io) = (app.add_string("std".to_string()), app.add_string("io".to_string()));
+    app.add_dependency(MaruDependency { module: std, requirement: MaruVersionReq::Caret(MaruVersion::new(1, 2, 0)) });
+    app.add_dependency(MaruDependency { module: io, requirement: MaruVersionReq::Any });
+
+    let mut linker = Linker::new();
+    linker.add_module(&app);
+    linker.add_module(&library);
+    let messages = linker.link().expect_err("incompatible dependency").iter().map(LinkError::to_string).collect::<Vec<_>>();
+    assert_eq!(messages, [
+        "`app` requires `std` ^1.2.0, but `std` is version 2.0.0",
+        "`app` requires `io` *, but there is no module `io`",
+    ]);
+
+    library.module_version = MaruVersion::new(1, 3, 1);
+    app.dependencies.pop();
+    let mut linker = Linker::new();
+    linker.add_module(&app);
+    linker.add_module(&library);
+    assert!(linker.link().is_ok());
+}
@@ -0,0 +1,196 @@
+use bytecode::{Encoding, Instruction, assemble, assemble_with};
+use maru::{
+    verifier::{Diagnostic, VerifyError, verify},
+    vm::VmType,
+};
+use maru_file::*;
+
+/// A file with the object `Option` as type 2 and an `I64` global.
+fn module() -> MaruFile {
+    let mut file = MaruFile::new();
+    let option = file.add_string("Option".to_string());
+    let value = file.add_string("value".to_string());
+    file.add_object(MaruObject {
+        name: option,
+        type_name: option,
+        variants: vec![
+            MaruVariant { name: 0, type_name: 0, members: vec![] },
+            MaruVariant { name: 0, type_name: 0, members: vec![(value, MaruTypeTag::U32)] },
+        ],
+        internal: 0,
+    });
+    file.add_global(MaruGlobal { name: 0, type_tag: MaruTypeTag::I64, init_index: -1 });
+    file
+}
+
+fn function(file: &mut MaruFile, parameters: Vec<MaruTypeTag>, return_type: MaruTypeTag, variables: u32, source: &str) {
+    let bytecode_index = file.add_bytecode(assemble(source).expect("assemble"));
+    file.add_function(MaruFunction { name: 0, type_name: 0, parameters, return_type, bytecode_index, variables });
+}
+
+fn errors(result: Result<(), Vec<Diagnostic>>) -> Vec<(usize, usize, VerifyError)> {
+    let diagnostics = result.err().unwrap_or_default();
+    diagnostics.into_iter().map(|diagnostic| (diagnostic.function, diagnostic.offset, diagnostic.error)).collect()
+}
+
+#[test]
+fn test_verify_accepts_valid_functions() {
+    let mut file = module();
+    // sum(n: u32) -> u32
+    function(&mut file, vec![MaruTypeTag::U32], MaruTypeTag::U32, 5, "
+        load32 r1, 0
+        load32 r2, 1
+        load32 r3, 0
+    block0:
+        gtu r4, r0, r3
+        if r4, @block1, @block2
+    block1:
+        addu r1, r1, r0
+        subu r0, r0, r2
+        jump @block0
+    block2:
+        return r1
+    ");
+    // unwrap_or_zero(o: Option) -> u32
+    function(&mut file, vec![MaruTypeTag::Object(0)], MaruTypeTag::U32, 2, "
+        match r0 { 0 -> @block0, 1 -> @block1 }
+    block0:
+        load32 r1, 0
+        return r1
+    block1:
+        copyfield r1, r0, 0
+        call 0 (r1)
+        loadreturn r1
+        return r1
+    ");
+    file.add_function(MaruFunction {
+        name: 0,
+        type_name: 0,
+        parameters: vec![],
+        return_type: MaruTypeTag::Unit,
+        bytecode_index: -1,
+        variables: 0,
+    });
+    assert_eq!(verify(&file), Ok(()));
+}
+
+#[test]
+fn test_verify_decodes_compact_files() {
+    const SOURCE: &str = "
+        load32 r1, 0
+    block0:
+        if r0, @block1, @block2
+    block1:
+        addu r1, r1, r1
+        jump @block0
+    block2:
+        return r1
+    ";
+    let mut file = module();
+    file.minor_version = COMPACT_MINOR_VERSION;
+    let bytecode_index = file.add_bytecode(assemble_with(SOURCE, Encoding::Compact).expect("assemble"));
+    file.add_function(MaruFunction {
+        name: 0,
+        type_name: 0,
+        parameters: vec![MaruTypeTag::Bool],
+        return_type: MaruTypeTag::U32,
+        bytecode_index,
+        variables: 2,
+    });
+    assert_eq!(verify(&file), Ok(()));
+
+    // The fixed encoding of the same function doesn't decode as compact
+    file.bytecode_table.entries[0] = assemble(SOURCE).expect("assemble");
+    assert!(verify(&file).is_err());
+}
+
+#[test]
+fn test_verify_reports_type_errors_with_offsets() {
+    let mut file = module();
+    function(&mut file, vec![MaruTypeTag::U32], MaruTypeTag::F64, 3, "
+        addf r1, r0, r0
+        setglobal g0, r0
+        createobject r1, 4, 1
+        placefield r1, 0, r0
+        loadf64 r2, 1.0
+        call 0 (r2)
+        return r0
+    ");
+    assert_eq!(errors(verify(&file)), vec![
+        (0, 0, VerifyError::TypeMismatch { instruction: Instruction::AddF, register: 0, expected: None, found: VmType::U32 }),
+        (0, 13, VerifyError::TypeMismatch {
+            instruction: Instruction::SetGlobal,
+            register: 0,
+            expected: Some(VmType::I64),
+            found: VmType::U32,
+        }),
+        // `setglobal` moved r0 out
+        (0, 35, VerifyError::TypeMismatch {
+            instruction: Instruction::PlaceField,
+            register: 0,
+            expected: Some(VmType::U32),
+            found: VmType::Unit,
+        }),
+        (0, 61, VerifyError::TypeMismatch {
+            instruction: Instruction::Call,
+            register: 2,
+            expected: Some(VmType::U32),
+            found: VmType::F64,
+        }),
+        (0, 75, VerifyError::ReturnMismatch { expected: VmType::F64, found: VmType::Unit }),
+    ]);
+}
+
+#[test]
+fn test_verify_reports_control_flow_errors() {
+    let mut file = module();
+    function(&mut file, vec![MaruTypeTag::Object(0)], MaruTypeTag::Unit, 1, "
+        match r0 { 0 -> @block0, 2 -> @block1 }
+    block0:
+        jump @block7
+    block1:
+        load8 r1, 0
+        if r0, @block0, @block1+2
+    ");
+    assert_eq!(errors(verify(&file)), vec![
+        (0, 0, VerifyError::UnknownTag { type_id: 4, tag: 2 }),
+        (0, 38, VerifyError::UnknownBlock(7)),
+        (0, 52, VerifyError::RegisterOutOfRange { register: 1, len: 1 }),
+        (0, 58, VerifyError::TypeMismatch {
+            instruction: Instruction::If,
+            register: 0,
+            expected: None,
+            found: VmType::Object(4),
+        }),
+        (0, 58, VerifyError::BranchOutOfRange { block_id: 1, offset: 2 }),
+    ]);
+
+    let mut file = module();
+    function(&mut file, vec![MaruTypeTag::Bool], MaruTypeTag::Unit, 1, "
+        if r0, @block0, @block1
+    block0:
+        returnunit
+    block1:
+        clear r0
+    ");
+    assert_eq!(errors(verify(&file)), vec![(0, 32, VerifyError::FallsOffEnd)]);
+}
+
+#[test]
+fn test_verify_checks_calls_to_imported_functions() {
+    let mut file = module();
+    let (std, print) = (file.add_string("std".to_string()), file.add_string("print".to_string()));
+    let signature = MaruSignature::Function { parameters: vec![MaruTypeTag::U32], return_type: MaruTypeTag::Unit };
+    function(&mut file, vec![], MaruTypeTag::Unit, 2, "
+        loadf64 r1, 1.0
+        call 1 (r1)
+        call 2 ()
+        returnunit
+    ");
+    assert_eq!(file.add_import(MaruImport { module: std, name: print, signature }), Some(1));
+    let errors = errors(verify(&file)).into_iter().map(|(_, _, error)| error).collect::<Vec<_>>();
+    assert_eq!(errors, vec![
+        VerifyError::TypeMismatch { instruction: Instruction::Call, register: 1, expected: Some(VmType::U32), found: VmType::F64 },
+        VerifyError::UnknownFunction(2),
+    ]);
+}
@@ -15,6 +15,12 @@ use std::sync::atomic::{AtomicIsize, Ordering};
 #[repr(C)]
 pub struct RefCounter(AtomicIsize);
 
+impl Default for RefCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 
 impl RefCounter {
     